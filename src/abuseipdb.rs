@@ -0,0 +1,100 @@
+use std::io::{Error, Result};
+
+/// Where the API key is read from (`--abuseipdb`).
+pub const KEY_ENV: &str = "RUPERT_ABUSEIPDB_KEY";
+
+const CHECK_URL: &str = "https://api.abuseipdb.com/api/v2/check";
+const REPORT_URL: &str = "https://api.abuseipdb.com/api/v2/report";
+
+/// How far back reports are counted towards an address's confidence score, in days.
+const MAX_AGE: u32 = 90;
+
+/// The least time between two requests, to stay within the API's rate limits.
+const INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The AbuseIPDB categories offenders are reported under.
+pub const CATEGORY_DDOS: u32 = 4;
+pub const CATEGORY_PORT_SCAN: u32 = 14;
+pub const CATEGORY_HACKING: u32 = 15;
+
+fn api_error<E>(error: E) -> Error
+where
+  E: std::fmt::Display,
+{
+  Error::other(format!("abuseipdb - {error}"))
+}
+
+/// Checks (and, with `--report`, reports) addresses with AbuseIPDB, one request at a time.
+#[derive(Debug)]
+pub struct Client {
+  key: String,
+  last: Option<std::time::Instant>,
+}
+
+impl Client {
+  pub fn new(key: String) -> Self {
+    Self { key, last: None }
+  }
+
+  /// Waits out what's left of the `INTERVAL` since the last request.
+  async fn pace(&mut self) {
+    if let Some(wait) = self.last.and_then(|last| INTERVAL.checked_sub(last.elapsed())) {
+      async_std::task::sleep(wait).await;
+    }
+
+    self.last = Some(std::time::Instant::now());
+  }
+
+  async fn send(&mut self, request: surf::RequestBuilder) -> Result<serde_json::Value> {
+    self.pace().await;
+
+    let mut response = request
+      .header("Key", self.key.as_str())
+      .header("Accept", "application/json")
+      .await
+      .map_err(api_error)?;
+
+    if !response.status().is_success() {
+      return Err(api_error(format!("responded with {}", response.status())));
+    }
+
+    response.body_json().await.map_err(api_error)
+  }
+
+  /// The confidence (0 to 100) that `address` is abusive, from what's been reported of it.
+  pub async fn check(&mut self, address: &str) -> Result<u32> {
+    let mut url = surf::Url::parse(CHECK_URL).map_err(api_error)?;
+    url
+      .query_pairs_mut()
+      .append_pair("ipAddress", address)
+      .append_pair("maxAgeInDays", &MAX_AGE.to_string());
+
+    let body = self.send(surf::get(url)).await?;
+    let score = body["data"]["abuseConfidenceScore"]
+      .as_u64()
+      .ok_or_else(|| api_error(format!("no confidence score for '{address}'")))?;
+
+    Ok(score as u32)
+  }
+
+  /// Reports `address` under `categories`, with a `comment` on what it did.
+  pub async fn report(&mut self, address: &str, categories: &[u32], comment: &str) -> Result<()> {
+    let categories = categories
+      .iter()
+      .map(|category| category.to_string())
+      .collect::<Vec<_>>()
+      .join(",");
+    // The form is encoded as a query would be, and sent as the body.
+    let mut form = surf::Url::parse(REPORT_URL).map_err(api_error)?;
+    form
+      .query_pairs_mut()
+      .append_pair("ip", address)
+      .append_pair("categories", &categories)
+      .append_pair("comment", comment);
+    let request = surf::post(REPORT_URL)
+      .body_string(form.query().unwrap_or_default().to_string())
+      .content_type("application/x-www-form-urlencoded");
+
+    self.send(request).await.map(|_| ())
+  }
+}
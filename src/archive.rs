@@ -0,0 +1,120 @@
+use std::io::{Error, Read, Result};
+use std::path::PathBuf;
+
+use async_std::channel;
+
+const ZIP_EXTENSION: &str = ".zip";
+const TAR_EXTENSION: &str = ".tar";
+const TAR_GZ_EXTENSIONS: [&str; 2] = [".tar.gz", ".tgz"];
+
+/// A single file pulled out of an archive, read fully into memory so it can be handed to the parser.
+pub struct Member {
+  pub name: String,
+  pub contents: Vec<u8>,
+}
+
+/// The archive formats we know how to look inside of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+  Zip,
+  Tar,
+  TarGz,
+}
+
+impl Kind {
+  pub fn detect<P>(path: P) -> Option<Self>
+  where
+    P: std::convert::AsRef<std::path::Path>,
+  {
+    let name = path.as_ref().file_name()?.to_string_lossy().to_lowercase();
+
+    if name.ends_with(ZIP_EXTENSION) {
+      Some(Self::Zip)
+    } else if name.ends_with(TAR_EXTENSION) {
+      Some(Self::Tar)
+    } else if TAR_GZ_EXTENSIONS.iter().any(|extension| name.ends_with(extension)) {
+      Some(Self::TarGz)
+    } else {
+      None
+    }
+  }
+}
+
+fn send(sender: &channel::Sender<Result<Member>>, member: Result<Member>) -> bool {
+  async_std::task::block_on(sender.send(member)).is_ok()
+}
+
+fn read_zip(path: PathBuf, sender: channel::Sender<Result<Member>>) -> Result<()> {
+  let file = std::fs::File::open(&path)?;
+  let mut archive = zip::ZipArchive::new(file).map_err(|error| Error::other(format!("{path:?} - {error}")))?;
+
+  for index in 0..archive.len() {
+    let mut entry = archive
+      .by_index(index)
+      .map_err(|error| Error::other(format!("{path:?} - {error}")))?;
+
+    if entry.is_dir() {
+      continue;
+    }
+
+    let name = entry
+      .name()
+      .map_err(|error| Error::other(format!("{path:?} - {error}")))?
+      .to_string();
+    let mut contents = Vec::with_capacity(entry.size() as usize);
+    let member = entry.read_to_end(&mut contents).map(|_| Member { name, contents });
+
+    if !send(&sender, member) {
+      break;
+    }
+  }
+
+  Ok(())
+}
+
+fn read_tar<R>(path: PathBuf, reader: R, sender: channel::Sender<Result<Member>>) -> Result<()>
+where
+  R: Read,
+{
+  let mut archive = tar::Archive::new(reader);
+
+  for entry in archive.entries()? {
+    let mut entry = entry.map_err(|error| Error::other(format!("{path:?} - {error}")))?;
+
+    if !entry.header().entry_type().is_file() {
+      continue;
+    }
+
+    let name = entry.path()?.to_string_lossy().to_string();
+    let mut contents = Vec::with_capacity(entry.size() as usize);
+    let member = entry.read_to_end(&mut contents).map(|_| Member { name, contents });
+
+    if !send(&sender, member) {
+      break;
+    }
+  }
+
+  Ok(())
+}
+
+/// Opens the archive on a dedicated thread, streaming its members back one at a time. Members are sent through a
+/// bounded channel so that large archives are never held in memory all at once.
+pub fn members(path: PathBuf, kind: Kind) -> channel::Receiver<Result<Member>> {
+  let (sender, receiver) = channel::bounded(1);
+
+  std::thread::spawn(move || {
+    let result = match kind {
+      Kind::Zip => read_zip(path, sender.clone()),
+      Kind::Tar => std::fs::File::open(&path).and_then(|file| read_tar(path, file, sender.clone())),
+      Kind::TarGz => {
+        std::fs::File::open(&path).and_then(|file| read_tar(path, flate2::read::GzDecoder::new(file), sender.clone()))
+      }
+    };
+
+    if let Err(error) = result {
+      send(&sender, Err(error));
+    }
+  });
+
+  receiver
+}
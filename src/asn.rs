@@ -0,0 +1,40 @@
+use std::io::{Error, Result};
+
+/// The autonomous system an address is routed by, e.g. `AS16276 OVH SAS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct System {
+  pub number: u32,
+  pub organization: Option<String>,
+}
+
+impl std::fmt::Display for System {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.organization.as_deref() {
+      Some(organization) => write!(formatter, "AS{} {organization}", self.number),
+      None => write!(formatter, "AS{}", self.number),
+    }
+  }
+}
+
+/// A GeoLite2 (or GeoIP2) ASN database (`--asn`), looked up locally.
+#[derive(Debug)]
+pub struct Asn(maxminddb::Reader<Vec<u8>>);
+
+impl Asn {
+  pub fn open(path: &str) -> Result<Self> {
+    maxminddb::Reader::open_readfile(path)
+      .map(Self)
+      .map_err(|error| Error::other(format!("invalid '--asn' '{path}' - {error}")))
+  }
+
+  /// The system `address` belongs to; an `--aggregate`d network is looked up by its first address.
+  pub fn system(&self, address: &str) -> Option<System> {
+    let address = address.split('/').next()?.parse().ok()?;
+    let record = self.0.lookup(address).ok()?.decode::<maxminddb::geoip2::Asn>().ok()??;
+
+    Some(System {
+      number: record.autonomous_system_number?,
+      organization: record.autonomous_system_organization.map(str::to_string),
+    })
+  }
+}
@@ -0,0 +1,107 @@
+use std::io::{Error, Result};
+
+use async_std::channel;
+
+use crate::archive::Member;
+
+pub const SCHEME: &str = "s3://";
+
+/// Region used when talking to an S3-compatible endpoint that doesn't care about regions.
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// Number of objects downloaded at the same time.
+const CONCURRENT_DOWNLOADS: usize = 4;
+
+/// Where to find the bucket; credentials come from the usual `AWS_*` environment variables or profile.
+#[derive(Debug, Default)]
+pub struct BucketConfig {
+  /// An S3-compatible endpoint (e.g. minio) to use instead of AWS, addressed path-style.
+  pub endpoint: Option<String>,
+  pub region: Option<String>,
+}
+
+fn s3_error<E>(error: E) -> Error
+where
+  E: std::fmt::Display,
+{
+  Error::other(format!("s3 - {error}"))
+}
+
+/// Splits an `s3://bucket/prefix` input into its bucket and key prefix.
+pub fn location(input: &str) -> Option<(&str, &str)> {
+  let rest = input.strip_prefix(SCHEME)?;
+  let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+  Some((bucket, prefix)).filter(|(bucket, _)| !bucket.is_empty())
+}
+
+fn bucket(name: &str, config: &BucketConfig) -> Result<Box<s3::Bucket>> {
+  let credentials = s3::creds::Credentials::default().map_err(s3_error)?;
+
+  let region = match (config.endpoint.as_ref(), config.region.as_ref()) {
+    (Some(endpoint), region) => s3::Region::Custom {
+      region: region.cloned().unwrap_or_else(|| DEFAULT_REGION.to_string()),
+      endpoint: endpoint.clone(),
+    },
+    (None, Some(region)) => region.parse().map_err(s3_error)?,
+    (None, None) => s3::Region::from_default_env().unwrap_or(s3::Region::UsEast1),
+  };
+
+  let bucket = s3::Bucket::new(name, region, credentials).map_err(s3_error)?;
+
+  match config.endpoint.is_some() {
+    true => Ok(bucket.with_path_style()),
+    false => Ok(bucket),
+  }
+}
+
+async fn download(bucket: Box<s3::Bucket>, keys: channel::Receiver<String>, output: channel::Sender<Result<Member>>) {
+  while let Ok(key) = keys.recv().await {
+    let member = bucket
+      .get_object(&key)
+      .await
+      .map(|response| Member {
+        name: key.clone(),
+        contents: response.bytes().to_vec(),
+      })
+      .map_err(s3_error);
+
+    if output.send(member).await.is_err() {
+      break;
+    }
+  }
+}
+
+/// Lists every object under the input's prefix and downloads them concurrently, sending each back as it
+/// completes.
+pub fn objects(input: &str, config: &BucketConfig) -> Result<channel::Receiver<Result<Member>>> {
+  let (name, prefix) = location(input).ok_or_else(|| Error::other(format!("invalid s3 location '{input}'")))?;
+  let bucket = bucket(name, config)?;
+  let prefix = prefix.to_string();
+
+  let (sender, receiver) = channel::bounded(CONCURRENT_DOWNLOADS);
+
+  async_std::task::spawn(async move {
+    let pages = match bucket.list(prefix, None).await {
+      Ok(pages) => pages,
+      Err(error) => {
+        let _ = sender.send(Err(s3_error(error))).await;
+        return;
+      }
+    };
+
+    let (keys, pending) = channel::unbounded();
+
+    for object in pages.into_iter().flat_map(|page| page.contents) {
+      let _ = keys.send(object.key).await;
+    }
+
+    // Closing our side lets the workers finish once every key has been taken.
+    drop(keys);
+
+    for _ in 0..CONCURRENT_DOWNLOADS {
+      async_std::task::spawn(download(bucket.clone(), pending.clone(), sender.clone()));
+    }
+  });
+
+  Ok(receiver)
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::io::{Error, Result};
+
+use crate::whois;
+
+/// How long each kind of result is trusted for: names and reputations change, registrations hardly ever do.
+const NAME_TTL: i64 = 24 * 60 * 60;
+const REPUTATION_TTL: i64 = 24 * 60 * 60;
+const REGISTRATION_TTL: i64 = 30 * 24 * 60 * 60;
+
+/// The results of the online lookups (`--resolve`, `--whois`, `--abuseipdb`), kept in a file between runs
+/// (`--cache`) so going over the same addresses again doesn't ask the same services the same questions. Each is
+/// one `KIND<tab>ADDRESS<tab>STORED<tab>VALUE` line, `STORED` being when it was looked up in seconds since the
+/// epoch; an empty `VALUE` is a lookup that found nothing. The GeoIP and ASN databases are local, so they
+/// aren't cached.
+#[derive(Debug, Default)]
+pub struct Cache {
+  path: std::path::PathBuf,
+  entries: HashMap<(String, String), (i64, String)>,
+}
+
+fn now() -> i64 {
+  std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_secs() as i64)
+    .unwrap_or_default()
+}
+
+fn ttl(kind: &str) -> i64 {
+  match kind {
+    "name" => NAME_TTL,
+    "reputation" => REPUTATION_TTL,
+    _ => REGISTRATION_TTL,
+  }
+}
+
+/// The kind, address, time stored and value of a line.
+fn entry(line: &str) -> Option<(String, String, i64, String)> {
+  let mut fields = line.splitn(4, '\t');
+  let (kind, address) = (fields.next()?, fields.next()?);
+  let stored = fields.next()?.parse().ok()?;
+  Some((
+    kind.to_string(),
+    address.to_string(),
+    stored,
+    fields.next()?.to_string(),
+  ))
+}
+
+impl Cache {
+  /// Reads the cache at `path`, leaving out whatever has expired; a file that doesn't exist yet is an empty cache.
+  pub fn load(path: &str) -> Result<Self> {
+    let contents = match std::fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+      Err(error) => return Err(Error::other(format!("invalid '--cache' '{path}' - {error}"))),
+    };
+
+    let now = now();
+    let mut entries = HashMap::new();
+
+    for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+      let (kind, address, stored, value) =
+        entry(line).ok_or_else(|| Error::other(format!("invalid '--cache' '{path}' line {}", number + 1)))?;
+
+      if now - stored < ttl(&kind) {
+        entries.insert((kind, address), (stored, value));
+      }
+    }
+
+    Ok(Self {
+      path: path.into(),
+      entries,
+    })
+  }
+
+  fn get(&self, kind: &str, address: &str) -> Option<&str> {
+    let (_, value) = self.entries.get(&(kind.to_string(), address.to_string()))?;
+    Some(value)
+  }
+
+  fn set(&mut self, kind: &str, address: &str, value: String) {
+    self
+      .entries
+      .insert((kind.to_string(), address.to_string()), (now(), value));
+  }
+
+  /// The host name `address` had, `Some(None)` if it was known to have none, or `None` if it isn't cached.
+  pub fn name(&self, address: &str) -> Option<Option<String>> {
+    self
+      .get("name", address)
+      .map(|name| Some(name.to_string()).filter(|name| !name.is_empty()))
+  }
+
+  pub fn set_name(&mut self, address: &str, name: Option<&String>) {
+    self.set("name", address, name.cloned().unwrap_or_default());
+  }
+
+  /// What the registry had for `address`, as its netname, organization and abuse contact.
+  pub fn registration(&self, address: &str) -> Option<whois::Record> {
+    let mut fields = self
+      .get("registration", address)?
+      .split('\t')
+      .map(|field| Some(field.to_string()).filter(|field| !field.is_empty()));
+
+    Some(whois::Record {
+      netname: fields.next().flatten(),
+      organization: fields.next().flatten(),
+      abuse: fields.next().flatten(),
+    })
+  }
+
+  pub fn set_registration(&mut self, address: &str, record: &whois::Record) {
+    let fields = [&record.netname, &record.organization, &record.abuse]
+      .map(|field| field.as_deref().unwrap_or_default().replace(['\t', '\n'], " "));
+    self.set("registration", address, fields.join("\t"));
+  }
+
+  /// The AbuseIPDB confidence score of `address`.
+  pub fn reputation(&self, address: &str) -> Option<u32> {
+    self.get("reputation", address)?.parse().ok()
+  }
+
+  pub fn set_reputation(&mut self, address: &str, score: u32) {
+    self.set("reputation", address, score.to_string());
+  }
+
+  /// Writes the cache back out.
+  pub fn save(&self) -> Result<()> {
+    let mut lines = self
+      .entries
+      .iter()
+      .map(|((kind, address), (stored, value))| format!("{kind}\t{address}\t{stored}\t{value}\n"))
+      .collect::<Vec<_>>();
+    lines.sort();
+
+    std::fs::write(&self.path, lines.concat())
+      .map_err(|error| Error::other(format!("invalid '--cache' '{:?}' - {error}", self.path)))
+  }
+}
@@ -0,0 +1,610 @@
+#[derive(Debug, Default, Clone)]
+pub struct CommandLineOption<T> {
+  pub parsed: bool,
+  pub value: Option<T>,
+}
+
+impl<T> CommandLineOption<T> {
+  fn store(self, value: T) -> Self {
+    Self {
+      parsed: false,
+      value: Some(value),
+    }
+  }
+}
+
+impl<T> CommandLineOption<Vec<T>> {
+  fn append(mut self, value: T) -> Self {
+    let mut values = self.value.take().unwrap_or_default();
+    values.push(value);
+    self.store(values)
+  }
+}
+
+/// The mode rupert runs in, selected by the first argument.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+  /// Parse local files, directories and archives (the default).
+  #[default]
+  Scan,
+
+  /// Download messages from a mail server and parse them.
+  Fetch,
+
+  /// Receive log entries from the router over syslog.
+  Listen,
+
+  /// Parse two inputs (e.g. last week's directory and this week's, or the files two runs wrote with `--output json`
+  /// or `--output csv`) and compare what was found in each.
+  Diff,
+
+  /// Parse like `scan`, and write the report as a page to send on (`--html`).
+  Report,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CommandLineOptions {
+  pub command: Command,
+
+  /// The two inputs compared by `diff`, earlier first.
+  pub diff: Option<(String, String)>,
+  pub inputs: CommandLineOption<Vec<String>>,
+  pub recursive: bool,
+  pub glob: CommandLineOption<String>,
+  pub host: CommandLineOption<String>,
+  pub port: CommandLineOption<String>,
+  pub user: CommandLineOption<String>,
+  pub folder: CommandLineOption<String>,
+  pub from: CommandLineOption<String>,
+  pub subject: CommandLineOption<String>,
+  pub cache_dir: CommandLineOption<String>,
+  pub protocol: CommandLineOption<String>,
+  pub delete: bool,
+  pub watch: bool,
+  pub syslog: CommandLineOption<String>,
+  pub journal_unit: CommandLineOption<String>,
+  pub journal_identifier: CommandLineOption<String>,
+  pub s3_endpoint: CommandLineOption<String>,
+  pub s3_region: CommandLineOption<String>,
+  pub follow: CommandLineOption<String>,
+  pub raw: bool,
+  pub filename_since: CommandLineOption<String>,
+  pub filename_until: CommandLineOption<String>,
+  pub filename_pattern: CommandLineOption<String>,
+  pub exclude: CommandLineOption<Vec<String>>,
+  pub no_follow_symlinks: bool,
+  pub input_lists: CommandLineOption<Vec<String>>,
+  pub verify: bool,
+  pub verify_spf: bool,
+  pub verbose: bool,
+  pub format: CommandLineOption<String>,
+  pub patterns: CommandLineOption<Vec<String>>,
+  pub strict: bool,
+  pub unmatched_out: CommandLineOption<String>,
+  pub group_by: CommandLineOption<Vec<String>>,
+  pub bucket: CommandLineOption<String>,
+  pub top: CommandLineOption<String>,
+  pub min_count: CommandLineOption<String>,
+  pub aggregate: CommandLineOption<String>,
+  pub history: CommandLineOption<String>,
+  pub session_gap: CommandLineOption<String>,
+  pub since: CommandLineOption<String>,
+  pub until: CommandLineOption<String>,
+  pub ignore_ips: CommandLineOption<Vec<String>>,
+  pub ignore_cidrs: CommandLineOption<Vec<String>>,
+  pub ignore_files: CommandLineOption<Vec<String>>,
+  pub rollups: CommandLineOption<Vec<String>>,
+  pub heatmap: CommandLineOption<String>,
+  pub compare: CommandLineOption<String>,
+  pub resolve: bool,
+  pub geoip: CommandLineOption<String>,
+  pub asn: CommandLineOption<String>,
+  pub whois: CommandLineOption<String>,
+  pub abuseipdb: bool,
+  pub report: bool,
+  pub blocklists: CommandLineOption<Vec<String>>,
+  pub tor: CommandLineOption<String>,
+  pub clouds: CommandLineOption<Vec<String>>,
+  pub cache: CommandLineOption<String>,
+  pub annotations: CommandLineOption<Vec<String>>,
+  pub dnsbls: CommandLineOption<Vec<String>>,
+  pub output: CommandLineOption<String>,
+  pub events: CommandLineOption<String>,
+  pub html: CommandLineOption<String>,
+}
+
+impl CommandLineOptions {
+  pub fn from_args<I>(args: I) -> Self
+  where
+    I: std::iter::Iterator<Item = String>,
+  {
+    let mut args = args.skip(1).peekable();
+
+    let command = match args.peek().map(String::as_str) {
+      Some("fetch") => Command::Fetch,
+      Some("listen") => Command::Listen,
+      Some("diff") => Command::Diff,
+      Some("report") => Command::Report,
+      _ => Command::Scan,
+    };
+
+    if command != Command::Scan {
+      args.next();
+    }
+
+    let diff = match command {
+      Command::Diff => args.next().zip(args.next()),
+      _ => None,
+    };
+
+    let initial = CommandLineOptions {
+      command,
+      diff,
+      ..CommandLineOptions::default()
+    };
+
+    args.fold(initial, |mut opts, item| {
+      if opts.inputs.parsed {
+        opts.inputs = opts.inputs.append(item.clone());
+      }
+
+      if item == "--input" || item == "--input-dir" {
+        opts.inputs.parsed = true;
+      }
+
+      if opts.glob.parsed {
+        opts.glob = opts.glob.store(item.clone());
+      }
+
+      if item == "--glob" {
+        opts.glob.parsed = true;
+      }
+
+      if item == "--recursive" {
+        opts.recursive = true;
+      }
+
+      if opts.host.parsed {
+        opts.host = opts.host.store(item.clone());
+      }
+
+      if item == "--host" {
+        opts.host.parsed = true;
+      }
+
+      if opts.port.parsed {
+        opts.port = opts.port.store(item.clone());
+      }
+
+      if item == "--port" {
+        opts.port.parsed = true;
+      }
+
+      if opts.user.parsed {
+        opts.user = opts.user.store(item.clone());
+      }
+
+      if item == "--user" {
+        opts.user.parsed = true;
+      }
+
+      if opts.folder.parsed {
+        opts.folder = opts.folder.store(item.clone());
+      }
+
+      if item == "--folder" {
+        opts.folder.parsed = true;
+      }
+
+      if opts.from.parsed {
+        opts.from = opts.from.store(item.clone());
+      }
+
+      if item == "--from" {
+        opts.from.parsed = true;
+      }
+
+      if opts.subject.parsed {
+        opts.subject = opts.subject.store(item.clone());
+      }
+
+      if item == "--subject" {
+        opts.subject.parsed = true;
+      }
+
+      if opts.cache_dir.parsed {
+        opts.cache_dir = opts.cache_dir.store(item.clone());
+      }
+
+      if item == "--cache-dir" {
+        opts.cache_dir.parsed = true;
+      }
+
+      if opts.protocol.parsed {
+        opts.protocol = opts.protocol.store(item.clone());
+      }
+
+      if item == "--protocol" {
+        opts.protocol.parsed = true;
+      }
+
+      if item == "--delete" {
+        opts.delete = true;
+      }
+
+      if item == "--watch" {
+        opts.watch = true;
+      }
+
+      if opts.syslog.parsed {
+        opts.syslog = opts.syslog.store(item.clone());
+      }
+
+      if item == "--syslog" {
+        opts.syslog.parsed = true;
+      }
+
+      if opts.journal_unit.parsed {
+        opts.journal_unit = opts.journal_unit.store(item.clone());
+      }
+
+      if item == "--journal-unit" {
+        opts.journal_unit.parsed = true;
+      }
+
+      if opts.journal_identifier.parsed {
+        opts.journal_identifier = opts.journal_identifier.store(item.clone());
+      }
+
+      if item == "--journal-identifier" {
+        opts.journal_identifier.parsed = true;
+      }
+
+      if opts.s3_endpoint.parsed {
+        opts.s3_endpoint = opts.s3_endpoint.store(item.clone());
+      }
+
+      if item == "--s3-endpoint" {
+        opts.s3_endpoint.parsed = true;
+      }
+
+      if opts.s3_region.parsed {
+        opts.s3_region = opts.s3_region.store(item.clone());
+      }
+
+      if item == "--s3-region" {
+        opts.s3_region.parsed = true;
+      }
+
+      if opts.follow.parsed {
+        opts.follow = opts.follow.store(item.clone());
+      }
+
+      if item == "--follow" {
+        opts.follow.parsed = true;
+      }
+
+      if item == "--raw" {
+        opts.raw = true;
+      }
+
+      if opts.filename_since.parsed {
+        opts.filename_since = opts.filename_since.store(item.clone());
+      }
+
+      if item == "--filename-since" {
+        opts.filename_since.parsed = true;
+      }
+
+      if opts.filename_until.parsed {
+        opts.filename_until = opts.filename_until.store(item.clone());
+      }
+
+      if item == "--filename-until" {
+        opts.filename_until.parsed = true;
+      }
+
+      if opts.filename_pattern.parsed {
+        opts.filename_pattern = opts.filename_pattern.store(item.clone());
+      }
+
+      if item == "--filename-pattern" {
+        opts.filename_pattern.parsed = true;
+      }
+
+      if opts.exclude.parsed {
+        opts.exclude = opts.exclude.append(item.clone());
+      }
+
+      if item == "--exclude" {
+        opts.exclude.parsed = true;
+      }
+
+      if item == "--follow-symlinks" {
+        opts.no_follow_symlinks = false;
+      }
+
+      if item == "--no-follow-symlinks" {
+        opts.no_follow_symlinks = true;
+      }
+
+      if opts.input_lists.parsed {
+        opts.input_lists = opts.input_lists.append(item.clone());
+      }
+
+      if item == "--input-list" {
+        opts.input_lists.parsed = true;
+      }
+
+      if item == "--verify" {
+        opts.verify = true;
+      }
+
+      if item == "--verify-spf" {
+        opts.verify_spf = true;
+      }
+
+      if item == "--verbose" {
+        opts.verbose = true;
+      }
+
+      if opts.format.parsed {
+        opts.format = opts.format.store(item.clone());
+      }
+
+      if item == "--format" {
+        opts.format.parsed = true;
+      }
+
+      if opts.patterns.parsed {
+        opts.patterns = opts.patterns.append(item.clone());
+      }
+
+      if item == "--patterns" {
+        opts.patterns.parsed = true;
+      }
+
+      if item == "--strict" {
+        opts.strict = true;
+      }
+
+      if opts.unmatched_out.parsed {
+        opts.unmatched_out = opts.unmatched_out.store(item.clone());
+      }
+
+      if item == "--unmatched-out" {
+        opts.unmatched_out.parsed = true;
+      }
+
+      if opts.group_by.parsed {
+        opts.group_by = opts.group_by.append(item.clone());
+      }
+
+      if item == "--group-by" {
+        opts.group_by.parsed = true;
+      }
+
+      if opts.bucket.parsed {
+        opts.bucket = opts.bucket.store(item.clone());
+      }
+
+      if item == "--bucket" {
+        opts.bucket.parsed = true;
+      }
+
+      if opts.top.parsed {
+        opts.top = opts.top.store(item.clone());
+      }
+
+      if item == "--top" {
+        opts.top.parsed = true;
+      }
+
+      if opts.min_count.parsed {
+        opts.min_count = opts.min_count.store(item.clone());
+      }
+
+      if item == "--min-count" {
+        opts.min_count.parsed = true;
+      }
+
+      if opts.aggregate.parsed {
+        opts.aggregate = opts.aggregate.store(item.clone());
+      }
+
+      if item == "--aggregate" {
+        opts.aggregate.parsed = true;
+      }
+
+      if opts.history.parsed {
+        opts.history = opts.history.store(item.clone());
+      }
+
+      if item == "--history" {
+        opts.history.parsed = true;
+      }
+
+      if opts.session_gap.parsed {
+        opts.session_gap = opts.session_gap.store(item.clone());
+      }
+
+      if item == "--session-gap" {
+        opts.session_gap.parsed = true;
+      }
+
+      if opts.since.parsed {
+        opts.since = opts.since.store(item.clone());
+      }
+
+      if item == "--since" {
+        opts.since.parsed = true;
+      }
+
+      if opts.until.parsed {
+        opts.until = opts.until.store(item.clone());
+      }
+
+      if item == "--until" {
+        opts.until.parsed = true;
+      }
+
+      if opts.ignore_ips.parsed {
+        opts.ignore_ips = opts.ignore_ips.append(item.clone());
+      }
+
+      if item == "--ignore-ip" {
+        opts.ignore_ips.parsed = true;
+      }
+
+      if opts.ignore_cidrs.parsed {
+        opts.ignore_cidrs = opts.ignore_cidrs.append(item.clone());
+      }
+
+      if item == "--ignore-cidr" {
+        opts.ignore_cidrs.parsed = true;
+      }
+
+      if opts.ignore_files.parsed {
+        opts.ignore_files = opts.ignore_files.append(item.clone());
+      }
+
+      if item == "--ignore-file" {
+        opts.ignore_files.parsed = true;
+      }
+
+      if opts.rollups.parsed {
+        opts.rollups = opts.rollups.append(item.clone());
+      }
+
+      if item == "--rollup" {
+        opts.rollups.parsed = true;
+      }
+
+      if opts.heatmap.parsed {
+        opts.heatmap = opts.heatmap.store(item.clone());
+      }
+
+      if item == "--heatmap" {
+        opts.heatmap.parsed = true;
+      }
+
+      if opts.compare.parsed {
+        opts.compare = opts.compare.store(item.clone());
+      }
+
+      if item == "--compare" {
+        opts.compare.parsed = true;
+      }
+
+      if item == "--resolve" {
+        opts.resolve = true;
+      }
+
+      if item == "--abuseipdb" {
+        opts.abuseipdb = true;
+      }
+
+      if item == "--report" {
+        opts.report = true;
+      }
+
+      if opts.geoip.parsed {
+        opts.geoip = opts.geoip.store(item.clone());
+      }
+
+      if item == "--geoip" {
+        opts.geoip.parsed = true;
+      }
+
+      if opts.asn.parsed {
+        opts.asn = opts.asn.store(item.clone());
+      }
+
+      if item == "--asn" {
+        opts.asn.parsed = true;
+      }
+
+      if opts.blocklists.parsed {
+        opts.blocklists = opts.blocklists.append(item.clone());
+      }
+
+      if item == "--blocklist" {
+        opts.blocklists.parsed = true;
+      }
+
+      if opts.tor.parsed {
+        opts.tor = opts.tor.store(item.clone());
+      }
+
+      if item == "--tor" {
+        opts.tor.parsed = true;
+      }
+
+      if opts.clouds.parsed {
+        opts.clouds = opts.clouds.append(item.clone());
+      }
+
+      if item == "--cloud" {
+        opts.clouds.parsed = true;
+      }
+
+      if opts.cache.parsed {
+        opts.cache = opts.cache.store(item.clone());
+      }
+
+      if item == "--cache" {
+        opts.cache.parsed = true;
+      }
+
+      if opts.annotations.parsed {
+        opts.annotations = opts.annotations.append(item.clone());
+      }
+
+      if item == "--annotate" {
+        opts.annotations.parsed = true;
+      }
+
+      if opts.dnsbls.parsed {
+        opts.dnsbls = opts.dnsbls.append(item.clone());
+      }
+
+      if item == "--dnsbl" {
+        opts.dnsbls.parsed = true;
+      }
+
+      if opts.output.parsed {
+        opts.output = opts.output.store(item.clone());
+      }
+
+      if item == "--output" {
+        opts.output.parsed = true;
+      }
+
+      if opts.events.parsed {
+        opts.events = opts.events.store(item.clone());
+      }
+
+      if item == "--events" {
+        opts.events.parsed = true;
+      }
+
+      if opts.html.parsed {
+        opts.html = opts.html.store(item.clone());
+      }
+
+      if item == "--html" {
+        opts.html.parsed = true;
+      }
+
+      if opts.whois.parsed {
+        opts.whois = opts.whois.store(item.clone());
+      }
+
+      if item == "--whois" {
+        opts.whois.parsed = true;
+      }
+
+      opts
+    })
+  }
+}
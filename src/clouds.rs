@@ -0,0 +1,88 @@
+use std::io::{Error, Result};
+
+use crate::networks::{self, Network};
+
+/// The keys the networks are found under in each provider's published ranges, and what the provider is called.
+const FORMATS: [(&str, &str); 5] = [
+  // <https://ip-ranges.amazonaws.com/ip-ranges.json>
+  ("ip_prefix", "AWS"),
+  ("ipv6_prefix", "AWS"),
+  // <https://www.gstatic.com/ipranges/cloud.json>
+  ("ipv4Prefix", "GCP"),
+  ("ipv6Prefix", "GCP"),
+  // The weekly `ServiceTags_Public` download.
+  ("addressPrefixes", "Azure"),
+];
+
+/// Reads the address ranges a cloud provider publishes (`--cloud`), and what it's called in the report. The JSON
+/// of AWS, GCP and Azure is recognized; anything else is read as a list of networks, one per line, of which only
+/// the first comma separated field counts (as in DigitalOcean's CSV) and that is named after the file, e.g.
+/// `ovh` for `ovh.txt`. Writing `NAME=PATH` names the provider explicitly.
+pub fn load(value: &str) -> Result<(String, Vec<Network>)> {
+  let (name, path) = match value.split_once('=') {
+    Some((name, path)) => (Some(name.to_string()), path),
+    None => (None, value),
+  };
+  let invalid = |reason: String| Error::other(format!("invalid '--cloud' '{path}' - {reason}"));
+  let contents = std::fs::read_to_string(path).map_err(|error| invalid(format!("{error}")))?;
+
+  if let Ok(document) = serde_json::from_str::<serde_json::Value>(&contents) {
+    let mut prefixes = Vec::new();
+    let mut found = None;
+    collect(&document, None, &mut found, &mut prefixes);
+
+    let networks = prefixes
+      .iter()
+      .filter_map(|prefix| prefix.parse().ok())
+      .collect::<Vec<_>>();
+
+    return match (name.or(found.map(str::to_string)), networks.is_empty()) {
+      (_, true) => Err(invalid("no address ranges".into())),
+      (None, _) => Ok((networks::list_name(path), networks)),
+      (Some(name), _) => Ok((name, networks)),
+    };
+  }
+
+  let networks = contents
+    .lines()
+    .enumerate()
+    .map(|(index, line)| (index + 1, line.split(['#', ',']).next().unwrap_or_default().trim()))
+    .filter(|(_, line)| !line.is_empty())
+    .map(|(number, line)| {
+      line
+        .parse()
+        .map_err(|error| invalid(format!("line {number} - {error}")))
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  Ok((name.unwrap_or_else(|| networks::list_name(path)), networks))
+}
+
+/// Gathers the strings found under any of the [`FORMATS`] keys anywhere in `value`, noting which provider's format
+/// they were in.
+fn collect<'a>(
+  value: &'a serde_json::Value,
+  key: Option<&'a str>,
+  found: &mut Option<&'static str>,
+  prefixes: &mut Vec<&'a str>,
+) {
+  match value {
+    serde_json::Value::Object(entries) => {
+      for (name, value) in entries {
+        collect(value, Some(name.as_str()), found, prefixes);
+      }
+    }
+    serde_json::Value::Array(values) => {
+      for value in values {
+        collect(value, key, found, prefixes);
+      }
+    }
+    serde_json::Value::String(prefix) => {
+      if let Some((_, provider)) = FORMATS.iter().find(|(name, _)| Some(*name) == key) {
+        found.get_or_insert(provider);
+        prefixes.push(prefix);
+      }
+    }
+    _ => {}
+  }
+}
@@ -0,0 +1,507 @@
+use std::io::{Error, Result};
+
+/// The pattern `--filename-since`/`--filename-until` look for when no `--filename-pattern` is given.
+pub const DEFAULT_FILENAME_PATTERN: &str = "%Y-%m-%d";
+
+/// A calendar day; fields are ordered so that comparing two dates compares them chronologically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+  pub year: u32,
+  pub month: u32,
+  pub day: u32,
+}
+
+impl std::str::FromStr for Date {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    let invalid = || Error::other(format!("invalid date '{value}' (expected YYYY-MM-DD)"));
+    let mut parts = value
+      .splitn(3, '-')
+      .map(|part| part.parse::<u32>().map_err(|_| invalid()));
+
+    let date = Date {
+      year: parts.next().ok_or_else(invalid)??,
+      month: parts.next().ok_or_else(invalid)??,
+      day: parts.next().ok_or_else(invalid)??,
+    };
+
+    date.checked().ok_or_else(invalid)
+  }
+}
+
+impl Date {
+  /// The date, if its month has that day; e.g. not `Jan 45`, which would otherwise roll over into February when
+  /// measured with [`DateTime::seconds`], or `Feb 29` outside a leap year.
+  fn checked(self) -> Option<Self> {
+    let days = match self.month {
+      2 if self.year.is_multiple_of(4) && (!self.year.is_multiple_of(100) || self.year.is_multiple_of(400)) => 29,
+      2 => 28,
+      4 | 6 | 9 | 11 => 30,
+      1..=12 => 31,
+      _ => return None,
+    };
+
+    (1..=days).contains(&self.day).then_some(self)
+  }
+}
+
+/// One piece of a filename pattern: either a literal character or a fixed-width date field.
+#[derive(Debug, Clone, Copy)]
+enum Token {
+  Literal(char),
+  Year,
+  Month,
+  Day,
+}
+
+impl Token {
+  fn width(&self) -> usize {
+    match self {
+      Self::Literal(_) => 1,
+      Self::Month | Self::Day => 2,
+      Self::Year => 4,
+    }
+  }
+}
+
+/// Finds the date embedded in a filename, using `%Y`, `%m` and `%d` for the (zero padded) year, month and day,
+/// e.g. `%Y-%m-%d` for `2022-01-03-digest.eml` or `log_%Y%m%d` for `log_20220103.txt`.
+#[derive(Debug, Clone)]
+pub struct FilenamePattern {
+  tokens: Vec<Token>,
+}
+
+impl std::str::FromStr for FilenamePattern {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    let mut tokens = Vec::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(character) = chars.next() {
+      let token = match character {
+        '%' => match chars.next() {
+          Some('Y') => Token::Year,
+          Some('m') => Token::Month,
+          Some('d') => Token::Day,
+          Some('%') => Token::Literal('%'),
+          _ => {
+            return Err(Error::other(format!(
+              "invalid filename pattern '{value}' (expected '%Y', '%m' and '%d' fields)"
+            )))
+          }
+        },
+        other => Token::Literal(other),
+      };
+
+      tokens.push(token);
+    }
+
+    let fields = [Token::Year, Token::Month, Token::Day];
+    let complete = fields.iter().all(|field| {
+      tokens
+        .iter()
+        .any(|token| std::mem::discriminant(token) == std::mem::discriminant(field))
+    });
+
+    match complete {
+      true => Ok(Self { tokens }),
+      false => Err(Error::other(format!(
+        "invalid filename pattern '{value}' (needs each of '%Y', '%m' and '%d')"
+      ))),
+    }
+  }
+}
+
+impl FilenamePattern {
+  fn match_at(&self, name: &[char]) -> Option<Date> {
+    let (mut year, mut month, mut day) = (0, 0, 0);
+    let mut position = 0;
+
+    for token in &self.tokens {
+      let field = name.get(position..position + token.width())?;
+      position += token.width();
+
+      if let Token::Literal(literal) = token {
+        if field[0] != *literal {
+          return None;
+        }
+
+        continue;
+      }
+
+      let number = field
+        .iter()
+        .try_fold(0u32, |number, digit| Some(number * 10 + digit.to_digit(10)?))?;
+
+      match token {
+        Token::Year => year = number,
+        Token::Month => month = number,
+        _ => day = number,
+      }
+    }
+
+    Date { year, month, day }.checked()
+  }
+
+  /// The first date in `name` that matches the pattern, if any.
+  pub fn find(&self, name: &str) -> Option<Date> {
+    let chars = name.chars().collect::<Vec<char>>();
+    (0..chars.len()).find_map(|start| self.match_at(&chars[start..]))
+  }
+}
+
+/// Restricts which files are opened using the date in their name. Files without a recognisable date are kept,
+/// since there's no telling whether they are in range.
+#[derive(Debug, Clone)]
+pub struct FilenameDates {
+  pub pattern: FilenamePattern,
+  pub since: Option<Date>,
+  pub until: Option<Date>,
+}
+
+impl FilenameDates {
+  pub fn accept(&self, name: &str) -> bool {
+    let Some(date) = self.pattern.find(name) else {
+      return true;
+    };
+
+    self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+  }
+}
+
+const MONTHS: [&str; 12] = [
+  "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// The month number for an English month name or abbreviation (`Jan`, `january`...).
+fn month(name: &str) -> Option<u32> {
+  let prefix = name.get(..3)?.to_ascii_lowercase();
+  MONTHS
+    .iter()
+    .position(|month| *month == prefix)
+    .map(|index| index as u32 + 1)
+}
+
+/// A `HH:MM[:SS]` time of day.
+fn time(value: &str) -> Option<(u32, u32, u32)> {
+  let mut parts = value.split(':').map(|part| part.parse::<u32>().ok());
+  let (hour, minute) = (parts.next()??, parts.next()??);
+  let second = parts.next().unwrap_or(Some(0))?;
+  (hour < 24 && minute < 60 && second < 61).then_some((hour, minute, second))
+}
+
+/// A point in time as the router (or mailer) wrote it, in its own local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+  pub date: Date,
+  pub hour: u32,
+  pub minute: u32,
+  pub second: u32,
+}
+
+/// Writes a number of seconds as e.g. `3d 4h 12m`, leaving out leading zero units.
+pub fn duration(seconds: i64) -> String {
+  let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+
+  match (days, hours) {
+    (0, 0) => format!("{minutes}m"),
+    (0, _) => format!("{hours}h {minutes}m"),
+    _ => format!("{days}d {hours}h {minutes}m"),
+  }
+}
+
+/// Reads a length of time written as e.g. `30m`, `1h` or `7d` (`--bucket`), in seconds.
+pub fn parse_duration(value: &str) -> Result<i64> {
+  let invalid = || {
+    Error::other(format!(
+      "invalid duration '{value}' (expected e.g. '30m', '1h' or '1d')"
+    ))
+  };
+  let unit = match value.chars().last() {
+    Some('m') => 60,
+    Some('h') => 3600,
+    Some('d') => 86400,
+    _ => return Err(invalid()),
+  };
+  let count = value[..value.len() - 1].parse::<i64>().map_err(|_| invalid())?;
+
+  match count > 0 {
+    true => Ok(count * unit),
+    false => Err(invalid()),
+  }
+}
+
+impl std::fmt::Display for DateTime {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      formatter,
+      "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+      self.date.year, self.date.month, self.date.day, self.hour, self.minute, self.second
+    )
+  }
+}
+
+impl DateTime {
+  fn new(date: Date, (hour, minute, second): (u32, u32, u32)) -> Self {
+    Self {
+      date,
+      hour,
+      minute,
+      second,
+    }
+  }
+
+  /// Seconds since 1970-01-01 00:00:00 (in whatever zone the time was written), for measuring between two times.
+  pub fn seconds(&self) -> i64 {
+    // Days from the civil date (Howard Hinnant's `days_from_civil`), with the year starting in March.
+    let (year, month) = match self.date.month {
+      1 | 2 => (self.date.year as i64 - 1, self.date.month as i64 + 9),
+      month => (self.date.year as i64, month as i64 - 3),
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + self.date.day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    days * 86400 + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+  }
+
+  /// The inverse of [`DateTime::seconds`] (Howard Hinnant's `civil_from_days`).
+  pub fn from_seconds(seconds: i64) -> Self {
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400) as u32);
+    let shifted = days + 719468;
+    let era = shifted.div_euclid(146097);
+    let day_of_era = shifted - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let (month, year) = match month {
+      0..=9 => (month as u32 + 3, year_of_era + era * 400),
+      _ => (month as u32 - 9, year_of_era + era * 400 + 1),
+    };
+
+    Self::new(
+      Date {
+        year: year as u32,
+        month,
+        day,
+      },
+      (time / 3600, time % 3600 / 60, time % 60),
+    )
+  }
+
+  /// The current time, in UTC since there's no zone database to hand. It's only used to give a year to entries
+  /// read as they are logged, which a few hours either way doesn't change.
+  pub fn now() -> Self {
+    let seconds = std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .map_or(0, |elapsed| elapsed.as_secs() as i64);
+    Self::from_seconds(seconds)
+  }
+
+  /// Parses an RFC 5322 `Date` header, e.g. `Mon, 03 Jan 2022 08:00:00 -0500`. The zone is ignored, leaving the
+  /// sender's local time, which is what the router's own entries are written in too.
+  pub fn parse_rfc5322(value: &str) -> Option<Self> {
+    let value = value.split_once(',').map_or(value, |(_, rest)| rest);
+    let mut parts = value.split_whitespace();
+
+    let day = parts.next()?.parse().ok()?;
+    let month = month(parts.next()?)?;
+    let year = match parts.next()?.parse::<u32>().ok()? {
+      // Obsolete two digit years (RFC 5322 4.3).
+      year @ 0..=49 => year + 2000,
+      year @ 50..=999 => year + 1900,
+      year => year,
+    };
+
+    Some(Self::new(Date { year, month, day }.checked()?, time(parts.next()?)?))
+  }
+
+  /// Parses a C `asctime` timestamp, as an mbox `From ` line has, e.g. `Mon Jan  3 08:00:00 2022`. Some mailers
+  /// put a zone before the year (`Mon Jan 3 08:00:00 +0000 2022`); it's ignored.
+  pub fn parse_asctime(value: &str) -> Option<Self> {
+    let mut parts = value.split_whitespace();
+    let weekday = parts.next()?;
+    let month = month(parts.next()?)?;
+    let day = parts.next()?.parse().ok()?;
+    let clock = time(parts.next()?)?;
+    let year = parts.last()?.parse().ok()?;
+
+    let date = Date { year, month, day }.checked()?;
+
+    (weekday.len() == 3 && weekday.chars().all(|character| character.is_ascii_alphabetic()))
+      .then(|| Self::new(date, clock))
+  }
+
+  /// Parses an ISO 8601 (or RFC 5424 syslog) timestamp, e.g. `2022-01-03 08:00:00` or
+  /// `2022-01-03T08:00:00.123-05:00`. As with the `Date` header, fractions of a second and the zone are ignored.
+  pub fn parse_iso(value: &str) -> Option<Self> {
+    let (date, clock) = value.split_once(['T', ' '])?;
+    let date = date.parse::<Date>().ok()?;
+    let clock = clock
+      .split(['.', 'Z', '+', '-'])
+      .next()
+      .filter(|clock| clock.len() == 8)?;
+
+    Some(Self::new(date, time(clock)?))
+  }
+
+  /// Parses one end of a range of time (`--since`/`--until`): either a full timestamp, or a date, which covers the
+  /// whole of that day (from its first second, or to its last when `end` is set).
+  pub fn parse_bound(value: &str, end: bool) -> Result<Self> {
+    if let Some(timestamp) = Self::parse_iso(value) {
+      return Ok(timestamp);
+    }
+
+    let date = value.parse::<Date>().map_err(|_| {
+      Error::other(format!(
+        "invalid time '{value}' (expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS)"
+      ))
+    })?;
+
+    Ok(Self::new(date, if end { (23, 59, 59) } else { (0, 0, 0) }))
+  }
+
+  /// Parses the timestamp at the end of a router log entry: the month name and then either `DD,YYYY HH:MM:SS` or,
+  /// from routers that leave the year out, `DD,HH:MM:SS`. A missing year is taken from `anchor` (when the entry was
+  /// sent, e.g. the email's `Date`), stepping back a year for December entries in a January digest.
+  pub fn parse_log(month_name: &str, stamp: &str, anchor: Option<DateTime>) -> Option<Self> {
+    let month = month(month_name)?;
+    let (day, rest) = stamp.split_once(',')?;
+    let day = day.trim().parse().ok()?;
+
+    let (year, clock) = match rest.trim().split_once(' ') {
+      Some((year, clock)) => (year.parse().ok()?, clock),
+      None => {
+        let anchor = anchor?.date;
+        let year = match month > anchor.month {
+          true => anchor.year - 1,
+          false => anchor.year,
+        };
+        (year, rest.trim())
+      }
+    };
+
+    Some(Self::new(Date { year, month, day }.checked()?, time(clock)?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn at(year: u32, month: u32, day: u32, clock: (u32, u32, u32)) -> DateTime {
+    DateTime::new(Date { year, month, day }, clock)
+  }
+
+  #[test]
+  fn reads_every_date_format() {
+    let expected = Some(at(2022, 1, 3, (8, 0, 0)));
+
+    assert_eq!(DateTime::parse_rfc5322("Mon, 03 Jan 2022 08:00:00 -0500"), expected);
+    assert_eq!(DateTime::parse_rfc5322("3 Jan 22 08:00:00 GMT"), expected);
+    assert_eq!(DateTime::parse_asctime("Mon Jan  3 08:00:00 2022"), expected);
+    assert_eq!(DateTime::parse_asctime("Mon Jan 3 08:00:00 +0000 2022"), expected);
+    assert_eq!(DateTime::parse_iso("2022-01-03 08:00:00"), expected);
+    assert_eq!(DateTime::parse_iso("2022-01-03T08:00:00.123-05:00"), expected);
+    assert_eq!(DateTime::parse_log("Jan", "03,2022 08:00:00", None), expected);
+
+    assert_eq!(DateTime::parse_rfc5322("Mon, 03 Jan 2022"), None);
+    assert_eq!(DateTime::parse_asctime("router@example.com Jan 3 08:00:00 2022"), None);
+    assert_eq!(DateTime::parse_iso("2022-13-03 08:00:00"), None);
+    assert_eq!(DateTime::parse_log("Jan", "03,2022 25:00:00", None), None);
+  }
+
+  #[test]
+  fn rejects_days_the_month_does_not_have() {
+    assert_eq!(DateTime::parse_log("Jan", "45,2022 08:00:00", None), None);
+    assert_eq!(DateTime::parse_log("Jan", "00,2022 08:00:00", None), None);
+    assert_eq!(DateTime::parse_log("Apr", "31,2022 08:00:00", None), None);
+    assert_eq!(DateTime::parse_log("Feb", "29,2022 08:00:00", None), None);
+    assert_eq!(
+      DateTime::parse_log("Feb", "29,2024 08:00:00", None),
+      Some(at(2024, 2, 29, (8, 0, 0)))
+    );
+    assert_eq!(DateTime::parse_rfc5322("Mon, 45 Jan 2022 08:00:00 -0500"), None);
+    assert_eq!(DateTime::parse_asctime("Mon Feb 30 08:00:00 2022"), None);
+    assert!("2100-02-29".parse::<Date>().is_err());
+    assert!("2000-02-29".parse::<Date>().is_ok());
+  }
+
+  #[test]
+  fn bounds_cover_whole_days() {
+    assert_eq!(
+      DateTime::parse_bound("2022-01-03", false).ok(),
+      Some(at(2022, 1, 3, (0, 0, 0)))
+    );
+    assert_eq!(
+      DateTime::parse_bound("2022-01-03", true).ok(),
+      Some(at(2022, 1, 3, (23, 59, 59)))
+    );
+    assert_eq!(
+      DateTime::parse_bound("2022-01-03 08:00:00", true).ok(),
+      Some(at(2022, 1, 3, (8, 0, 0)))
+    );
+    assert!(DateTime::parse_bound("yesterday", false).is_err());
+  }
+
+  #[test]
+  fn yearless_log_entries_take_the_year_they_were_sent() {
+    let sent = Some(at(2022, 1, 2, (9, 0, 0)));
+
+    assert_eq!(
+      DateTime::parse_log("Jan", "02,08:00:00", sent),
+      Some(at(2022, 1, 2, (8, 0, 0)))
+    );
+    assert_eq!(
+      DateTime::parse_log("Dec", "31,23:59:59", sent),
+      Some(at(2021, 12, 31, (23, 59, 59)))
+    );
+    assert_eq!(DateTime::parse_log("Dec", "31,23:59:59", None), None);
+  }
+
+  fn date(year: u32, month: u32, day: u32) -> Date {
+    Date { year, month, day }
+  }
+
+  #[test]
+  fn finds_dates_in_filenames() {
+    let dashed = DEFAULT_FILENAME_PATTERN.parse::<FilenamePattern>().unwrap();
+    let packed = "log_%Y%m%d".parse::<FilenamePattern>().unwrap();
+
+    assert_eq!(dashed.find("2022-01-03-digest.eml"), Some(date(2022, 1, 3)));
+    assert_eq!(dashed.find("digest 2021-12-31.eml"), Some(date(2021, 12, 31)));
+    assert_eq!(packed.find("router/log_20220103.txt"), Some(date(2022, 1, 3)));
+    assert_eq!(packed.find("log_2022010.txt"), None);
+    assert_eq!(dashed.find("20220103.txt"), None);
+    assert_eq!(dashed.find("2022-13-03.eml"), None);
+    assert_eq!(dashed.find("2022-01-32.eml"), None);
+    assert_eq!(dashed.find("2022-00-03 then 2022-01-04.eml"), Some(date(2022, 1, 4)));
+  }
+
+  #[test]
+  fn filename_patterns_need_every_field() {
+    assert!("%Y-%m".parse::<FilenamePattern>().is_err());
+    assert!("%Y-%m-%e".parse::<FilenamePattern>().is_err());
+    assert!("100%%_%Y%m%d".parse::<FilenamePattern>().is_ok());
+  }
+
+  #[test]
+  fn keeps_files_without_a_date_in_range() {
+    let dates = FilenameDates {
+      pattern: DEFAULT_FILENAME_PATTERN.parse().unwrap(),
+      since: Some(date(2022, 1, 1)),
+      until: Some(date(2022, 1, 31)),
+    };
+
+    assert!(dates.accept("2022-01-01.eml"));
+    assert!(dates.accept("2022-01-31.eml"));
+    assert!(!dates.accept("2021-12-31.eml"));
+    assert!(!dates.accept("2022-02-01.eml"));
+    assert!(dates.accept("digest.eml"));
+  }
+}
@@ -0,0 +1,123 @@
+use base64::Engine;
+
+/// Decoding that tolerates the missing (or extra) padding some mailers produce.
+pub const BASE64: base64::engine::GeneralPurpose = base64::engine::GeneralPurpose::new(
+  &base64::alphabet::STANDARD,
+  base64::engine::GeneralPurposeConfig::new().with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+);
+
+/// The `Content-Transfer-Encoding` of a message body or MIME part.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+  /// `7bit`, `8bit` and `binary`, or no encoding given at all.
+  #[default]
+  Identity,
+  QuotedPrintable,
+  Base64,
+}
+
+impl Encoding {
+  pub fn parse(value: &str) -> Self {
+    match value.trim().to_ascii_lowercase().as_str() {
+      "quoted-printable" => Self::QuotedPrintable,
+      "base64" => Self::Base64,
+      _ => Self::Identity,
+    }
+  }
+}
+
+fn hex(digit: u8) -> Option<u8> {
+  (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+/// Decodes one line of a quoted-printable body (RFC 2045 6.7) onto the end of `output`, returning whether it ended
+/// with a soft line break (a trailing `=`), meaning the next line continues it.
+pub fn quoted_printable(line: &[u8], output: &mut Vec<u8>) -> bool {
+  // Trailing whitespace was added in transport and isn't part of the content.
+  let end = line
+    .iter()
+    .rposition(|byte| !matches!(byte, b' ' | b'\t' | b'\r'))
+    .map_or(0, |last| last + 1);
+  let (bytes, soft) = match line[..end].strip_suffix(b"=") {
+    Some(rest) => (rest, true),
+    None => (&line[..end], false),
+  };
+
+  let mut position = 0;
+
+  while position < bytes.len() {
+    let escaped = bytes
+      .get(position + 1..position + 3)
+      .filter(|_| bytes[position] == b'=')
+      .and_then(|digits| Some(hex(digits[0])? << 4 | hex(digits[1])?));
+
+    match escaped {
+      Some(byte) => {
+        output.push(byte);
+        position += 3;
+      }
+      // A stray `=` that isn't followed by two hex digits is kept as-is rather than dropping the line.
+      None => {
+        output.push(bytes[position]);
+        position += 1;
+      }
+    }
+  }
+
+  soft
+}
+
+/// Decodes a whole base64 body, ignoring the line breaks (and any other whitespace) it was wrapped with.
+pub fn base64(encoded: &str) -> Result<Vec<u8>, base64::DecodeError> {
+  let compact = encoded.split_whitespace().collect::<String>();
+  BASE64.decode(compact)
+}
+
+/// Looks up a MIME `charset` label (e.g. `iso-8859-1`, `windows-1252`), returning `None` for unknown ones.
+pub fn charset(label: &str) -> Option<&'static encoding_rs::Encoding> {
+  encoding_rs::Encoding::for_label(label.trim().as_bytes())
+}
+
+/// Transcodes decoded content to text. Content without a (known) charset is read as UTF-8, falling back to
+/// windows-1252 (a superset of ISO-8859-1) when it isn't valid UTF-8, which is what older mailers tend to send.
+pub fn text(bytes: &[u8], charset: Option<&'static encoding_rs::Encoding>) -> String {
+  let charset = charset.unwrap_or_else(|| match std::str::from_utf8(bytes) {
+    Ok(_) => encoding_rs::UTF_8,
+    Err(_) => encoding_rs::WINDOWS_1252,
+  });
+
+  charset.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn quoted_printable_joins_soft_line_breaks() {
+    let mut output = Vec::new();
+
+    assert!(quoted_printable(b"[admin login] from source 1.2.=  ", &mut output));
+    assert!(quoted_printable(b"3.=\r", &mut output));
+    assert!(!quoted_printable(b"4, caf=C3=A9=3D 100% =ZZ \t", &mut output));
+    assert_eq!(
+      String::from_utf8_lossy(&output),
+      "[admin login] from source 1.2.3.4, café= 100% =ZZ"
+    );
+  }
+
+  #[test]
+  fn base64_ignores_wrapping_and_padding() {
+    assert_eq!(base64("YW\r\nI=\n").ok(), Some(b"ab".to_vec()));
+    assert_eq!(base64("YWI").ok(), Some(b"ab".to_vec()));
+    assert!(base64("YW*I").is_err());
+  }
+
+  #[test]
+  fn text_uses_the_charset_or_falls_back_to_windows_1252() {
+    assert_eq!(text(b"caf\xe9", charset(" ISO-8859-1 ")), "café");
+    assert_eq!(text("café".as_bytes(), None), "café");
+    assert_eq!(text(b"caf\xe9 \x80", None), "café €");
+    assert!(charset("x-unknown").is_none());
+  }
+}
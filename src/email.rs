@@ -0,0 +1,661 @@
+use crate::dates::DateTime;
+use crate::decode::{self, Encoding};
+
+const BOUNDARY_PREFIX: &str = "--";
+const MULTIPART_PREFIX: &str = "multipart/";
+const TEXT_PREFIX: &str = "text/";
+const PLAIN_TYPE: &str = "text/plain";
+const HTML_TYPE: &str = "text/html";
+const ALTERNATIVE_TYPE: &str = "multipart/alternative";
+const ATTACHMENT_DISPOSITION: &str = "attachment";
+
+/// Attachment names that suggest a plain text log even when sent as `application/octet-stream`.
+const LOG_EXTENSIONS: [&str; 2] = [".txt", ".log"];
+
+/// A single header field, e.g. `Subject: Router log`.
+#[derive(Debug, Clone)]
+pub struct Header {
+  pub name: String,
+  pub value: String,
+}
+
+/// Whether `line` could open a message, i.e. is a header field: a name of printable characters (RFC 5322 2.2)
+/// followed by a `:`.
+pub fn is_field(line: &[u8]) -> bool {
+  let name = line.iter().take_while(|byte| **byte != b':').collect::<Vec<_>>();
+  !name.is_empty() && name.len() < line.len() && name.iter().all(|byte| (33..=126).contains(*byte))
+}
+
+/// Whether `line` separates messages in an mbox: `From `, the envelope sender (or `-`) and when it was received,
+/// e.g. `From router@example.com Mon Jan  3 08:00:00 2022`.
+pub fn is_mbox_separator(line: &[u8]) -> bool {
+  let Some(rest) = line.strip_prefix(b"From ") else {
+    return false;
+  };
+  let rest = String::from_utf8_lossy(rest);
+
+  match rest.trim_start().split_once(char::is_whitespace) {
+    Some((sender, received)) => !sender.is_empty() && DateTime::parse_asctime(received).is_some(),
+    None => false,
+  }
+}
+
+/// The header section of a message (or of a MIME part), read a line at a time up to the blank line that ends it.
+/// Fields keep their order and repeats (e.g. every `Received`), and folded values are unfolded (RFC 5322 2.2.3).
+#[derive(Default, Debug)]
+pub struct Headers {
+  pub fields: Vec<Header>,
+  done: bool,
+}
+
+impl Headers {
+  /// Feeds the next line, returning whether it belonged to the header section.
+  pub fn push<S>(&mut self, item: S) -> bool
+  where
+    S: std::convert::AsRef<str>,
+  {
+    if self.done {
+      return false;
+    }
+
+    let line = item.as_ref();
+
+    if line.is_empty() {
+      self.done = true;
+      return true;
+    }
+
+    // A line starting with whitespace continues the previous field; unfolding just drops the line break.
+    if line.starts_with([' ', '\t']) {
+      if let Some(field) = self.fields.last_mut() {
+        field.value.push_str(line);
+      }
+
+      return true;
+    }
+
+    // Lines that aren't a `name:value` field at all are dropped rather than ending the section early.
+    if let Some((name, value)) = line.split_once(':') {
+      self.fields.push(Header {
+        name: name.trim().to_string(),
+        value: value.trim_start().to_string(),
+      });
+    }
+
+    true
+  }
+
+  /// Every value of the named field (ignoring case), in the order they appeared.
+  pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    self
+      .fields
+      .iter()
+      .filter(move |field| field.name.eq_ignore_ascii_case(name))
+      .map(|field| field.value.trim_end())
+  }
+
+  /// The first value of the named field, ignoring case.
+  pub fn get<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+    self.get_all(name).next()
+  }
+
+  pub fn content_type(&self) -> HeaderValue {
+    self.get("Content-Type").map(HeaderValue::parse).unwrap_or_default()
+  }
+
+  pub fn content_disposition(&self) -> HeaderValue {
+    self
+      .get("Content-Disposition")
+      .map(HeaderValue::parse)
+      .unwrap_or_default()
+  }
+
+  /// The (lowercased) `Content-Transfer-Encoding`, if one was given.
+  pub fn transfer_encoding(&self) -> Option<String> {
+    self
+      .get("Content-Transfer-Encoding")
+      .map(|encoding| encoding.trim().to_ascii_lowercase())
+  }
+}
+
+/// A header value split into its lowercased leading token and `key=value` parameters, e.g. the
+/// `multipart/mixed; boundary="abc"` of a `Content-Type`.
+#[derive(Debug, Default)]
+pub struct HeaderValue {
+  pub token: String,
+  pub params: std::collections::HashMap<String, String>,
+}
+
+impl HeaderValue {
+  pub fn parse(value: &str) -> Self {
+    let mut pieces = Vec::with_capacity(4);
+    let mut current = String::with_capacity(value.len());
+    let mut quoted = false;
+
+    for character in value.chars() {
+      match character {
+        '"' => quoted = !quoted,
+        ';' if !quoted => pieces.push(std::mem::take(&mut current)),
+        other => current.push(other),
+      }
+    }
+
+    pieces.push(current);
+
+    let mut pieces = pieces.into_iter();
+    let token = pieces.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let params = pieces
+      .filter_map(|piece| {
+        let (key, value) = piece.split_once('=')?;
+        Some((key.trim().to_ascii_lowercase(), value.trim().to_string()))
+      })
+      .collect();
+
+    Self { token, params }
+  }
+}
+
+/// The headers that tell one message apart from another, kept with every access found in it.
+#[derive(Debug, Default)]
+pub struct Metadata {
+  pub date: Option<String>,
+  pub subject: Option<String>,
+  pub message_id: Option<String>,
+}
+
+impl Metadata {
+  fn from_headers(head: &Headers) -> Self {
+    Self {
+      date: head.get("Date").map(str::to_string),
+      subject: head.get("Subject").map(str::to_string),
+      message_id: head.get("Message-ID").map(str::to_string),
+    }
+  }
+}
+
+/// Which messages are worth reading, by their sender and subject; an unset field matches anything.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+  pub from: Option<String>,
+  pub subject: Option<String>,
+
+  /// The `Message-ID` of every message read so far, shared by every clone of the filter so that the same
+  /// message found in several places (e.g. an inbox and an archive folder) is only counted once.
+  pub seen: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+}
+
+impl Filter {
+  /// Whether `head` matches, comparing case-insensitive substrings like the IMAP `SEARCH` used when fetching does.
+  pub fn matches(&self, head: &Headers) -> bool {
+    [("From", self.from.as_ref()), ("Subject", self.subject.as_ref())]
+      .into_iter()
+      .all(|(name, wanted)| {
+        wanted.is_none_or(|wanted| {
+          head
+            .get_all(name)
+            .any(|value| value.to_lowercase().contains(&wanted.to_lowercase()))
+        })
+      })
+  }
+
+  /// Records the message's id, returning whether it was already seen. Messages without one are never duplicates.
+  fn duplicate(&self, head: &Headers) -> bool {
+    let Some(id) = head.get("Message-ID").map(str::trim).filter(|id| !id.is_empty()) else {
+      return false;
+    };
+
+    let mut seen = self.seen.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    if seen.insert(id.to_string()) {
+      return false;
+    }
+
+    eprintln!("skipping duplicate message '{id}'");
+    true
+  }
+}
+
+/// How the (decoded) lines of the entity currently being read are handled.
+#[derive(Debug)]
+enum Body {
+  /// Inline text, checked line by line just like a plain message body.
+  Text,
+
+  /// HTML, reduced to the text it shows. Within a `multipart/alternative` it's only used when there's no plain
+  /// text rendering as well.
+  Html,
+
+  /// A text attachment.
+  Attachment,
+
+  /// A multipart preamble or epilogue, or a part that can't contain log lines (images, signatures...).
+  Skip,
+}
+
+/// An enclosing multipart entity.
+#[derive(Debug)]
+struct Multipart {
+  boundary: String,
+
+  /// Whether the parts are alternative renderings of the same content, rather than separate pieces.
+  alternative: bool,
+  plain: bool,
+
+  /// The text of any HTML alternative, held back until it's known whether a plain text one was sent too.
+  html: Vec<String>,
+}
+
+/// Reads one email a line at a time, walking into MIME parts and handing back the lines that may hold log
+/// entries: the body of a plain message, inline text parts (preferring the plain text rendering of a
+/// `multipart/alternative`) and text attachments, each decoded from its transfer encoding.
+#[derive(Debug, Default)]
+pub struct Message {
+  /// The headers of the message itself.
+  pub head: Headers,
+
+  /// The message's `Date`, once its headers have been read.
+  pub date: Option<crate::dates::DateTime>,
+
+  /// The message's identifying headers, once they have been read.
+  pub metadata: Option<std::sync::Arc<Metadata>>,
+
+  filter: Filter,
+
+  /// The headers of the MIME part currently being read, if any.
+  part: Option<Headers>,
+
+  /// The enclosing multipart entities, innermost last.
+  multiparts: Vec<Multipart>,
+
+  /// `None` while the current entity's headers are being read.
+  body: Option<Body>,
+  encoding: Encoding,
+  charset: Option<&'static encoding_rs::Encoding>,
+
+  /// A quoted-printable line continued by a soft line break.
+  decoded: Vec<u8>,
+
+  /// Base64 content, collected until the entity ends and then decoded.
+  encoded: String,
+  html: crate::html::Stripper,
+}
+
+impl Message {
+  /// A message whose headers are still to be read, or (`raw`) one that is all body, for logs that were never
+  /// emailed at all. Messages with headers that don't match `filter` are skipped entirely.
+  pub fn new(raw: bool, filter: Filter) -> Self {
+    let mut message = Self {
+      filter,
+      ..Self::default()
+    };
+
+    if raw {
+      message.head.done = true;
+      message.body = Some(Body::Text);
+    }
+
+    message
+  }
+
+  /// The nearest enclosing `multipart/alternative`, if any (its parts may themselves be multipart, e.g. an HTML
+  /// rendering wrapped in `multipart/related` with its images).
+  fn alternative(&mut self) -> Option<&mut Multipart> {
+    self.multiparts.iter_mut().rev().find(|multipart| multipart.alternative)
+  }
+
+  /// Works out what to do with the body of an entity whose headers have just been read.
+  fn classify(&mut self) -> Body {
+    let head = self.part.as_ref().unwrap_or(&self.head);
+    let content_type = head.content_type();
+    let disposition = head.content_disposition();
+    self.encoding = head
+      .transfer_encoding()
+      .map(|encoding| Encoding::parse(&encoding))
+      .unwrap_or_default();
+    self.charset = content_type
+      .params
+      .get("charset")
+      .and_then(|label| decode::charset(label));
+
+    if content_type.token.starts_with(MULTIPART_PREFIX) {
+      if let Some(boundary) = content_type.params.get("boundary") {
+        self.multiparts.push(Multipart {
+          boundary: boundary.clone(),
+          alternative: content_type.token == ALTERNATIVE_TYPE,
+          plain: false,
+          html: Vec::new(),
+        });
+        return Body::Skip;
+      }
+    }
+
+    let name = disposition
+      .params
+      .get("filename")
+      .or_else(|| content_type.params.get("name"))
+      .map(|name| name.to_ascii_lowercase());
+    let attachment = disposition.token == ATTACHMENT_DISPOSITION || name.is_some();
+    let text = content_type.token.is_empty() || content_type.token.starts_with(TEXT_PREFIX);
+
+    if !attachment && content_type.token == HTML_TYPE {
+      return Body::Html;
+    }
+
+    if let Some(enclosing) = self.alternative() {
+      enclosing.plain |= content_type.token.is_empty() || content_type.token == PLAIN_TYPE;
+    }
+
+    match attachment {
+      true if text || name.is_some_and(|name| LOG_EXTENSIONS.iter().any(|ext| name.ends_with(ext))) => Body::Attachment,
+      false if text => Body::Text,
+      _ => Body::Skip,
+    }
+  }
+
+  /// Undoes the transfer encoding of the current entity, returning whichever lines are now complete.
+  fn decode(&mut self, line: &[u8]) -> Vec<String> {
+    match self.encoding {
+      Encoding::Identity => vec![decode::text(line, self.charset)],
+      Encoding::QuotedPrintable => match decode::quoted_printable(line, &mut self.decoded) {
+        true => Vec::new(),
+        false => vec![decode::text(&std::mem::take(&mut self.decoded), self.charset)],
+      },
+      Encoding::Base64 => {
+        self.encoded.push_str(&String::from_utf8_lossy(line));
+        Vec::new()
+      }
+    }
+  }
+
+  /// Hands decoded lines on according to the kind of entity they came from.
+  fn emit(&mut self, lines: Vec<String>) -> Vec<String> {
+    match self.body {
+      Some(Body::Text) | Some(Body::Attachment) => lines,
+      Some(Body::Html) => {
+        let text = lines
+          .iter()
+          .flat_map(|line| self.html.push(line))
+          .collect::<Vec<String>>();
+
+        match self.alternative() {
+          Some(enclosing) => {
+            enclosing.html.extend(text);
+            Vec::new()
+          }
+          None => text,
+        }
+      }
+      Some(Body::Skip) | None => Vec::new(),
+    }
+  }
+
+  /// Ends the entity being read, returning any lines that were still waiting on the rest of its content.
+  fn close(&mut self) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if !self.decoded.is_empty() {
+      lines.push(decode::text(&std::mem::take(&mut self.decoded), self.charset));
+    }
+
+    if !self.encoded.is_empty() {
+      match decode::base64(&std::mem::take(&mut self.encoded)) {
+        Ok(decoded) => lines.extend(decode::text(&decoded, self.charset).lines().map(str::to_string)),
+        Err(error) => eprintln!("WARNING - unable to decode base64 content - {error}"),
+      }
+    }
+
+    let lines = self.emit(lines);
+    self.html = Default::default();
+    self.body = None;
+    self.encoding = Encoding::Identity;
+    self.charset = None;
+    lines
+  }
+
+  /// Ends every multipart entity nested deeper than `depth`, returning the HTML alternatives that turned out to be
+  /// the only rendering of their content.
+  fn end_multiparts(&mut self, depth: usize) -> Vec<String> {
+    self
+      .multiparts
+      .drain(depth.min(self.multiparts.len())..)
+      .filter(|multipart| multipart.alternative && !multipart.plain)
+      .flat_map(|multipart| multipart.html)
+      .collect()
+  }
+
+  /// Checks whether a line is the delimiter of one of the enclosing multipart entities, returning its depth and
+  /// whether it closes that entity.
+  fn delimiter(&self, line: &str) -> Option<(usize, bool)> {
+    let rest = line.trim_end().strip_prefix(BOUNDARY_PREFIX)?;
+
+    self.multiparts.iter().enumerate().rev().find_map(|(depth, multipart)| {
+      let tail = rest.strip_prefix(multipart.boundary.as_str())?;
+      match tail {
+        "" => Some((depth, false)),
+        BOUNDARY_PREFIX => Some((depth, true)),
+        _ => None,
+      }
+    })
+  }
+
+  /// Feeds the next line of the message, returning any lines that should be checked for log entries.
+  pub fn push(&mut self, line: &[u8]) -> Vec<String> {
+    let lossy = String::from_utf8_lossy(line);
+
+    if let Some((depth, closing)) = self.delimiter(&lossy) {
+      let mut lines = self.close();
+      lines.extend(self.end_multiparts(depth + 1));
+
+      match closing {
+        true => {
+          lines.extend(self.end_multiparts(depth));
+          self.body = Some(Body::Skip);
+        }
+        false => self.part = Some(Headers::default()),
+      }
+
+      return lines;
+    }
+
+    match &self.body {
+      None => {
+        let head = self.part.as_mut().unwrap_or(&mut self.head);
+        head.push(&lossy);
+
+        if head.done {
+          if self.part.is_none() {
+            self.date = self.head.get("Date").and_then(crate::dates::DateTime::parse_rfc5322);
+            self.metadata = Some(std::sync::Arc::new(Metadata::from_headers(&self.head)));
+
+            if !self.filter.matches(&self.head) || self.filter.duplicate(&self.head) {
+              self.body = Some(Body::Skip);
+              return Vec::new();
+            }
+          }
+
+          self.body = Some(self.classify());
+        }
+
+        Vec::new()
+      }
+      Some(Body::Skip) => Vec::new(),
+      Some(_) => {
+        let lines = self.decode(line);
+        self.emit(lines)
+      }
+    }
+  }
+
+  /// Flushes anything still pending once the message has ended (e.g. one cut off before its closing boundary).
+  pub fn finish(&mut self) -> Vec<String> {
+    let mut lines = self.close();
+    lines.extend(self.end_multiparts(0));
+    lines
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Feeds `raw` to a new message a line at a time, returning every line it handed back.
+  fn read(raw: &str) -> Vec<String> {
+    let mut message = Message::new(false, Filter::default());
+    let mut lines = raw
+      .lines()
+      .flat_map(|line| message.push(line.as_bytes()))
+      .collect::<Vec<_>>();
+    lines.extend(message.finish());
+    lines
+  }
+
+  #[test]
+  fn mbox_separators_have_a_sender_and_a_date() {
+    assert!(is_mbox_separator(b"From router@example.com Mon Jan  3 08:00:00 2022"));
+    assert!(is_mbox_separator(b"From - Mon Jan 3 08:00:00 +0000 2022"));
+    assert!(!is_mbox_separator(b"From: router@example.com"));
+    assert!(!is_mbox_separator(b"From the router, with love"));
+    assert!(!is_mbox_separator(b"From router@example.com"));
+    assert!(!is_mbox_separator(b""));
+  }
+
+  #[test]
+  fn headers_unfold_and_keep_repeats() {
+    let mut head = Headers::default();
+
+    for line in [
+      "Received: from a",
+      "Subject: Router",
+      "\tlog",
+      "received: from b",
+      "not a field",
+      "",
+      "body",
+    ] {
+      head.push(line);
+    }
+
+    assert_eq!(head.get("subject"), Some("Router\tlog"));
+    assert_eq!(head.get_all("Received").collect::<Vec<_>>(), ["from a", "from b"]);
+    assert_eq!(head.fields.len(), 3);
+    assert!(!head.push("Subject: too late"));
+  }
+
+  #[test]
+  fn header_values_keep_quoted_parameters_whole() {
+    let value = HeaderValue::parse("Multipart/Mixed; Boundary=\"a;b=c\"; charset = utf-8");
+
+    assert_eq!(value.token, "multipart/mixed");
+    assert_eq!(value.params.get("boundary").map(String::as_str), Some("a;b=c"));
+    assert_eq!(value.params.get("charset").map(String::as_str), Some("utf-8"));
+  }
+
+  #[test]
+  fn nested_parts_end_at_their_own_boundaries() {
+    let lines = read(
+      "Content-Type: multipart/mixed; boundary=outer
+
+preamble
+--outer
+Content-Type: multipart/mixed; boundary=inner
+
+--inner
+
+first
+--inner
+Content-Type: image/png
+
+not text
+--inner--
+--outerish
+still in the inner epilogue
+--outer
+Content-Disposition: attachment; filename=\"router.log\"
+Content-Type: application/octet-stream
+
+second
+--outer--
+epilogue",
+    );
+
+    assert_eq!(lines, ["first", "second"]);
+  }
+
+  #[test]
+  fn alternatives_prefer_plain_text() {
+    let alternative = |plain: &str| {
+      read(&format!(
+        "Content-Type: multipart/alternative; boundary=b
+
+--b
+Content-Type: text/html
+
+<p>html</p>
+{plain}--b--"
+      ))
+    };
+
+    assert_eq!(alternative("--b\nContent-Type: text/plain\n\nplain\n"), ["plain"]);
+    assert_eq!(alternative(""), ["html"]);
+  }
+
+  #[test]
+  fn quoted_printable_lines_are_joined_before_being_handed_on() {
+    let lines = read(
+      "Content-Transfer-Encoding: quoted-printable
+
+[DoS Attack: SYN Flood] from source: 1.2.3.4, port 80, Monday,=
+ Jan 03,2022 08:00:00
+a=3Db",
+    );
+
+    assert_eq!(
+      lines,
+      [
+        "[DoS Attack: SYN Flood] from source: 1.2.3.4, port 80, Monday, Jan 03,2022 08:00:00",
+        "a=b"
+      ]
+    );
+  }
+
+  #[test]
+  fn base64_parts_are_decoded_once_they_end() {
+    let lines = read(
+      "Content-Type: multipart/mixed; boundary=b
+
+--b
+Content-Transfer-Encoding: BASE64
+
+W2FkbWluIGxvZ2luXSBmcm9tIHNvdXJjZSAxLjIu
+My40LCBNb25kYXksIEphbiAwMywyMDIyIDA4OjAw
+OjAw
+--b--",
+    );
+
+    assert_eq!(
+      lines,
+      ["[admin login] from source 1.2.3.4, Monday, Jan 03,2022 08:00:00"]
+    );
+  }
+
+  #[test]
+  fn parts_are_read_in_their_own_charset() {
+    let lines = read(
+      "Content-Type: multipart/mixed; boundary=b
+
+--b
+Content-Type: text/plain; charset=\"iso-8859-1\"
+Content-Transfer-Encoding: quoted-printable
+
+[Site blocked: caf=E9.example] from source 192.168.1.2
+--b
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: base64
+
+Y2Fmw6k=
+--b--",
+    );
+
+    assert_eq!(lines, ["[Site blocked: café.example] from source 192.168.1.2", "café"]);
+  }
+}
@@ -0,0 +1,410 @@
+use std::io::{BufRead, Error, Read, Result, Write};
+
+use async_std::channel;
+
+use crate::archive::Member;
+
+pub const DEFAULT_FOLDER: &str = "INBOX";
+pub const PASSWORD_ENV: &str = "RUPERT_PASSWORD";
+
+/// Number of messages requested from the server per `UID FETCH` round trip.
+const FETCH_CHUNK_SIZE: usize = 50;
+
+const POP3_OK: &str = "+OK";
+const POP3_TERMINATOR: &[u8] = b".";
+
+/// The mail protocols messages can be downloaded over.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+  #[default]
+  Imap,
+  Pop3,
+}
+
+impl Protocol {
+  pub fn default_port(&self) -> u16 {
+    match self {
+      Self::Imap => 993,
+      Self::Pop3 => 995,
+    }
+  }
+}
+
+impl std::str::FromStr for Protocol {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    match value {
+      "imap" => Ok(Self::Imap),
+      "pop3" => Ok(Self::Pop3),
+      other => Err(Error::other(format!(
+        "unknown protocol '{other}' (expected 'imap' or 'pop3')"
+      ))),
+    }
+  }
+}
+
+/// Everything needed to log in to a mail server and find the router's log messages.
+#[derive(Debug)]
+pub struct FetchConfig {
+  pub protocol: Protocol,
+  pub host: String,
+  pub port: u16,
+  pub user: String,
+  pub password: String,
+
+  /// The IMAP folder to search; POP3 only has the one mailbox.
+  pub folder: String,
+
+  /// Server-side search criteria. POP3 has no search, so every message is downloaded.
+  pub from: Option<String>,
+  pub subject: Option<String>,
+
+  /// Remove messages from the server once they have been handed to the parser.
+  pub delete: bool,
+}
+
+fn imap_error(error: imap::Error) -> Error {
+  Error::other(format!("imap - {error}"))
+}
+
+fn tls() -> Result<native_tls::TlsConnector> {
+  native_tls::TlsConnector::new().map_err(|error| Error::other(format!("tls - {error}")))
+}
+
+/// Quotes a value for use as an IMAP `astring` inside a `SEARCH` command.
+fn quote(value: &str) -> String {
+  format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl FetchConfig {
+  fn query(&self) -> String {
+    let criteria = [("FROM", self.from.as_ref()), ("SUBJECT", self.subject.as_ref())]
+      .into_iter()
+      .filter_map(|(key, value)| value.map(|value| format!("{key} {}", quote(value))))
+      .collect::<Vec<String>>();
+
+    if criteria.is_empty() {
+      return "ALL".into();
+    }
+
+    criteria.join(" ")
+  }
+}
+
+fn send(sender: &channel::Sender<Result<Member>>, member: Result<Member>) -> bool {
+  async_std::task::block_on(sender.send(member)).is_ok()
+}
+
+fn read_imap(config: FetchConfig, sender: channel::Sender<Result<Member>>) -> Result<()> {
+  let client = imap::connect((config.host.as_str(), config.port), &config.host, &tls()?).map_err(imap_error)?;
+  let mut session = client
+    .login(&config.user, &config.password)
+    .map_err(|(error, _)| imap_error(error))?;
+
+  session.select(&config.folder).map_err(imap_error)?;
+
+  let mut uids = session
+    .uid_search(config.query())
+    .map_err(imap_error)?
+    .into_iter()
+    .collect::<Vec<u32>>();
+  uids.sort_unstable();
+
+  eprintln!("found {} messages in '{}'", uids.len(), config.folder);
+
+  'chunks: for chunk in uids.chunks(FETCH_CHUNK_SIZE) {
+    let set = chunk.iter().map(u32::to_string).collect::<Vec<String>>().join(",");
+    let mut handed = Vec::with_capacity(chunk.len());
+
+    // `BODY.PEEK[]` fetches the whole message without flagging it as `\Seen`.
+    for message in session.uid_fetch(set, "(UID BODY.PEEK[])").map_err(imap_error)?.iter() {
+      let (Some(uid), Some(body)) = (message.uid, message.body()) else {
+        continue;
+      };
+
+      let member = Member {
+        name: format!("imap-{uid}.eml"),
+        contents: body.to_vec(),
+      };
+
+      if !send(&sender, Ok(member)) {
+        break 'chunks;
+      }
+
+      handed.push(uid.to_string());
+    }
+
+    if config.delete && !handed.is_empty() {
+      session
+        .uid_store(handed.join(","), "+FLAGS.SILENT (\\Deleted)")
+        .map_err(imap_error)?;
+    }
+  }
+
+  // As with POP3, the messages flagged above are only removed once the session ends without an error.
+  if config.delete {
+    session.expunge().map_err(imap_error)?;
+  }
+
+  session.logout().map_err(imap_error)
+}
+
+/// A minimal POP3 (RFC 1939) client; just enough to list, retrieve and delete messages.
+struct Pop3<S>
+where
+  S: Read + Write,
+{
+  stream: std::io::BufReader<S>,
+}
+
+impl<S> Pop3<S>
+where
+  S: Read + Write,
+{
+  fn response(&mut self) -> Result<String> {
+    let mut line = String::with_capacity(64);
+
+    if self.stream.read_line(&mut line)? == 0 {
+      return Err(Error::other("pop3 - connection closed awaiting a response"));
+    }
+
+    match line.strip_prefix(POP3_OK) {
+      Some(rest) => Ok(rest.trim().to_string()),
+      None => Err(Error::other(format!("pop3 - {}", line.trim()))),
+    }
+  }
+
+  fn command(&mut self, command: &str) -> Result<String> {
+    write!(self.stream.get_mut(), "{command}\r\n")?;
+    self.stream.get_mut().flush()?;
+    self.response()
+  }
+
+  /// Reads a multi-line response body up to the terminating `.` line, undoing dot-stuffing along the way.
+  fn body(&mut self) -> Result<Vec<u8>> {
+    let mut body = Vec::with_capacity(4096);
+    let mut line = Vec::with_capacity(256);
+
+    loop {
+      line.clear();
+
+      if self.stream.read_until(b'\n', &mut line)? == 0 {
+        return Err(Error::other("pop3 - connection closed mid-response"));
+      }
+
+      let content = line.strip_suffix(b"\n").unwrap_or(&line);
+      let content = content.strip_suffix(b"\r").unwrap_or(content);
+
+      if content == POP3_TERMINATOR {
+        return Ok(body);
+      }
+
+      body.extend_from_slice(line.strip_prefix(POP3_TERMINATOR).unwrap_or(&line));
+    }
+  }
+}
+
+fn read_pop3(config: FetchConfig, sender: channel::Sender<Result<Member>>) -> Result<()> {
+  let stream = std::net::TcpStream::connect((config.host.as_str(), config.port))?;
+  let stream = tls()?
+    .connect(&config.host, stream)
+    .map_err(|error| Error::other(format!("tls - {error}")))?;
+
+  let mut client = Pop3 {
+    stream: std::io::BufReader::new(stream),
+  };
+
+  pop3_session(&mut client, &config, &sender)
+}
+
+/// Logs in and downloads every message, from the server's greeting to `QUIT`.
+fn pop3_session<S>(client: &mut Pop3<S>, config: &FetchConfig, sender: &channel::Sender<Result<Member>>) -> Result<()>
+where
+  S: Read + Write,
+{
+  client.response()?;
+  client.command(&format!("USER {}", config.user))?;
+  client.command(&format!("PASS {}", config.password))?;
+
+  client.command("LIST")?;
+  let listing = String::from_utf8_lossy(&client.body()?).to_string();
+  let ids = listing
+    .lines()
+    .filter_map(|line| line.split_whitespace().next())
+    .filter_map(|id| id.parse::<u32>().ok())
+    .collect::<Vec<u32>>();
+
+  eprintln!("found {} messages", ids.len());
+
+  for id in ids {
+    // Prefer the server's persistent unique id for naming, falling back to the (session-scoped) message number.
+    let name = client
+      .command(&format!("UIDL {id}"))
+      .ok()
+      .and_then(|response| response.split_whitespace().nth(1).map(str::to_string))
+      .unwrap_or_else(|| id.to_string());
+
+    client.command(&format!("RETR {id}"))?;
+
+    let member = Member {
+      name: format!("pop3-{name}.eml"),
+      contents: client.body()?,
+    };
+
+    if !send(sender, Ok(member)) {
+      break;
+    }
+
+    if config.delete {
+      client.command(&format!("DELE {id}"))?;
+    }
+  }
+
+  // Deletions are only committed by the server once the session ends cleanly.
+  client.command("QUIT").map(|_| ())
+}
+
+/// Connects to the server on a dedicated thread, sending back each matching message as it is downloaded.
+pub fn messages(config: FetchConfig) -> channel::Receiver<Result<Member>> {
+  let (sender, receiver) = channel::bounded(1);
+
+  std::thread::spawn(move || {
+    let result = match config.protocol {
+      Protocol::Imap => read_imap(config, sender.clone()),
+      Protocol::Pop3 => read_pop3(config, sender.clone()),
+    };
+
+    if let Err(error) = result {
+      send(&sender, Err(error));
+    }
+  });
+
+  receiver
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A server that replies with `script` whatever it's sent, keeping what was sent in `sent`.
+  struct Scripted {
+    script: std::io::Cursor<Vec<u8>>,
+    sent: Vec<u8>,
+  }
+
+  impl Read for Scripted {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+      self.script.read(buffer)
+    }
+  }
+
+  impl Write for Scripted {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize> {
+      self.sent.write(buffer)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+      Ok(())
+    }
+  }
+
+  fn client(script: &str) -> Pop3<Scripted> {
+    Pop3 {
+      stream: std::io::BufReader::new(Scripted {
+        script: std::io::Cursor::new(script.replace('\n', "\r\n").into_bytes()),
+        sent: Vec::new(),
+      }),
+    }
+  }
+
+  fn config(delete: bool) -> FetchConfig {
+    FetchConfig {
+      protocol: Protocol::Pop3,
+      host: "mail.example.com".into(),
+      port: Protocol::Pop3.default_port(),
+      user: "router".into(),
+      password: "secret".into(),
+      folder: DEFAULT_FOLDER.into(),
+      from: None,
+      subject: None,
+      delete,
+    }
+  }
+
+  /// Runs a whole session against `script`, returning what was downloaded and each command sent.
+  fn session(script: &str, delete: bool) -> (Result<()>, Vec<Member>, Vec<String>) {
+    let mut client = client(script);
+    let (sender, receiver) = channel::unbounded();
+    let result = pop3_session(&mut client, &config(delete), &sender);
+    let members = std::iter::from_fn(|| receiver.try_recv().ok())
+      .filter_map(Result::ok)
+      .collect();
+    let sent = String::from_utf8_lossy(&client.stream.get_ref().sent)
+      .lines()
+      .map(str::to_string)
+      .collect();
+
+    (result, members, sent)
+  }
+
+  #[test]
+  fn errors_carry_the_server_reply() {
+    let error = client("-ERR no such message\n").command("RETR 9").unwrap_err();
+    assert_eq!(error.to_string(), "pop3 - -ERR no such message");
+
+    let error = client("").response().unwrap_err();
+    assert_eq!(error.to_string(), "pop3 - connection closed awaiting a response");
+  }
+
+  #[test]
+  fn bodies_are_unstuffed_up_to_the_terminator() {
+    let body = client("line\n..stuffed\n.\nnext\n").body().unwrap();
+    assert_eq!(body, b"line\r\n.stuffed\r\n");
+
+    let error = client("line\n").body().unwrap_err();
+    assert_eq!(error.to_string(), "pop3 - connection closed mid-response");
+  }
+
+  #[test]
+  fn downloads_then_deletes_each_message() {
+    let (result, members, sent) = session(
+      "+OK ready\n+OK\n+OK\n+OK 1 messages\n1 120\n.\n+OK 1 abc\n+OK\nSubject: log\n\n..\n.\n+OK\n+OK bye\n",
+      true,
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(
+      sent,
+      [
+        "USER router",
+        "PASS secret",
+        "LIST",
+        "UIDL 1",
+        "RETR 1",
+        "DELE 1",
+        "QUIT"
+      ]
+    );
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].name, "pop3-abc.eml");
+    assert_eq!(members[0].contents, b"Subject: log\r\n\r\n.\r\n");
+  }
+
+  #[test]
+  fn an_empty_mailbox_downloads_nothing() {
+    let (result, members, sent) = session("+OK ready\n+OK\n+OK\n+OK 0 messages\n.\n+OK bye\n", false);
+
+    assert!(result.is_ok());
+    assert!(members.is_empty());
+    assert_eq!(sent, ["USER router", "PASS secret", "LIST", "QUIT"]);
+  }
+
+  #[test]
+  fn a_failed_login_ends_the_session() {
+    let (result, members, _) = session("+OK ready\n+OK\n-ERR invalid password\n", false);
+
+    assert_eq!(result.unwrap_err().to_string(), "pop3 - -ERR invalid password");
+    assert!(members.is_empty());
+  }
+}
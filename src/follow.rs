@@ -0,0 +1,107 @@
+use std::io::Result;
+use std::path::PathBuf;
+
+use async_std::channel;
+use async_std::io::prelude::{BufReadExt, SeekExt};
+
+use crate::logs::{self, LogEvent};
+use crate::syslog;
+
+/// How long to wait at the end of the file before checking for more data (or a rotation).
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Something that identifies the file itself rather than its name, so a rotation (the name now pointing at a new
+/// file) can be told apart from the same file growing.
+#[cfg(unix)]
+fn identity(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+  use std::os::unix::fs::MetadataExt;
+  Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+  None
+}
+
+async fn open(path: &PathBuf) -> Result<(async_std::io::BufReader<async_std::fs::File>, Option<(u64, u64)>)> {
+  let file = async_std::fs::File::open(path).await?;
+  let identity = identity(&std::fs::metadata(path)?);
+  Ok((async_std::io::BufReader::new(file), identity))
+}
+
+/// Parses `path` from the start and then keeps reading lines as they are appended, like `tail -f`. If the file is
+/// truncated it is read again from the start, and if it is replaced (rotated) the new file is opened.
+pub async fn follow(
+  path: PathBuf,
+  grammar: logs::Grammar,
+  unmatched: Option<logs::Unmatched>,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  let (mut reader, mut current) = open(&path).await?;
+  let mut position = 0u64;
+  let mut line = Vec::with_capacity(256);
+  let mut number = 0;
+  let source: std::sync::Arc<str> = format!("{path:?}").into();
+
+  // The file is followed indefinitely, so unrecognized entries are written out (or dropped) rather than accumulated.
+  let mut peripheral = Vec::new();
+
+  eprintln!("following '{path:?}'");
+
+  loop {
+    // Lines are read as bytes, as one that isn't UTF-8 (or a failed read) mustn't stop the rest being followed.
+    let read = match reader.read_until(b'\n', &mut line).await {
+      Ok(read) => read,
+      Err(error) => {
+        eprintln!("WARNING - '{path:?}' - {error}");
+        async_std::task::sleep(POLL_INTERVAL).await;
+        continue;
+      }
+    };
+    position += read as u64;
+
+    // A line without its newline is still being written; leave it buffered until the rest arrives.
+    if read > 0 && line.ends_with(b"\n") {
+      number += 1;
+
+      if let Some(entry) = syslog::entry(&String::from_utf8_lossy(&line), grammar.format) {
+        let context = logs::LineContext::live(source.clone(), Some(number), grammar.clone());
+        logs::parse_line(entry, &context, &output, &mut peripheral).await?;
+      }
+
+      match unmatched.as_ref() {
+        Some(unmatched) => unmatched.drain(&mut peripheral)?,
+        None => peripheral.clear(),
+      }
+      line.clear();
+      continue;
+    }
+
+    if read > 0 {
+      continue;
+    }
+
+    async_std::task::sleep(POLL_INTERVAL).await;
+
+    let metadata = match std::fs::metadata(&path) {
+      Ok(metadata) => metadata,
+      // Mid-rotation the name may briefly point at nothing; keep reading the old file until it reappears.
+      Err(_) => continue,
+    };
+
+    if identity(&metadata) != current {
+      eprintln!("'{path:?}' was rotated, reopening");
+      (reader, current) = open(&path).await?;
+      (position, number) = (0, 0);
+      line.clear();
+    } else if metadata.len() < position {
+      eprintln!("'{path:?}' was truncated, reading from the start");
+      reader.seek(std::io::SeekFrom::Start(0)).await?;
+      (position, number) = (0, 0);
+      line.clear();
+    } else {
+      // Seeking in place discards the reader's record of having hit the end, so the appended data is read.
+      reader.seek(std::io::SeekFrom::Start(position)).await?;
+    }
+  }
+}
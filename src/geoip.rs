@@ -0,0 +1,54 @@
+use std::io::{Error, Result};
+
+/// Where an address is, as far as the database knows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+  /// The ISO 3166 code of the country, e.g. `DE`.
+  pub country: Option<String>,
+  pub city: Option<String>,
+}
+
+impl std::fmt::Display for Location {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match (self.country.as_deref(), self.city.as_deref()) {
+      (Some(country), Some(city)) => write!(formatter, "{country}, {city}"),
+      (Some(place), None) | (None, Some(place)) => formatter.write_str(place),
+      (None, None) => formatter.write_str("unknown"),
+    }
+  }
+}
+
+/// A GeoLite2 (or GeoIP2) Country or City database (`--geoip`), looked up locally.
+#[derive(Debug)]
+pub struct GeoIp(maxminddb::Reader<Vec<u8>>);
+
+impl GeoIp {
+  pub fn open(path: &str) -> Result<Self> {
+    maxminddb::Reader::open_readfile(path)
+      .map(Self)
+      .map_err(|error| Error::other(format!("invalid '--geoip' '{path}' - {error}")))
+  }
+
+  /// Where `address` is; an `--aggregate`d network is looked up by its first address.
+  pub fn locate(&self, address: &str) -> Option<Location> {
+    let address = address.split('/').next()?.parse().ok()?;
+    // A Country database's records are a City's without the city.
+    let record = self
+      .0
+      .lookup(address)
+      .ok()?
+      .decode::<maxminddb::geoip2::City>()
+      .ok()??;
+
+    let location = Location {
+      country: record
+        .country
+        .iso_code
+        .or(record.registered_country.iso_code)
+        .map(str::to_string),
+      city: record.city.names.english.map(str::to_string),
+    };
+
+    (location != Location::default()).then_some(location)
+  }
+}
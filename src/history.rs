@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::io::{Error, Result};
+
+/// How many earlier runs each remote address was seen in (`--history`), kept in a file between runs as one
+/// `ADDRESS<tab>RUNS` line per address.
+#[derive(Debug, Default)]
+pub struct History {
+  path: std::path::PathBuf,
+  runs: HashMap<String, u32>,
+}
+
+impl History {
+  /// Reads the history at `path`; a file that doesn't exist yet is an empty history.
+  pub fn load(path: &str) -> Result<Self> {
+    let contents = match std::fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+      Err(error) => return Err(Error::other(format!("invalid '--history' '{path}' - {error}"))),
+    };
+
+    let mut runs = HashMap::new();
+
+    for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+      let entry = line
+        .split_once('\t')
+        .and_then(|(address, count)| Some((address.to_string(), count.trim().parse().ok()?)));
+      let (address, count) =
+        entry.ok_or_else(|| Error::other(format!("invalid '--history' '{path}' line {}", number + 1)))?;
+      runs.insert(address, count);
+    }
+
+    Ok(Self {
+      path: path.into(),
+      runs,
+    })
+  }
+
+  /// How many earlier runs `address` was seen in.
+  pub fn runs(&self, address: &str) -> u32 {
+    self.runs.get(address).copied().unwrap_or(0)
+  }
+
+  /// Writes the history back out, counting this run for each of the `seen` addresses.
+  pub fn save<'a, S>(&self, seen: S) -> Result<()>
+  where
+    S: Iterator<Item = &'a String>,
+  {
+    let mut runs = self.runs.clone();
+
+    for address in seen.collect::<std::collections::HashSet<_>>() {
+      *runs.entry(address.clone()).or_insert(0) += 1;
+    }
+
+    let mut lines = runs
+      .iter()
+      .map(|(address, count)| format!("{address}\t{count}\n"))
+      .collect::<Vec<_>>();
+    lines.sort();
+
+    std::fs::write(&self.path, lines.concat())
+      .map_err(|error| Error::other(format!("invalid '--history' '{:?}' - {error}", self.path)))
+  }
+}
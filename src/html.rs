@@ -0,0 +1,171 @@
+/// Tags that end a line of text when rendered, so a log sent as one long `<br>`-separated line comes apart into
+/// its entries again.
+const BREAK_TAGS: [&str; 9] = ["br", "p", "div", "tr", "li", "pre", "table", "h1", "h2"];
+
+/// Tags whose content is never shown and so can't hold log lines.
+const HIDDEN_TAGS: [&str; 3] = ["head", "style", "script"];
+
+/// Named character references worth knowing about in a log; anything else is left as written.
+const ENTITIES: [(&str, &str); 7] = [
+  ("amp", "&"),
+  ("lt", "<"),
+  ("gt", ">"),
+  ("quot", "\""),
+  ("apos", "'"),
+  ("nbsp", " "),
+  ("#160", " "),
+];
+
+/// Replaces character references, e.g. `&#91;` or `&#x5B;` (`[`) and `&amp;`, with the characters they stand for.
+fn decode_entities(text: &str) -> String {
+  let mut decoded = String::with_capacity(text.len());
+  let mut rest = text;
+
+  while let Some(start) = rest.find('&') {
+    decoded.push_str(&rest[..start]);
+    rest = &rest[start..];
+
+    let reference = rest[1..]
+      .split_once(';')
+      .map(|(name, _)| name)
+      .filter(|name| name.len() <= 8);
+    let character = reference.and_then(|name| {
+      let named = ENTITIES
+        .iter()
+        .find(|(entity, _)| *entity == name)
+        .map(|(_, value)| value.to_string());
+      let numeric = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => name.strip_prefix('#').and_then(|digits| digits.parse().ok()),
+      };
+
+      named.or_else(|| numeric.and_then(char::from_u32).map(String::from))
+    });
+
+    match (reference, character) {
+      (Some(name), Some(character)) => {
+        decoded.push_str(&character);
+        rest = &rest[name.len() + 2..];
+      }
+      _ => {
+        decoded.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+
+  decoded.push_str(rest);
+  decoded
+}
+
+/// Turns HTML, fed a line at a time, into the lines of text it would show. Tags may span lines.
+#[derive(Debug, Default)]
+pub struct Stripper {
+  /// The content of a tag that has been opened but not yet closed.
+  tag: Option<String>,
+  text: String,
+  hidden: bool,
+}
+
+impl Stripper {
+  fn flush(&mut self, lines: &mut Vec<String>) {
+    let text = decode_entities(&std::mem::take(&mut self.text));
+    let text = text.trim();
+
+    if !text.is_empty() {
+      lines.push(text.to_string());
+    }
+  }
+
+  fn close_tag(&mut self, tag: &str, lines: &mut Vec<String>) {
+    let closing = tag.starts_with('/');
+    let name = tag
+      .trim_start_matches('/')
+      .split(|character: char| character.is_whitespace() || character == '/')
+      .next()
+      .unwrap_or_default()
+      .to_ascii_lowercase();
+
+    if BREAK_TAGS.contains(&name.as_str()) {
+      self.flush(lines);
+    }
+
+    if HIDDEN_TAGS.contains(&name.as_str()) {
+      self.hidden = !closing;
+    }
+  }
+
+  /// Feeds the next line of markup, returning the lines of text it completes.
+  pub fn push(&mut self, line: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for character in line.chars() {
+      match (self.tag.as_mut(), character) {
+        (None, '<') => self.tag = Some(String::new()),
+        (None, other) if !self.hidden => self.text.push(other),
+        (None, _) => (),
+        (Some(_), '>') => {
+          let tag = self.tag.take().unwrap_or_default();
+          self.close_tag(&tag, &mut lines);
+        }
+        (Some(tag), other) => tag.push(other),
+      }
+    }
+
+    match self.tag.as_mut() {
+      Some(tag) => tag.push(' '),
+      None => self.flush(&mut lines),
+    }
+
+    lines
+  }
+}
+
+/// The size of a [`bar_chart`], in pixels, and the space left under it for the labels.
+const CHART_WIDTH: usize = 720;
+const CHART_HEIGHT: usize = 160;
+const CHART_LABELS: usize = 20;
+
+/// Escapes `text` for use in an element or a quoted attribute of a page being written (`report --html`).
+pub fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// An inline SVG bar chart of `bars`, in order, each labelled below (as many as fit) and with its count shown on
+/// hover.
+pub fn bar_chart(bars: &[(String, u32)]) -> String {
+  let highest = bars.iter().map(|(_, count)| *count).max().unwrap_or(0).max(1) as usize;
+  let width = CHART_WIDTH as f64 / bars.len().max(1) as f64;
+  // Labels are spaced out so that they don't overlap, which at 7px a character is about 60px apart.
+  let every = (60.0 / width).ceil().max(1.0) as usize;
+  let mut svg = format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{CHART_WIDTH}\" height=\"{}\" class=\"chart\">",
+    CHART_HEIGHT + CHART_LABELS
+  );
+
+  for (index, (label, count)) in bars.iter().enumerate() {
+    let height = *count as usize * CHART_HEIGHT / highest;
+    let x = index as f64 * width;
+    svg.push_str(&format!(
+      "<rect x=\"{x:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{height}\"><title>{}: {count}</title></rect>",
+      CHART_HEIGHT - height,
+      (width - 1.0).max(1.0),
+      escape(label)
+    ));
+
+    if index % every == 0 {
+      svg.push_str(&format!(
+        "<text x=\"{x:.1}\" y=\"{}\">{}</text>",
+        CHART_HEIGHT + CHART_LABELS - 6,
+        escape(label)
+      ));
+    }
+  }
+
+  svg.push_str("</svg>");
+  svg
+}
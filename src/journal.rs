@@ -0,0 +1,111 @@
+use std::io::{BufRead, Error, Result};
+
+use async_std::channel;
+
+use crate::logs::{self, LogEvent};
+use crate::syslog;
+
+/// Which journal entries to read; at least one of `unit` or `identifier` is expected.
+#[derive(Debug, Default)]
+pub struct JournalFilter {
+  pub unit: Option<String>,
+  pub identifier: Option<String>,
+
+  /// Keep reading new entries as they are written rather than stopping at the end of the journal.
+  pub follow: bool,
+
+  /// How the entries are read.
+  pub grammar: logs::Grammar,
+}
+
+impl JournalFilter {
+  fn command(&self) -> std::process::Command {
+    let mut command = std::process::Command::new("journalctl");
+
+    // `cat` output is just the `MESSAGE` field of each entry, one per line.
+    command.args(["--output", "cat", "--no-pager"]);
+
+    if let Some(unit) = self.unit.as_ref() {
+      command.args(["--unit", unit]);
+    }
+
+    if let Some(identifier) = self.identifier.as_ref() {
+      command.args(["--identifier", identifier]);
+    }
+
+    if self.follow {
+      command.arg("--follow");
+    }
+
+    command.stdout(std::process::Stdio::piped());
+    command
+  }
+}
+
+/// Runs `journalctl`, streaming each line of its output back from a dedicated thread.
+fn lines(filter: &JournalFilter) -> Result<channel::Receiver<Result<String>>> {
+  let mut child = filter
+    .command()
+    .spawn()
+    .map_err(|error| Error::other(format!("unable to run journalctl - {error}")))?;
+
+  let stdout = child
+    .stdout
+    .take()
+    .ok_or_else(|| Error::other("journalctl has no stdout"))?;
+
+  let (sender, receiver) = channel::bounded(100);
+
+  std::thread::spawn(move || {
+    for line in std::io::BufReader::new(stdout).lines() {
+      if async_std::task::block_on(sender.send(line)).is_err() {
+        let _ = child.kill();
+        break;
+      }
+    }
+
+    let status = child.wait().and_then(|status| match status.success() {
+      true => Ok(()),
+      false => Err(Error::other(format!("journalctl exited with {status}"))),
+    });
+
+    if let Err(error) = status {
+      let _ = async_std::task::block_on(sender.send(Err(error)));
+    }
+  });
+
+  Ok(receiver)
+}
+
+pub async fn read(
+  filter: JournalFilter,
+  unmatched: Option<logs::Unmatched>,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  let lines = lines(&filter)?;
+  let mut peripheral = Vec::with_capacity(100);
+
+  eprintln!("checking journal ({filter:?})");
+
+  while let Ok(line) = lines.recv().await {
+    if let Some(entry) = syslog::entry(&line?, filter.grammar.format) {
+      logs::parse_line(
+        entry,
+        &logs::LineContext::live("journal".into(), None, filter.grammar.clone()),
+        &output,
+        &mut peripheral,
+      )
+      .await?;
+    }
+
+    // Unrecognized entries are written out as they're found. Otherwise, when following, the journal is read
+    // indefinitely; don't let them pile up.
+    match unmatched.as_ref() {
+      Some(unmatched) => unmatched.drain(&mut peripheral)?,
+      None if filter.follow => peripheral.clear(),
+      None => (),
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,941 @@
+use std::io::{Error, Result, Write};
+
+use async_std::channel;
+
+use crate::dates::DateTime;
+use crate::email::Metadata;
+
+const LOG_LINE_DELIM: &str = "] ";
+const REMOTE_ACCESS_PREFIX: &str = "[LAN access from remote";
+const DOS_ATTACK_PREFIX: &str = "[dos attack: ";
+const ADMIN_LOGIN_TAG: &str = "[admin login";
+const ADMIN_LOGIN_FAILURE_TAG: &str = "[admin login failure";
+const SOURCE_PREFIX: &str = "from source";
+const WLAN_REJECTED_PREFIX: &str = "[WLAN access rejected";
+const MAC_PREFIX: &str = "from MAC address";
+const DHCP_PREFIX: &str = "[DHCP IP: ";
+const DHCP_MAC_PREFIX: &str = "to MAC address";
+const UPNP_PREFIX: &str = "[UPnP set event: ";
+const INTERNET_CONNECTED_TAG: &str = "[Internet connected";
+const INTERNET_DISCONNECTED_TAG: &str = "[Internet disconnected";
+const WAN_ADDRESS_PREFIX: &str = "IP address:";
+const TIME_SYNC_PREFIX: &str = "[Time synchronized with NTP server";
+const EMAIL_SENT_PREFIX: &str = "[email sent to: ";
+const SITE_BLOCKED_PREFIX: &str = "[Site blocked: ";
+const SERVICE_BLOCKED_PREFIX: &str = "[Service blocked: ";
+const DYNAMIC_DNS_TAG: &str = "[Dynamic DNS";
+const HOST_NAME_PREFIX: &str = "host name ";
+
+pub struct RemoteAccess {
+  pub address: String,
+  pub port: Option<u16>,
+
+  /// The local host (usually the router itself, or a forwarded device) and port that were reached.
+  pub local_address: String,
+  pub local_port: Option<u16>,
+
+  /// The transport protocol (e.g. `TCP`), for the formats that log it.
+  pub protocol: Option<String>,
+
+  /// When the router logged the access, if the entry's timestamp could be read (and given a year).
+  pub timestamp: Option<DateTime>,
+
+  /// Where the entry was found.
+  pub origin: Origin,
+}
+
+/// The kinds of attack the router's firewall tells apart.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Attack {
+  SynFlood,
+  UdpFlood,
+  IcmpFlood,
+  SynAckScan,
+  AckScan,
+  RstScan,
+  FinScan,
+  PortScan,
+  Smurf,
+  PingOfDeath,
+  /// Anything else, as the router named it (e.g. `TCP/UDP Chargen`).
+  Other(String),
+}
+
+impl Attack {
+  /// Reads the name the router gave an attack by the words in it, as the spelling, spacing and what comes around
+  /// them vary between vendors and firmware versions (e.g. `SYN Flood`, `TCP SYN Flood`, `syn-flood attack`).
+  pub fn parse(name: &str) -> Self {
+    let normalized = name
+      .chars()
+      .filter(|character| character.is_ascii_alphanumeric())
+      .collect::<String>()
+      .to_ascii_lowercase();
+
+    // Checked in order, so that e.g. a SYN/ACK scan isn't taken for an ACK one.
+    let keywords: [(&[&str], Self); 10] = [
+      (&["synackscan"], Self::SynAckScan),
+      (&["synflood"], Self::SynFlood),
+      (&["udpflood"], Self::UdpFlood),
+      (&["icmpflood", "pingflood"], Self::IcmpFlood),
+      (&["pingofdeath"], Self::PingOfDeath),
+      (&["smurf"], Self::Smurf),
+      (&["rstscan"], Self::RstScan),
+      (&["finscan"], Self::FinScan),
+      (&["ackscan"], Self::AckScan),
+      (&["portscan"], Self::PortScan),
+    ];
+
+    keywords
+      .into_iter()
+      .find(|(words, _)| words.iter().any(|word| normalized.contains(word)))
+      .map_or_else(|| Self::Other(name.trim().to_string()), |(_, attack)| attack)
+  }
+
+  /// The protocol the attack was made over, where its name tells; `TCP/UDP` ones (e.g. `TCP/UDP Chargen`) could
+  /// have been either.
+  pub fn protocol(&self) -> Option<&'static str> {
+    match self {
+      Self::SynFlood | Self::SynAckScan | Self::AckScan | Self::RstScan | Self::FinScan | Self::PortScan => Some("TCP"),
+      Self::UdpFlood => Some("UDP"),
+      Self::IcmpFlood | Self::Smurf | Self::PingOfDeath => Some("ICMP"),
+      Self::Other(name) => {
+        let name = name.to_ascii_uppercase();
+        ["TCP/UDP", "ICMP", "TCP", "UDP"]
+          .into_iter()
+          .find(|protocol| name.contains(protocol))
+      }
+    }
+  }
+}
+
+impl std::fmt::Display for Attack {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      Self::SynFlood => "SYN Flood",
+      Self::UdpFlood => "UDP Flood",
+      Self::IcmpFlood => "ICMP Flood",
+      Self::SynAckScan => "SYN/ACK Scan",
+      Self::AckScan => "ACK Scan",
+      Self::RstScan => "RST Scan",
+      Self::FinScan => "FIN Scan",
+      Self::PortScan => "Port Scan",
+      Self::Smurf => "Smurf",
+      Self::PingOfDeath => "Ping of Death",
+      Self::Other(name) => name,
+    };
+
+    formatter.write_str(name)
+  }
+}
+
+/// A denial of service attempt the router's firewall noticed (and dropped), e.g. `[DoS Attack: SYN/ACK Scan]`.
+pub struct DosAttack {
+  pub kind: Attack,
+  pub address: String,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// A sign in (or failed attempt) to the router's web interface.
+pub struct AdminLogin {
+  pub address: String,
+  pub failed: bool,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// A device that failed to join the wireless network, e.g. `[WLAN access rejected: incorrect security]`.
+pub struct WlanRejected {
+  /// Why the router turned the device away, e.g. `incorrect security`.
+  pub reason: String,
+  pub mac: String,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// An address handed out to a device on the local network, from `[DHCP IP: ADDRESS] to MAC address MAC`.
+pub struct DhcpLease {
+  pub address: String,
+  pub mac: String,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// A port mapping a device on the local network asked the router (over UPnP) to open or close, from
+/// `[UPnP set event: add_nat_rule] from source ADDRESS`.
+pub struct UpnpMapping {
+  /// What was asked for, e.g. `add_nat_rule` or `del_nat_rule`.
+  pub action: String,
+  pub address: String,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+impl UpnpMapping {
+  pub fn opens(&self) -> bool {
+    self.action.starts_with("add")
+  }
+}
+
+/// The router's WAN link coming up (with the address it was given) or going down.
+pub struct InternetConnection {
+  pub connected: bool,
+  pub address: Option<String>,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// The router setting its clock from an NTP server. Its timestamp is the one the router is surest of, so it is used
+/// to check the others.
+pub struct TimeSync {
+  pub server: Option<String>,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// The router noting that it emailed its log, e.g. `[email sent to: someone@example.com]`.
+pub struct EmailSent {
+  pub recipient: String,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// A local device stopped by the router's access control, from `[Site blocked: SITE]` or
+/// `[Service blocked: SERVICE]`.
+pub struct Blocked {
+  /// Whether a service (e.g. `ICMP`) rather than a site was blocked.
+  pub service: bool,
+  pub destination: String,
+  pub address: String,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// The router updating its Dynamic DNS record, from `[Dynamic DNS] host name HOST registration successful` (or
+/// `failed`).
+pub struct DynamicDns {
+  pub host: String,
+  pub succeeded: bool,
+  pub timestamp: Option<DateTime>,
+  pub origin: Origin,
+}
+
+/// Something the router logged that is worth counting. Every parser produces one of these, and they are all sent
+/// over the same channel to be dispatched, per variant, by the [`crate::summary::Summary`].
+pub enum LogEvent {
+  RemoteAccess(RemoteAccess),
+  DosAttack(DosAttack),
+  AdminLogin(AdminLogin),
+  WlanRejected(WlanRejected),
+  DhcpLease(DhcpLease),
+  UpnpMapping(UpnpMapping),
+  InternetConnection(InternetConnection),
+  TimeSync(TimeSync),
+  EmailSent(EmailSent),
+  Blocked(Blocked),
+  DynamicDns(DynamicDns),
+}
+
+impl LogEvent {
+  pub fn timestamp(&self) -> Option<DateTime> {
+    match self {
+      Self::RemoteAccess(access) => access.timestamp,
+      Self::DosAttack(attack) => attack.timestamp,
+      Self::AdminLogin(login) => login.timestamp,
+      Self::WlanRejected(rejection) => rejection.timestamp,
+      Self::DhcpLease(lease) => lease.timestamp,
+      Self::UpnpMapping(mapping) => mapping.timestamp,
+      Self::InternetConnection(connection) => connection.timestamp,
+      Self::TimeSync(sync) => sync.timestamp,
+      Self::EmailSent(sent) => sent.timestamp,
+      Self::Blocked(blocked) => blocked.timestamp,
+      Self::DynamicDns(update) => update.timestamp,
+    }
+  }
+
+  pub fn origin(&self) -> &Origin {
+    match self {
+      Self::RemoteAccess(access) => &access.origin,
+      Self::DosAttack(attack) => &attack.origin,
+      Self::AdminLogin(login) => &login.origin,
+      Self::WlanRejected(rejection) => &rejection.origin,
+      Self::DhcpLease(lease) => &lease.origin,
+      Self::UpnpMapping(mapping) => &mapping.origin,
+      Self::InternetConnection(connection) => &connection.origin,
+      Self::TimeSync(sync) => &sync.origin,
+      Self::EmailSent(sent) => &sent.origin,
+      Self::Blocked(blocked) => &blocked.origin,
+      Self::DynamicDns(update) => &update.origin,
+    }
+  }
+
+  /// The address the event is about, if it has one: the remote end of an access, attack or login, the local
+  /// device of a lease, mapping or block, or the router's own on connecting.
+  pub fn address(&self) -> Option<&str> {
+    match self {
+      Self::RemoteAccess(access) => Some(&access.address),
+      Self::DosAttack(attack) => Some(&attack.address),
+      Self::AdminLogin(login) => Some(&login.address),
+      Self::DhcpLease(lease) => Some(&lease.address),
+      Self::UpnpMapping(mapping) => Some(&mapping.address),
+      Self::InternetConnection(connection) => connection.address.as_deref(),
+      Self::Blocked(blocked) => Some(&blocked.address),
+      Self::WlanRejected(_) | Self::TimeSync(_) | Self::EmailSent(_) | Self::DynamicDns(_) => None,
+    }
+  }
+
+  /// The kind of event, named as in a patterns file.
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Self::RemoteAccess(_) => "access",
+      Self::DosAttack(_) => "dos",
+      Self::AdminLogin(_) => "login",
+      Self::WlanRejected(_) => "wlan",
+      Self::DhcpLease(_) => "dhcp",
+      Self::UpnpMapping(_) => "upnp",
+      Self::InternetConnection(connection) if connection.connected => "connected",
+      Self::InternetConnection(_) => "disconnected",
+      Self::TimeSync(_) => "sync",
+      Self::EmailSent(_) => "email",
+      Self::Blocked(blocked) if blocked.service => "service",
+      Self::Blocked(_) => "site",
+      Self::DynamicDns(_) => "ddns",
+    }
+  }
+
+  /// The event as one JSON object (`--events ndjson`): its [`LogEvent::kind`], its fields, when it happened and
+  /// where it was found.
+  pub fn to_json(&self) -> serde_json::Value {
+    let fields = match self {
+      Self::RemoteAccess(access) => serde_json::json!({
+        "address": access.address,
+        "port": access.port,
+        "local_address": access.local_address,
+        "local_port": access.local_port,
+        "protocol": access.protocol,
+      }),
+      Self::DosAttack(attack) => serde_json::json!({ "attack": attack.kind.to_string(), "address": attack.address }),
+      Self::AdminLogin(login) => serde_json::json!({ "address": login.address, "failed": login.failed }),
+      Self::WlanRejected(rejection) => serde_json::json!({ "reason": rejection.reason, "mac": rejection.mac }),
+      Self::DhcpLease(lease) => serde_json::json!({ "address": lease.address, "mac": lease.mac }),
+      Self::UpnpMapping(mapping) => serde_json::json!({ "action": mapping.action, "address": mapping.address }),
+      Self::InternetConnection(connection) => serde_json::json!({ "address": connection.address }),
+      Self::TimeSync(sync) => serde_json::json!({ "server": sync.server }),
+      Self::EmailSent(sent) => serde_json::json!({ "recipient": sent.recipient }),
+      Self::Blocked(blocked) => {
+        serde_json::json!({ "destination": blocked.destination, "address": blocked.address })
+      }
+      Self::DynamicDns(update) => serde_json::json!({ "host": update.host, "failed": !update.succeeded }),
+    };
+
+    let origin = self.origin();
+    let message = origin.message.as_deref();
+    let mut event = serde_json::json!({
+      "kind": self.kind(),
+      "timestamp": self.timestamp().map(|timestamp| timestamp.to_string()),
+      "source": origin.source.as_deref(),
+      "line": origin.line,
+      "message_id": message.and_then(|message| message.message_id.as_deref()),
+      "subject": message.and_then(|message| message.subject.as_deref()),
+    });
+
+    if let (Some(event), serde_json::Value::Object(fields)) = (event.as_object_mut(), fields) {
+      event.extend(fields);
+    }
+
+    event
+  }
+}
+
+fn endpoint(address: &str, port: Option<u16>) -> String {
+  match port {
+    Some(port) if address.contains(':') => format!("[{address}]:{port}"),
+    Some(port) => format!("{address}:{port}"),
+    None => address.to_string(),
+  }
+}
+
+/// Describes the event itself, e.g. `access from 1.2.3.4`; when and where it was logged are left to the caller.
+impl std::fmt::Display for LogEvent {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::RemoteAccess(access) => write!(
+        formatter,
+        "access from {} to {}",
+        endpoint(&access.address, access.port),
+        endpoint(&access.local_address, access.local_port)
+      ),
+      Self::DosAttack(attack) => write!(formatter, "{} from {}", attack.kind, attack.address),
+      Self::AdminLogin(login) if login.failed => write!(formatter, "failed admin login from {}", login.address),
+      Self::AdminLogin(login) => write!(formatter, "admin login from {}", login.address),
+      Self::WlanRejected(rejection) => write!(
+        formatter,
+        "wlan access rejected ({}) for {}",
+        rejection.reason, rejection.mac
+      ),
+      Self::DhcpLease(lease) => write!(formatter, "dhcp lease of {} to {}", lease.address, lease.mac),
+      Self::UpnpMapping(mapping) => write!(formatter, "upnp {} from {}", mapping.action, mapping.address),
+      Self::InternetConnection(connection) => match connection.address.as_deref() {
+        Some(address) if connection.connected => write!(formatter, "internet connected as {address}"),
+        _ if connection.connected => write!(formatter, "internet connected"),
+        _ => write!(formatter, "internet disconnected"),
+      },
+      Self::TimeSync(sync) => match sync.server.as_deref() {
+        Some(server) => write!(formatter, "time synchronized with {server}"),
+        None => write!(formatter, "time synchronized"),
+      },
+      Self::EmailSent(sent) => write!(formatter, "email sent to {}", sent.recipient),
+      Self::Blocked(blocked) => {
+        let kind = if blocked.service { "service" } else { "site" };
+        write!(
+          formatter,
+          "{kind} {} blocked for {}",
+          blocked.destination, blocked.address
+        )
+      }
+      Self::DynamicDns(update) => {
+        let outcome = if update.succeeded { "succeeded" } else { "failed" };
+        write!(formatter, "dynamic dns update for {} {outcome}", update.host)
+      }
+    }
+  }
+}
+
+/// Where an entry was found, so a suspicious count can be traced back to the lines behind it.
+#[derive(Debug, Default, Clone)]
+pub struct Origin {
+  /// The identifying headers of the email the entry was in, if it came from one.
+  pub message: Option<std::sync::Arc<Metadata>>,
+
+  /// The file (or URL, archive member, syslog peer...) the entry was read from, and its line there.
+  pub source: Option<std::sync::Arc<str>>,
+  pub line: Option<usize>,
+}
+
+/// Whose grammar log lines are read with (`--format`). Every format produces the same [`LogEvent`]s; Netgear's,
+/// e.g. `[LAN access from remote] from ...`, is read here and the others in [`crate::vendors`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  #[default]
+  Netgear,
+  TpLink,
+  Asus,
+  Pfsense,
+
+  /// Plain syslog from a Linux based router (OpenWrt, DD-WRT...): iptables, dnsmasq and sshd messages.
+  GenericSyslog,
+}
+
+impl std::str::FromStr for Format {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    match value {
+      "netgear" => Ok(Self::Netgear),
+      "tplink" => Ok(Self::TpLink),
+      "asus" => Ok(Self::Asus),
+      "pfsense" => Ok(Self::Pfsense),
+      "generic-syslog" => Ok(Self::GenericSyslog),
+      other => Err(Error::other(format!(
+        "unknown format '{other}' (expected 'netgear', 'tplink', 'asus', 'pfsense' or 'generic-syslog')"
+      ))),
+    }
+  }
+}
+
+/// How log lines are read: with their router's grammar, then (for any line it can't read) the patterns given with
+/// `--patterns`.
+#[derive(Debug, Default, Clone)]
+pub struct Grammar {
+  pub format: Format,
+  pub patterns: std::sync::Arc<[crate::patterns::Pattern]>,
+
+  /// Fail on entries that are recognized but can't be read (`--strict`), rather than warning and moving on, so a
+  /// firmware update that changes their wording is noticed straight away.
+  pub strict: bool,
+}
+
+/// Writes where the entry was found as `SOURCE:LINE`.
+impl std::fmt::Display for Origin {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match (self.source.as_deref(), self.line) {
+      (Some(source), Some(line)) => write!(formatter, "{source}:{line}"),
+      (Some(source), None) => formatter.write_str(source),
+      _ => formatter.write_str("an unknown source"),
+    }
+  }
+}
+
+/// What is known about where a log line came from, beyond the line itself.
+#[derive(Debug, Default, Clone)]
+pub struct LineContext {
+  /// When the line was sent (e.g. the `Date` of the email carrying it), used to fill in the year for routers that
+  /// leave it out of their entries.
+  pub received: Option<DateTime>,
+
+  /// Where the line was read, passed on to each event found in it.
+  pub origin: Origin,
+
+  /// How the line is read.
+  pub grammar: Grammar,
+}
+
+/// Reads the `Day, Mon DD,YYYY HH:MM:SS` (or yearless) timestamp that ends an entry.
+pub fn trailing_timestamp(text: &str, context: &LineContext) -> Option<DateTime> {
+  match &text.split(' ').filter(|part| !part.is_empty()).collect::<Vec<&str>>()[..] {
+    [_day, month, stamp @ ..] if !stamp.is_empty() => DateTime::parse_log(month, &stamp.join(" "), context.received),
+    _ => None,
+  }
+}
+
+/// Splits an `ADDRESS:PORT` (or `[IPV6]:PORT`) pair, leaving the port out if there isn't one. Addresses are
+/// written the way `std::net` writes them, so the same host is counted once however the router spelled it.
+pub fn host_port(value: &str) -> (String, Option<u16>) {
+  if let Ok(socket) = value.parse::<std::net::SocketAddr>() {
+    return (socket.ip().to_string(), Some(socket.port()));
+  }
+
+  // A bare IPv6 address, whose groups would otherwise be mistaken for a port.
+  if let Ok(address) = value
+    .trim_start_matches('[')
+    .trim_end_matches(']')
+    .parse::<std::net::IpAddr>()
+  {
+    return (address.to_string(), None);
+  }
+
+  match value.rsplit_once(':') {
+    Some((address, port)) => match port.parse() {
+      Ok(port) => (address.to_string(), Some(port)),
+      Err(_) => (value.to_string(), None),
+    },
+    None => (value.to_string(), None),
+  }
+}
+
+/// Reads the `from source[:] ADDRESS[, port PORT]` (or `ADDRESS:PORT`) that starts many entries, returning the
+/// address and whatever follows (usually the timestamp).
+fn source(value: &str) -> Option<(String, &str)> {
+  let rest = value.strip_prefix(SOURCE_PREFIX)?.trim_start_matches(':').trim_start();
+  let (peer, rest) = rest.split_once(',')?;
+  let (address, _) = host_port(peer.trim());
+  let rest = rest.trim_start();
+  let rest = match rest.starts_with("port ") {
+    true => rest.split_once(',').map_or("", |(_, rest)| rest),
+    false => rest,
+  };
+
+  Some((address, rest))
+}
+
+fn dos_attack(kind: &str, value: &str, context: &LineContext) -> Option<DosAttack> {
+  let (address, rest) = source(value)?;
+
+  Some(DosAttack {
+    kind: Attack::parse(kind),
+    address,
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+fn admin_login(failed: bool, value: &str, context: &LineContext) -> Option<AdminLogin> {
+  let (address, rest) = source(value)?;
+
+  Some(AdminLogin {
+    address,
+    failed,
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+/// Reads the `from MAC address XX:XX:XX:XX:XX:XX, <timestamp>` of a rejected wireless device.
+fn wlan_rejected(reason: &str, value: &str, context: &LineContext) -> Option<WlanRejected> {
+  let rest = value.strip_prefix(MAC_PREFIX)?.trim_start_matches(':').trim_start();
+  let (mac, rest) = rest.split_once(',')?;
+
+  Some(WlanRejected {
+    reason: reason.trim_start_matches(':').trim().to_string(),
+    mac: mac.trim().to_ascii_lowercase(),
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+/// Reads the `to MAC address MAC, <timestamp>` of a lease; some firmware writes the address as `(ADDRESS)`.
+fn dhcp_lease(address: &str, value: &str, context: &LineContext) -> Option<DhcpLease> {
+  let rest = value
+    .strip_prefix(DHCP_MAC_PREFIX)?
+    .trim_start_matches(':')
+    .trim_start();
+  let (mac, rest) = rest.split_once(',')?;
+
+  Some(DhcpLease {
+    address: address.trim().trim_matches(['(', ')']).to_string(),
+    mac: mac.trim().to_ascii_lowercase(),
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+fn upnp_mapping(action: &str, value: &str, context: &LineContext) -> Option<UpnpMapping> {
+  let (address, rest) = source(value)?;
+
+  Some(UpnpMapping {
+    action: action.trim().to_ascii_lowercase(),
+    address,
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+/// Reads `IP address: ADDRESS, <timestamp>` for a connection, or just the timestamp for a disconnection.
+fn internet_connection(connected: bool, value: &str, context: &LineContext) -> InternetConnection {
+  let (address, rest) = match value
+    .strip_prefix(WAN_ADDRESS_PREFIX)
+    .and_then(|rest| rest.split_once(','))
+  {
+    Some((address, rest)) => (Some(address.trim().to_string()), rest),
+    None => (None, value),
+  };
+
+  InternetConnection {
+    connected,
+    address,
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  }
+}
+
+/// Reads a time sync, warning when its timestamp is later than the message carrying it was sent: the clock was
+/// set just then, so a mismatch means this router's timestamps (or the year given to them) can't be trusted.
+fn time_sync(server: &str, value: &str, context: &LineContext) -> TimeSync {
+  let timestamp = trailing_timestamp(value, context);
+
+  if let Some((synced, received)) = timestamp
+    .zip(context.received)
+    .filter(|(synced, received)| synced > received)
+  {
+    eprintln!("WARNING - time synchronized at {synced}, after the message was sent at {received}");
+  }
+
+  TimeSync {
+    server: Some(server.trim())
+      .filter(|server| !server.is_empty())
+      .map(str::to_string),
+    timestamp,
+    origin: context.origin.clone(),
+  }
+}
+
+fn blocked(service: bool, destination: &str, value: &str, context: &LineContext) -> Option<Blocked> {
+  let (address, rest) = source(value)?;
+
+  Some(Blocked {
+    service,
+    destination: destination.trim().to_string(),
+    address,
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+fn dynamic_dns(value: &str, context: &LineContext) -> Option<DynamicDns> {
+  let (update, rest) = value.strip_prefix(HOST_NAME_PREFIX)?.split_once(',')?;
+  let (host, outcome) = update.split_once(' ')?;
+
+  Some(DynamicDns {
+    host: host.to_string(),
+    succeeded: outcome.contains("success"),
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+async fn send(output: &channel::Sender<LogEvent>, event: LogEvent) -> Result<()> {
+  output.send(event).await.map_err(|error| {
+    eprintln!("WARNING - {error}");
+    Error::other(format!("{error}"))
+  })
+}
+
+/// Reads `from ADDRESS:PORT to LOCAL:PORT, <timestamp>`.
+fn remote_access(value: &str, context: &LineContext) -> Option<RemoteAccess> {
+  let (peer, rest) = value.strip_prefix("from ")?.split_once(" to ")?;
+  let (mine, rest) = rest.split_once(',')?;
+  let (address, port) = host_port(peer.trim());
+  let (local_address, local_port) = host_port(mine.trim());
+
+  Some(RemoteAccess {
+    address,
+    port,
+    local_address,
+    local_port,
+    protocol: None,
+    timestamp: trailing_timestamp(rest, context),
+    origin: context.origin.clone(),
+  })
+}
+
+impl LineContext {
+  /// The context of a line read as it is logged (syslog, the journal, a followed file), which is taken to have
+  /// been received just now.
+  pub fn live(source: std::sync::Arc<str>, line: Option<usize>, grammar: Grammar) -> Self {
+    Self {
+      received: Some(DateTime::now()),
+      grammar,
+      origin: Origin {
+        source: Some(source),
+        line,
+        ..Origin::default()
+      },
+    }
+  }
+}
+
+/// Reads the event a line describes. Lines that aren't any kind of entry we know about give `None`, and those that
+/// are but can't be read give the name of the kind; either way, the `--patterns` get a chance to read it instead.
+fn parse_event(line: &str, context: &LineContext) -> Option<std::result::Result<LogEvent, &'static str>> {
+  let event = match context.grammar.format {
+    Format::Netgear => netgear(line, context),
+    _ => crate::vendors::parse_event(line, context),
+  };
+
+  match event {
+    Some(Ok(event)) => Some(Ok(event)),
+    unread => context
+      .grammar
+      .patterns
+      .iter()
+      .find_map(|pattern| pattern.parse(line, context))
+      .or(unread),
+  }
+}
+
+fn netgear(line: &str, context: &LineContext) -> Option<std::result::Result<LogEvent, &'static str>> {
+  let event = match &line.split(LOG_LINE_DELIM).collect::<Vec<&str>>()[..] {
+    [REMOTE_ACCESS_PREFIX, value] => remote_access(value, context)
+      .map(LogEvent::RemoteAccess)
+      .ok_or("access"),
+
+    // Netgear has written both `DoS Attack` and `DoS attack` over the years.
+    [tag, value] if tag.to_ascii_lowercase().starts_with(DOS_ATTACK_PREFIX) => {
+      dos_attack(&tag[DOS_ATTACK_PREFIX.len()..], value, context)
+        .map(LogEvent::DosAttack)
+        .ok_or("dos attack")
+    }
+
+    [tag @ (ADMIN_LOGIN_TAG | ADMIN_LOGIN_FAILURE_TAG), value] => {
+      admin_login(*tag == ADMIN_LOGIN_FAILURE_TAG, value, context)
+        .map(LogEvent::AdminLogin)
+        .ok_or("admin login")
+    }
+
+    [tag, value] if tag.starts_with(WLAN_REJECTED_PREFIX) => {
+      wlan_rejected(&tag[WLAN_REJECTED_PREFIX.len()..], value, context)
+        .map(LogEvent::WlanRejected)
+        .ok_or("wlan")
+    }
+
+    [tag, value] if tag.starts_with(DHCP_PREFIX) => dhcp_lease(&tag[DHCP_PREFIX.len()..], value, context)
+      .map(LogEvent::DhcpLease)
+      .ok_or("dhcp"),
+
+    [tag, value] if tag.starts_with(UPNP_PREFIX) => upnp_mapping(&tag[UPNP_PREFIX.len()..], value, context)
+      .map(LogEvent::UpnpMapping)
+      .ok_or("upnp"),
+
+    [tag @ (INTERNET_CONNECTED_TAG | INTERNET_DISCONNECTED_TAG), value] => Ok(LogEvent::InternetConnection(
+      internet_connection(*tag == INTERNET_CONNECTED_TAG, value, context),
+    )),
+
+    [tag, value] if tag.starts_with(TIME_SYNC_PREFIX) => Ok(LogEvent::TimeSync(time_sync(
+      &tag[TIME_SYNC_PREFIX.len()..],
+      value,
+      context,
+    ))),
+
+    [tag, value] if tag.starts_with(EMAIL_SENT_PREFIX) => Ok(LogEvent::EmailSent(EmailSent {
+      recipient: tag[EMAIL_SENT_PREFIX.len()..].trim().to_string(),
+      timestamp: trailing_timestamp(value, context),
+      origin: context.origin.clone(),
+    })),
+
+    [tag, value] if tag.starts_with(SITE_BLOCKED_PREFIX) || tag.starts_with(SERVICE_BLOCKED_PREFIX) => {
+      let service = tag.starts_with(SERVICE_BLOCKED_PREFIX);
+      let destination = &tag[tag.find(": ").map_or(0, |start| start + 2)..];
+      blocked(service, destination, value, context)
+        .map(LogEvent::Blocked)
+        .ok_or("blocked")
+    }
+
+    [DYNAMIC_DNS_TAG, value] => dynamic_dns(value, context)
+      .map(LogEvent::DynamicDns)
+      .ok_or("dynamic dns"),
+
+    _ => return None,
+  };
+
+  Some(event)
+}
+
+/// The file lines that aren't events we know about are written to (`--unmatched-out`), shared by every source.
+#[derive(Debug, Clone)]
+pub struct Unmatched(std::sync::Arc<std::sync::Mutex<std::io::BufWriter<std::fs::File>>>);
+
+impl Unmatched {
+  pub fn create(path: &str) -> Result<Self> {
+    let file = std::fs::File::create(path)
+      .map_err(|error| Error::other(format!("invalid '--unmatched-out' '{path}' - {error}")))?;
+    Ok(Self(std::sync::Arc::new(std::sync::Mutex::new(
+      std::io::BufWriter::new(file),
+    ))))
+  }
+
+  /// Writes out, and empties, the lines collected by [`parse_line`].
+  pub fn drain(&self, peripheral: &mut Vec<String>) -> Result<()> {
+    let mut file = self.0.lock().map_err(|error| Error::other(format!("{error}")))?;
+
+    for line in peripheral.drain(..) {
+      writeln!(file, "{line}")?;
+    }
+
+    file.flush()
+  }
+}
+
+/// Matches a single router log line, sending any event it describes to `output`. Lines that are not events we
+/// know about, or can't be read, are collected into `peripheral` as `SOURCE:LINE<tab>LINE`.
+pub async fn parse_line(
+  line: &str,
+  context: &LineContext,
+  output: &channel::Sender<LogEvent>,
+  peripheral: &mut Vec<String>,
+) -> Result<()> {
+  // A stray `\r` (or BOM) left by a Windows editor would otherwise end up in the last token, e.g. the timestamp.
+  let line = line.trim_start_matches('\u{feff}').trim_end_matches(['\r', '\n']);
+
+  match parse_event(line, context) {
+    Some(Ok(event)) => send(output, event).await?,
+    Some(Err(kind)) if context.grammar.strict => {
+      return Err(Error::other(format!(
+        "unrecognized {kind} log at {} - '{line}'",
+        context.origin
+      )))
+    }
+    Some(Err(kind)) => {
+      eprintln!("unrecognized {kind} log - '{line}'");
+      peripheral.push(format!("{}\t{line}", context.origin));
+    }
+    None if line.trim().is_empty() => (),
+    None => peripheral.push(format!("{}\t{line}", context.origin)),
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn attacks_are_read_by_the_words_in_them() {
+    for (name, attack) in [
+      ("SYN Flood", Attack::SynFlood),
+      ("TCP SYN Flood", Attack::SynFlood),
+      ("syn-flood attack", Attack::SynFlood),
+      ("SYN/ACK Scan", Attack::SynAckScan),
+      ("ACK Scan", Attack::AckScan),
+      ("UDP Flood", Attack::UdpFlood),
+      ("ICMP Flood", Attack::IcmpFlood),
+      ("Ping of Death", Attack::PingOfDeath),
+      ("ICMP ping-of-death", Attack::PingOfDeath),
+      ("TCP Port Scan", Attack::PortScan),
+      ("Smurf Attack", Attack::Smurf),
+    ] {
+      assert_eq!(Attack::parse(name), attack, "{name}");
+    }
+
+    assert_eq!(
+      Attack::parse(" TCP/UDP Chargen "),
+      Attack::Other("TCP/UDP Chargen".into())
+    );
+    assert_eq!(Attack::parse("TCP/UDP Chargen").protocol(), Some("TCP/UDP"));
+  }
+
+  fn sent_in_january() -> LineContext {
+    LineContext {
+      received: DateTime::parse_iso("2022-01-04 09:00:00"),
+      ..LineContext::default()
+    }
+  }
+
+  #[test]
+  fn reads_netgear_entries() {
+    let context = sent_in_january();
+    let monday = DateTime::parse_iso("2022-01-03 08:00:00");
+
+    let line = "[LAN access from remote] from 7.7.7.7:4000 to 192.168.1.1:22, Monday, Jan 03,2022 08:00:00";
+    let Some(Ok(LogEvent::RemoteAccess(access))) = netgear(line, &context) else {
+      panic!("{line}");
+    };
+    assert_eq!(
+      (access.address.as_str(), access.port, access.local_port),
+      ("7.7.7.7", Some(4000), Some(22))
+    );
+    assert_eq!(access.timestamp, monday);
+
+    let line = "[DoS Attack: SYN Flood] from source: 1.2.3.4, port 80, Monday, Jan 03,2022 08:00:00";
+    let Some(Ok(LogEvent::DosAttack(attack))) = netgear(line, &context) else {
+      panic!("{line}");
+    };
+    assert_eq!((attack.kind, attack.address.as_str()), (Attack::SynFlood, "1.2.3.4"));
+    assert_eq!(attack.timestamp, monday);
+
+    let line = "[admin login failure] from source 1.2.3.4, Monday, Jan 03,2022 08:00:00";
+    let Some(Ok(LogEvent::AdminLogin(login))) = netgear(line, &context) else {
+      panic!("{line}");
+    };
+    assert!(login.failed);
+    assert_eq!(login.timestamp, monday);
+
+    // Routers that leave the year out write it as it was when the digest was sent, a year back for December.
+    let line = "[Internet connected] IP address: 5.6.7.8, Friday, Dec 31,23:59:59";
+    let Some(Ok(LogEvent::InternetConnection(connection))) = netgear(line, &context) else {
+      panic!("{line}");
+    };
+    assert_eq!(connection.address.as_deref(), Some("5.6.7.8"));
+    assert_eq!(connection.timestamp, DateTime::parse_iso("2021-12-31 23:59:59"));
+  }
+
+  #[test]
+  fn names_netgear_entries_it_cannot_read() {
+    let context = sent_in_january();
+
+    assert!(matches!(
+      netgear("[LAN access from remote] garbage", &context),
+      Some(Err("access"))
+    ));
+    assert!(matches!(
+      netgear("[DoS attack: Smurf] nowhere", &context),
+      Some(Err("dos attack"))
+    ));
+    assert!(netgear("Monday, Jan 03,2022 08:00:00", &context).is_none());
+  }
+
+  #[test]
+  fn splits_hosts_and_ports() {
+    for (value, host, port) in [
+      ("1.2.3.4:80", "1.2.3.4", Some(80)),
+      ("1.2.3.4", "1.2.3.4", None),
+      ("[2001:DB8:0::1]:443", "2001:db8::1", Some(443)),
+      ("2001:db8:0:0:0:0:0:1", "2001:db8::1", None),
+      ("[2001:db8::1]", "2001:db8::1", None),
+      ("router.local:8080", "router.local", Some(8080)),
+      ("router.local:http", "router.local:http", None),
+    ] {
+      assert_eq!(host_port(value), (host.to_string(), port), "{value}");
+    }
+  }
+}
@@ -1,174 +1,1174 @@
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, IsTerminal, Result};
 
 use async_std::channel;
-use async_std::io::prelude::BufReadExt;
+use async_std::io::prelude::{BufReadExt, ReadExt, SeekExt};
 use async_std::stream::StreamExt;
 
-const LOG_LINE_DELIM: &'static str = "] ";
-const REMOTE_ACCESS_PREFIX: &'static str = "[LAN access from remote";
+use cli::{Command, CommandLineOptions};
+use logs::LogEvent;
 
-#[derive(Debug, Default)]
-struct CommandLineOption<T> {
-  parsed: bool,
-  value: Option<T>,
+mod abuseipdb;
+mod archive;
+mod asn;
+mod bucket;
+mod cache;
+mod cli;
+mod clouds;
+mod dates;
+mod decode;
+mod email;
+mod fetch;
+mod follow;
+mod geoip;
+mod history;
+mod html;
+mod journal;
+mod logs;
+mod networks;
+mod patterns;
+mod resolve;
+mod sftp;
+mod sqlite;
+mod summary;
+mod syslog;
+mod tor;
+mod vendors;
+mod verify;
+mod walk;
+mod watch;
+mod web;
+mod whois;
+
+const STDIN_INPUT: &str = "-";
+const GZIP_EXTENSION: &str = "gz";
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// How long the aggregate needs to sit idle while running continuously (`--watch`, `--follow` or `listen`) before
+/// the counts are printed again.
+const WATCH_REPORT_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Settings that change how the content of every input is read, handed to each parsing task.
+#[derive(Debug, Default, Clone)]
+struct ParseSettings {
+  /// Treat every line as a log line, rather than reading each input as an email with headers to skip.
+  raw: bool,
+
+  /// Only messages from this sender or with this subject are read (`--from`/`--subject`).
+  filter: email::Filter,
+
+  /// Checks each message's DKIM signature (`--verify`), dropping the entries of any that fail.
+  verifier: Option<verify::Verifier>,
+
+  /// How the log lines are read (`--format`, `--patterns`).
+  grammar: logs::Grammar,
+
+  /// Where lines that aren't events are written (`--unmatched-out`); otherwise they're dropped.
+  unmatched: Option<logs::Unmatched>,
+
+  /// How many inputs have been read through, shared by every task.
+  inputs: std::sync::Arc<std::sync::atomic::AtomicUsize>,
 }
 
-impl<T> CommandLineOption<T> {
-  fn store(self, value: T) -> Self {
-    Self {
-      parsed: false,
-      value: Some(value),
-    }
+async fn parse_file<S>(input: S, settings: ParseSettings, output: channel::Sender<LogEvent>) -> Result<()>
+where
+  S: std::convert::AsRef<std::path::Path>,
+{
+  if let Some(kind) = archive::Kind::detect(input.as_ref()) {
+    return parse_archive(input.as_ref().to_path_buf(), kind, settings, output).await;
   }
+
+  let mut file = async_std::fs::File::open(input.as_ref()).await?;
+
+  let mut magic = [0u8; GZIP_MAGIC.len()];
+  let compressed = input.as_ref().extension().is_some_and(|ext| ext == GZIP_EXTENSION)
+    || (file.read_exact(&mut magic).await.is_ok() && magic == GZIP_MAGIC);
+  file.seek(std::io::SeekFrom::Start(0)).await?;
+
+  let reader = async_std::io::BufReader::new(file);
+  let source = format!("{:?}", input.as_ref());
+
+  if compressed {
+    let decoder = async_compression::futures::bufread::GzipDecoder::new(reader);
+    return parse(async_std::io::BufReader::new(decoder), source, settings, output).await;
+  }
+
+  parse(reader, source, settings, output).await
 }
 
-#[derive(Debug, Default)]
-struct CommandLineOptions {
-  input_dir: CommandLineOption<String>,
+async fn parse_contents(
+  contents: Vec<u8>,
+  source: String,
+  settings: ParseSettings,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  let compressed = contents.starts_with(&GZIP_MAGIC);
+  let reader = async_std::io::Cursor::new(contents);
+
+  if compressed {
+    let decoder = async_compression::futures::bufread::GzipDecoder::new(reader);
+    return parse(async_std::io::BufReader::new(decoder), source, settings, output).await;
+  }
+
+  parse(reader, source, settings, output).await
 }
 
-#[derive(Default, Debug)]
-struct EmailHead {
-  headers: std::collections::HashMap<String, String>,
-  done: bool,
+async fn parse_url(url: String, settings: ParseSettings, output: channel::Sender<LogEvent>) -> Result<()> {
+  let download = web::get(&url).await?;
+  eprintln!("checking '{url}'");
+
+  let reader = async_std::io::BufReader::new(download.body);
+
+  if download.compressed {
+    let decoder = async_compression::futures::bufread::GzipDecoder::new(reader);
+    return parse(async_std::io::BufReader::new(decoder), url, settings, output).await;
+  }
+
+  parse(reader, url, settings, output).await
 }
 
-impl EmailHead {
-  fn push<S>(&mut self, item: S) -> bool
-  where
-    S: std::convert::AsRef<str>,
-  {
-    if self.done == true {
-      return false;
-    }
+async fn parse_archive(
+  path: std::path::PathBuf,
+  kind: archive::Kind,
+  settings: ParseSettings,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  let members = archive::members(path.clone(), kind);
+  parse_members(format!("{path:?}"), members, settings, output).await
+}
 
-    if item.as_ref().len() == 0 {
-      self.done = true;
-      return true;
-    }
+/// Parses each member received from an archive or remote source, labelling progress with `source`.
+async fn parse_members(
+  source: String,
+  members: channel::Receiver<Result<archive::Member>>,
+  settings: ParseSettings,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  while let Ok(member) = members.recv().await {
+    let member = member?;
+    eprintln!("checking '{}' in '{source}'", member.name);
+    let name = format!("{} in {source}", member.name);
+    parse_contents(member.contents, name, settings.clone(), output.clone()).await?;
+  }
 
-    let mut parts = item.as_ref().split(": ");
-    let (key, value) = parts.next().zip(parts.next()).unwrap_or_else(|| ("".into(), "".into()));
-    self.headers.insert(key.to_string(), value.to_string());
+  Ok(())
+}
+
+/// Matches the log lines found in a message, each alongside the number of the line it was read from.
+async fn parse_entries(
+  entries: Vec<(usize, String)>,
+  message: &email::Message,
+  origin: &logs::Origin,
+  grammar: &logs::Grammar,
+  output: &channel::Sender<LogEvent>,
+  peripheral: &mut Vec<String>,
+) -> Result<()> {
+  let mut context = logs::LineContext {
+    received: message.date,
+    origin: logs::Origin {
+      message: message.metadata.clone(),
+      ..origin.clone()
+    },
+    grammar: grammar.clone(),
+  };
 
-    true
+  for (line, entry) in entries {
+    context.origin.line = Some(line);
+    logs::parse_line(&entry, &context, output, peripheral).await?;
   }
+
+  Ok(())
 }
 
-struct RemoteAccess {
-  address: String,
+/// Finishes a message, whose last line was the `origin`'s. When verifying, its entries were held back until now and
+/// are only matched if the raw message passes.
+async fn conclude(
+  message: &mut email::Message,
+  raw: Vec<u8>,
+  mut entries: Vec<(usize, String)>,
+  origin: &logs::Origin,
+  settings: &ParseSettings,
+  output: &channel::Sender<LogEvent>,
+  peripheral: &mut Vec<String>,
+) -> Result<()> {
+  let line = origin.line.unwrap_or_default();
+  entries.extend(message.finish().into_iter().map(|entry| (line, entry)));
+
+  if let Some(verifier) = settings.verifier.as_ref().filter(|_| !entries.is_empty()) {
+    if let verify::Verdict::Fail(reason) = verifier.verify(raw, &message.head).await {
+      let id = message.head.get("Message-ID").unwrap_or("without a Message-ID");
+      eprintln!("WARNING - rejecting message '{id}' - {reason}");
+      return Ok(());
+    }
+  }
+
+  parse_entries(entries, message, origin, &settings.grammar, output, peripheral).await?;
+
+  match settings.unmatched.as_ref() {
+    Some(unmatched) => unmatched.drain(peripheral),
+    None => Ok(()),
+  }
 }
 
-async fn parse<S>(input: S, output: channel::Sender<RemoteAccess>) -> Result<()>
+/// Reads the messages (or, with `--raw`, log lines) in `reader`, labelling any notice with `source`.
+async fn parse<R>(reader: R, source: String, settings: ParseSettings, output: channel::Sender<LogEvent>) -> Result<()>
 where
-  S: std::convert::AsRef<std::path::Path>,
+  R: async_std::io::BufRead + Unpin,
 {
-  let mut file = async_std::fs::File::open(input.as_ref()).await?;
-  let reader = async_std::io::BufReader::new(&mut file);
-
-  let mut lines = reader.lines();
-  let mut head = EmailHead::default();
+  // Lines are read as bytes since a body in some other charset isn't valid UTF-8 until it has been transcoded.
+  let mut lines = reader.split(b'\n');
+  let mut message = email::Message::new(settings.raw, settings.filter.clone());
   let mut peripheral = Vec::with_capacity(100);
+  let mut is_mbox = None;
+  let mut previous_blank = true;
+  let mut sniffed = false;
+  let mut origin = logs::Origin {
+    source: Some(source.as_str().into()),
+    ..logs::Origin::default()
+  };
+
+  // Only used when verifying: the message as received, and the entries waiting on its verdict.
+  let mut raw = Vec::new();
+  let mut held = Vec::new();
+
+  while let Some(line) = lines.next().await {
+    let number = origin.line.map_or(1, |previous| previous + 1);
+    origin.line = Some(number);
+
+    // What was read up to here still counts, but the rest of the file (e.g. past a corrupt stretch of a gzip) can't
+    // be read.
+    let mut line = match line {
+      Ok(line) => line,
+      Err(error) => {
+        eprintln!("WARNING - unable to read line {number} of '{source}', skipping the rest - {error}");
+        break;
+      }
+    };
+
+    if line.ends_with(b"\r") {
+      line.pop();
+    }
+
+    // Files saved on Windows may open with a byte order mark, which would otherwise hide the first header.
+    if is_mbox.is_none() && line.starts_with(UTF8_BOM) {
+      line.drain(..UTF8_BOM.len());
+    }
+
+    // Anything without headers (or, raw or not, with NUL bytes) is some other kind of file that happens to be
+    // alongside the digests; its lines would only pile up unmatched.
+    if !sniffed && !line.is_empty() {
+      sniffed = true;
+      let reason = match line.contains(&0) {
+        true => Some("looks like a binary file"),
+        false => (!settings.raw && !email::is_mbox_separator(&line) && !email::is_field(&line))
+          .then_some("doesn't start with email headers"),
+      };
+
+      if let Some(reason) = reason {
+        eprintln!("skipping '{source}' - {reason}");
+        return Ok(());
+      }
+    }
+
+    // An mbox opens with a `From ` envelope line, and every later one that follows a blank line starts the next
+    // message; a file whose first line isn't one (a header, or even a blank line) is a single message.
+    let separator = previous_blank && email::is_mbox_separator(&line);
+    let mbox = *is_mbox.get_or_insert(separator);
+    previous_blank = line.is_empty();
 
-  while let Some(Ok(line)) = lines.next().await {
-    if head.push(&line) {
+    if mbox && separator {
+      let mut finished = std::mem::replace(&mut message, email::Message::new(settings.raw, settings.filter.clone()));
+      let (raw, held) = (std::mem::take(&mut raw), std::mem::take(&mut held));
+      conclude(&mut finished, raw, held, &origin, &settings, &output, &mut peripheral).await?;
       continue;
     }
 
-    match &line.split(LOG_LINE_DELIM).collect::<Vec<&str>>()[..] {
-      [REMOTE_ACCESS_PREFIX, value] => match &value.split(" ").collect::<Vec<&str>>()[..] {
-        ["from", peer, "to", _mine, _day, _mon, _date, _time] => {
-          let mut bits = peer.split(":");
-          let (peer_ip, _peer_port) = (bits.next(), bits.next());
-          let key = format!("{}", peer_ip.unwrap_or("unknown"));
-          output.send(RemoteAccess { address: key }).await.map_err(|error| {
-            println!("WARNING - {error}");
-            Error::new(ErrorKind::Other, format!("{error}"))
-          })?;
-        }
-        other => println!("unrecognized access log - '{}'", other.join("|")),
-      },
+    let entries = message.push(&line).into_iter().map(|entry| (number, entry)).collect();
 
-      other => peripheral.push(other.join(LOG_LINE_DELIM)),
+    if settings.verifier.is_none() {
+      parse_entries(entries, &message, &origin, &settings.grammar, &output, &mut peripheral).await?;
+      continue;
     }
+
+    // Signatures are computed over CRLF line endings.
+    raw.extend_from_slice(&line);
+    raw.extend_from_slice(b"\r\n");
+    held.extend(entries);
   }
 
+  conclude(&mut message, raw, held, &origin, &settings, &output, &mut peripheral).await?;
+  settings.inputs.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
   Ok(())
 }
 
-async fn run(mut options: CommandLineOptions) -> Result<()> {
-  let dir = options
-    .input_dir
+fn scan(
+  options: &mut CommandLineOptions,
+  settings: ParseSettings,
+  sender: channel::Sender<LogEvent>,
+) -> Result<Vec<async_std::task::JoinHandle<Result<()>>>> {
+  let journal = match (
+    options.journal_unit.value.take(),
+    options.journal_identifier.value.take(),
+  ) {
+    (None, None) => None,
+    (unit, identifier) => Some(journal::JournalFilter {
+      unit,
+      identifier,
+      follow: options.watch,
+      grammar: settings.grammar.clone(),
+    }),
+  };
+
+  let follow = options.follow.value.take().map(std::path::PathBuf::from);
+  let streaming = journal.is_some() || follow.is_some();
+
+  let mut inputs = options.inputs.value.take().unwrap_or_default();
+
+  for list in options.input_lists.value.take().unwrap_or_default() {
+    inputs.extend(input_list(&list)?);
+  }
+
+  // Stdin is only read implicitly when nothing else, including the journal or a followed file, was asked for.
+  let inputs = Some(inputs)
+    .filter(|inputs| !inputs.is_empty())
+    .or_else(|| (!streaming && !std::io::stdin().is_terminal()).then(|| vec![STDIN_INPUT.to_string()]))
+    .or_else(|| streaming.then(Vec::new))
+    .ok_or_else(|| Error::other("no '--input' (or '--input-dir', '--input-list')"))?;
+
+  let glob = options
+    .glob
+    .value
+    .take()
+    .map(|pattern| glob::Pattern::new(&pattern))
+    .transpose()
+    .map_err(|error| Error::other(format!("invalid '--glob' - {error}")))?;
+
+  let mut walker = walk::Walker::default();
+  walker.recursive = options.recursive;
+  walker.glob = glob;
+  walker.follow_symlinks = !options.no_follow_symlinks;
+  walker.dates = filename_dates(options)?;
+  walker.exclude = options
+    .exclude
     .value
     .take()
-    .and_then(|attempt| {
-      let path = std::path::Path::new(&attempt);
+    .unwrap_or_default()
+    .iter()
+    .map(|pattern| pattern.parse())
+    .collect::<std::result::Result<_, _>>()
+    .map_err(|error| Error::other(format!("invalid '--exclude' - {error}")))?;
 
-      if path.is_dir() {
-        Some(std::path::PathBuf::from(path))
-      } else {
-        None
+  let bucket_config = bucket::BucketConfig {
+    endpoint: options.s3_endpoint.value.take(),
+    region: options.s3_region.value.take(),
+  };
+
+  let mut roots = Vec::with_capacity(inputs.len());
+
+  // Remote and journal sources are where failures (credentials, missing tools) are likely, so their tasks are
+  // handed back to be awaited rather than left detached. With `--strict` an unreadable entry is a failure too, so
+  // local inputs are awaited as well.
+  let mut producers = Vec::with_capacity(inputs.len());
+  let strict = settings.grammar.strict;
+
+  for input in inputs {
+    let path = std::path::PathBuf::from(&input);
+
+    if web::is_url(&input) {
+      producers.push(async_std::task::spawn(parse_url(
+        input,
+        settings.clone(),
+        sender.clone(),
+      )));
+    } else if input.starts_with(sftp::SCHEME) {
+      let files = sftp::files(&input, options.recursive)?;
+      producers.push(async_std::task::spawn(parse_members(
+        input,
+        files,
+        settings.clone(),
+        sender.clone(),
+      )));
+    } else if input.starts_with(bucket::SCHEME) {
+      let objects = bucket::objects(&input, &bucket_config)?;
+      producers.push(async_std::task::spawn(parse_members(
+        input,
+        objects,
+        settings.clone(),
+        sender.clone(),
+      )));
+    } else if input == STDIN_INPUT {
+      eprintln!("checking stdin");
+      let reader = async_std::io::BufReader::new(async_std::io::stdin());
+      let task = async_std::task::spawn(parse(reader, "stdin".into(), settings.clone(), sender.clone()));
+      producers.extend(Some(task).filter(|_| strict));
+    } else if path.is_dir() {
+      for file in walker.walk(path.clone())? {
+        let task = async_std::task::spawn(parse_file(file, settings.clone(), sender.clone()));
+        producers.extend(Some(task).filter(|_| strict));
       }
-    })
-    .ok_or_else(|| Error::new(ErrorKind::Other, "no '--input-dur'"))?;
 
-  let mut mappings = std::collections::HashMap::with_capacity(1000);
+      roots.push(path);
+    } else if path.is_file() {
+      eprintln!("checking '{path:?}'");
+      let task = async_std::task::spawn(parse_file(path, settings.clone(), sender.clone()));
+      producers.extend(Some(task).filter(|_| strict));
+    } else {
+      return Err(Error::other(format!("'{input}' is not a file or directory")));
+    }
+  }
 
-  let (sender, receiver) = channel::bounded(4);
-  let mut entries = dir.read_dir()?;
+  if let Some(filter) = journal {
+    producers.push(async_std::task::spawn(journal::read(
+      filter,
+      settings.unmatched.clone(),
+      sender.clone(),
+    )));
+  }
+
+  if let Some(path) = follow {
+    producers.push(async_std::task::spawn(follow::follow(
+      path,
+      settings.grammar.clone(),
+      settings.unmatched.clone(),
+      sender.clone(),
+    )));
+  }
+
+  if options.watch && !roots.is_empty() {
+    let watch = watch::watch(&roots, options.recursive)?;
+    async_std::task::spawn(parse_arrivals(watch, roots, walker, settings, sender));
+  }
+
+  Ok(producers)
+}
+
+/// Reads the inputs named one per line in an `--input-list` file (`-` for stdin, e.g. piped from `find`), skipping
+/// blank lines and `#` comments.
+fn input_list(list: &str) -> Result<Vec<String>> {
+  let contents = match list {
+    STDIN_INPUT => std::io::read_to_string(std::io::stdin())?,
+    path => std::fs::read_to_string(path)
+      .map_err(|error| Error::other(format!("invalid '--input-list' '{path}' - {error}")))?,
+  };
+
+  Ok(
+    contents
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(str::to_string)
+      .collect(),
+  )
+}
+
+/// Reads the `--format` and any `--patterns` files.
+fn grammar(options: &mut CommandLineOptions) -> Result<logs::Grammar> {
+  let mut patterns = Vec::new();
+
+  for path in options.patterns.value.take().unwrap_or_default() {
+    patterns.extend(patterns::load(&path)?);
+  }
+
+  Ok(logs::Grammar {
+    format: options
+      .format
+      .value
+      .take()
+      .map(|format| format.parse())
+      .transpose()?
+      .unwrap_or_default(),
+    patterns: patterns.into(),
+    strict: options.strict,
+  })
+}
 
-  println!("scanning '{dir:?}'");
+/// Builds the filename date filter from `--filename-since`/`--filename-until`, if either was given.
+fn filename_dates(options: &mut CommandLineOptions) -> Result<Option<dates::FilenameDates>> {
+  let since = options.filename_since.value.take();
+  let until = options.filename_until.value.take();
 
-  while let Some(Ok(entry)) = entries.next() {
-    if entry.path().is_dir() == true {
+  if since.is_none() && until.is_none() {
+    return Ok(None);
+  }
+
+  let pattern = options
+    .filename_pattern
+    .value
+    .take()
+    .unwrap_or_else(|| dates::DEFAULT_FILENAME_PATTERN.to_string())
+    .parse()?;
+
+  Ok(Some(dates::FilenameDates {
+    pattern,
+    since: since
+      .map(|since| since.parse())
+      .transpose()
+      .map_err(|error| Error::other(format!("invalid '--filename-since' - {error}")))?,
+    until: until
+      .map(|until| until.parse())
+      .transpose()
+      .map_err(|error| Error::other(format!("invalid '--filename-until' - {error}")))?,
+  }))
+}
+
+/// Parses files as they appear in any of the watched `roots`, holding on to `output` indefinitely.
+async fn parse_arrivals(
+  watch: watch::Watch,
+  roots: Vec<std::path::PathBuf>,
+  mut walker: walk::Walker,
+  settings: ParseSettings,
+  output: channel::Sender<LogEvent>,
+) {
+  while let Ok(path) = watch.paths.recv().await {
+    let Some(root) = roots.iter().find(|root| path.starts_with(root)) else {
+      continue;
+    };
+
+    if !path.is_file() || !walker.accept(root, &path) {
       continue;
     }
 
-    let name = entry.file_name();
-    println!("checking '{name:?}'");
+    eprintln!("checking '{path:?}'");
+    async_std::task::spawn(parse_file(path, settings.clone(), output.clone()));
+  }
+}
+
+/// Reads how the counts are broken down in the report (`--group-by`, `--bucket`, `--top`, `--min-count`,
+/// `--aggregate`, `--session-gap`, `--rollup`, `--compare`, `--heatmap`) and how it's written (`--output`, or
+/// `report --html`), the range of time counted (`--since`/`--until`), what addresses are annotated with
+/// (`--annotate`, `--geoip`, `--asn`, `--whois`, `--blocklist`, `--cloud`, `--tor`) and the `--history` of earlier
+/// runs. Loading a `--tor` exit list may mean fetching it.
+async fn report_settings(options: &mut CommandLineOptions) -> Result<summary::ReportSettings> {
+  let mut ignored = Vec::new();
+
+  for (name, values) in [
+    ("--ignore-ip", options.ignore_ips.value.take()),
+    ("--ignore-cidr", options.ignore_cidrs.value.take()),
+  ] {
+    for value in values.unwrap_or_default() {
+      ignored.push(
+        value
+          .parse::<networks::Network>()
+          .map_err(|error| Error::other(format!("invalid '{name}' - {error}")))?,
+      );
+    }
+  }
+
+  let rollups = options
+    .rollups
+    .value
+    .take()
+    .unwrap_or_default()
+    .iter()
+    .map(|rollup| rollup.parse())
+    .collect::<Result<Vec<_>>>()
+    .map_err(|error| Error::other(format!("invalid '--rollup' - {error}")))?;
 
-    async_std::task::spawn(parse(entry.path(), sender.clone()));
+  for path in options.ignore_files.value.take().unwrap_or_default() {
+    ignored.extend(networks::load(&path).map_err(|error| Error::other(format!("invalid '--ignore-file' {error}")))?);
   }
 
-  // With all tasks spawned, drop our copy of the sender.
-  drop(sender);
+  let groupings = options
+    .group_by
+    .value
+    .take()
+    .unwrap_or_default()
+    .iter()
+    .map(|grouping| grouping.parse())
+    .collect::<Result<Vec<_>>>()
+    .map_err(|error| Error::other(format!("invalid '--group-by' - {error}")))?;
 
-  while let Ok(next) = receiver.recv().await {
-    let existing = mappings.remove(&next.address).unwrap_or(0u32);
+  let geoip = options
+    .geoip
+    .value
+    .take()
+    .map(|path| geoip::GeoIp::open(&path))
+    .transpose()?;
 
-    mappings.insert(next.address, existing + 1);
+  if groupings.contains(&summary::Grouping::Country) && geoip.is_none() {
+    return Err(Error::other(
+      "invalid '--group-by' - 'country' needs a GeoIP database ('--geoip')",
+    ));
   }
 
-  println!("done receiving");
+  let asn = options.asn.value.take().map(|path| asn::Asn::open(&path)).transpose()?;
 
-  let mut hidden = 0;
-  let total = mappings.len();
+  if groupings.contains(&summary::Grouping::Asn) && asn.is_none() {
+    return Err(Error::other(
+      "invalid '--group-by' - 'asn' needs an ASN database ('--asn')",
+    ));
+  }
 
-  for (key, value) in mappings.into_iter() {
-    if value > 100 {
-      println!("{:?}: {:?}", key, value);
-    } else {
-      hidden += 1;
+  let bucket = options
+    .bucket
+    .value
+    .take()
+    .map(|bucket| dates::parse_duration(&bucket))
+    .transpose()
+    .map_err(|error| Error::other(format!("invalid '--bucket' - {error}")))?;
+  let session_gap = options
+    .session_gap
+    .value
+    .take()
+    .map(|gap| dates::parse_duration(&gap))
+    .transpose()
+    .map_err(|error| Error::other(format!("invalid '--session-gap' - {error}")))?;
+  let top = options
+    .top
+    .value
+    .take()
+    .map(|top| top.parse::<usize>())
+    .transpose()
+    .map_err(|error| Error::other(format!("invalid '--top' - {error}")))?;
+
+  // Asking for the `--top` sources is asking for that many, however few accesses they had.
+  let min_count = options
+    .min_count
+    .value
+    .take()
+    .map(|count| count.parse::<u32>())
+    .transpose()
+    .map_err(|error| Error::other(format!("invalid '--min-count' - {error}")))?
+    .unwrap_or(match top {
+      Some(_) => 0,
+      None => summary::DEFAULT_MIN_COUNT,
+    });
+
+  let aggregate = options
+    .aggregate
+    .value
+    .take()
+    .map(|aggregate| aggregate.parse())
+    .transpose()
+    .map_err(|error| Error::other(format!("invalid '--aggregate' - {error}")))?
+    .unwrap_or_default();
+
+  Ok(summary::ReportSettings {
+    groupings,
+    bucket,
+    top,
+    min_count,
+    aggregate,
+    session_gap,
+    ignored,
+    rollups,
+    geoip,
+    asn,
+    blocklists: options
+      .blocklists
+      .value
+      .take()
+      .unwrap_or_default()
+      .into_iter()
+      .map(|path| {
+        let networks = networks::load(&path).map_err(|error| Error::other(format!("invalid '--blocklist' {error}")))?;
+        Ok((networks::list_name(&path), networks))
+      })
+      .collect::<Result<Vec<_>>>()?,
+    annotations: networks::load_labels(&options.annotations.value.take().unwrap_or_default())
+      .map_err(|error| Error::other(format!("invalid '--annotate' {error}")))?,
+    clouds: options
+      .clouds
+      .value
+      .take()
+      .unwrap_or_default()
+      .iter()
+      .map(|value| clouds::load(value))
+      .collect::<Result<Vec<_>>>()?,
+    tor_exits: match options.tor.value.take() {
+      Some(source) => Some(tor::load(&source).await?),
+      None => None,
+    },
+    whois: options
+      .whois
+      .value
+      .take()
+      .map(|count| count.parse::<usize>())
+      .transpose()
+      .map_err(|error| Error::other(format!("invalid '--whois' - {error}")))?,
+    compare: options
+      .compare
+      .value
+      .take()
+      .map(|compare| match compare.strip_prefix("previous-") {
+        Some(period) => period.parse(),
+        None => Err(Error::other(format!(
+          "unknown comparison '{compare}' (expected 'previous-week' or 'previous-month')"
+        ))),
+      })
+      .transpose()
+      .map_err(|error| Error::other(format!("invalid '--compare' - {error}")))?,
+    heatmap: options
+      .heatmap
+      .value
+      .take()
+      .map(|heatmap| heatmap.parse())
+      .transpose()
+      .map_err(|error| Error::other(format!("invalid '--heatmap' - {error}")))?,
+    output: match (options.command, options.html.value.take()) {
+      (Command::Report, Some(path)) => summary::Output::Html(path),
+      (Command::Report, None) => {
+        return Err(Error::other("'report' needs where to write it, e.g. '--html out.html'"));
+      }
+      (_, Some(_)) => return Err(Error::other("'--html' is only for 'report'")),
+      (_, None) => options
+        .output
+        .value
+        .take()
+        .map(|output| output.parse())
+        .transpose()
+        .map_err(|error| Error::other(format!("invalid '--output' - {error}")))?
+        .unwrap_or_default(),
+    },
+    since: options
+      .since
+      .value
+      .take()
+      .map(|since| dates::DateTime::parse_bound(&since, false))
+      .transpose()
+      .map_err(|error| Error::other(format!("invalid '--since' - {error}")))?,
+    until: options
+      .until
+      .value
+      .take()
+      .map(|until| dates::DateTime::parse_bound(&until, true))
+      .transpose()
+      .map_err(|error| Error::other(format!("invalid '--until' - {error}")))?,
+    history: options
+      .history
+      .value
+      .take()
+      .map(|path| history::History::load(&path))
+      .transpose()?,
+  })
+}
+
+fn fetch_config(options: &mut CommandLineOptions) -> Result<fetch::FetchConfig> {
+  let protocol = options
+    .protocol
+    .value
+    .take()
+    .map(|protocol| protocol.parse::<fetch::Protocol>())
+    .transpose()?
+    .unwrap_or_default();
+  let host = options.host.value.take().ok_or_else(|| Error::other("no '--host'"))?;
+  let user = options.user.value.take().ok_or_else(|| Error::other("no '--user'"))?;
+  let password = std::env::var(fetch::PASSWORD_ENV)
+    .map_err(|_| Error::other(format!("no password in '{}'", fetch::PASSWORD_ENV)))?;
+
+  let port = options
+    .port
+    .value
+    .take()
+    .map(|port| port.parse::<u16>())
+    .transpose()
+    .map_err(|error| Error::other(format!("invalid '--port' - {error}")))?
+    .unwrap_or_else(|| protocol.default_port());
+
+  Ok(fetch::FetchConfig {
+    protocol,
+    host,
+    port,
+    user,
+    password,
+    folder: options
+      .folder
+      .value
+      .take()
+      .unwrap_or_else(|| fetch::DEFAULT_FOLDER.to_string()),
+    from: options.from.value.take(),
+    subject: options.subject.value.take(),
+    delete: options.delete,
+  })
+}
+
+/// The file a downloaded message is kept in under `--cache-dir`. Its name comes from the server (a POP3 UIDL, say),
+/// so anything but `[A-Za-z0-9._-]` is replaced, and it can't be `..` or hidden.
+fn cache_name(name: &str) -> String {
+  let name = name
+    .chars()
+    .map(|character| match character {
+      'A'..='Z' | 'a'..='z' | '0'..='9' | '.' | '_' | '-' => character,
+      _ => '_',
+    })
+    .collect::<String>();
+
+  match name.trim_start_matches('.') {
+    "" => "_".to_string(),
+    name => name.to_string(),
+  }
+}
+
+async fn download(
+  messages: channel::Receiver<Result<archive::Member>>,
+  cache: Option<std::path::PathBuf>,
+  settings: ParseSettings,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  if let Some(dir) = cache.as_ref() {
+    async_std::fs::create_dir_all(dir).await?;
+  }
+
+  while let Ok(message) = messages.recv().await {
+    let message = message?;
+    eprintln!("checking '{}'", message.name);
+
+    if let Some(dir) = cache.as_ref() {
+      async_std::fs::write(dir.join(cache_name(&message.name)), &message.contents).await?;
     }
+
+    parse_contents(message.contents, message.name, settings.clone(), output.clone()).await?;
   }
 
-  println!("{hidden} hidden entries (of {})", total);
+  Ok(())
+}
+
+/// Reads the settings every parsing task is handed from the options.
+fn parse_settings(options: &mut CommandLineOptions) -> Result<ParseSettings> {
+  Ok(ParseSettings {
+    raw: options.raw,
+    filter: email::Filter {
+      from: options.from.value.clone(),
+      subject: options.subject.value.clone(),
+      ..email::Filter::default()
+    },
+    grammar: grammar(options)?,
+    unmatched: options
+      .unmatched_out
+      .value
+      .take()
+      .map(|path| logs::Unmatched::create(&path))
+      .transpose()?,
+    inputs: Default::default(),
+    verifier: match options.verify || options.verify_spf {
+      true if options.raw => return Err(Error::other("'--verify' needs email input, not '--raw'")),
+      true => Some(verify::start(options.verify_spf)?),
+      false => None,
+    },
+  })
+}
+
+/// Parses both of the `diff` inputs in full, with the same options, and reports how the later one differs. Either
+/// may instead be what an earlier run exported with `--output json` or `--output csv`.
+async fn diff(options: CommandLineOptions) -> Result<()> {
+  let (earlier, later) = options
+    .diff
+    .clone()
+    .ok_or_else(|| Error::other("'diff' needs two inputs to compare, e.g. 'rupert diff last-week this-week'"))?;
+  let mut summaries = Vec::new();
+
+  for input in [earlier, later] {
+    let mut side = options.clone();
+    side.inputs.value = Some(vec![input.clone()]);
+
+    let mut summary = summary::Summary::new(report_settings(&mut side).await?);
+
+    if summary.import(&input)? {
+      summaries.push(summary);
+      continue;
+    }
+
+    let (sender, receiver) = channel::bounded(4);
+    let settings = parse_settings(&mut side)?;
+    let producers = scan(&mut side, settings, sender)?;
+
+    while let Ok(next) = receiver.recv().await {
+      summary.record(next);
+    }
+
+    for producer in producers {
+      producer.await?;
+    }
+
+    summaries.push(summary);
+  }
+
+  if let [earlier, later] = summaries.as_slice() {
+    earlier.report_diff(later);
+  }
 
   Ok(())
 }
 
-fn main() -> Result<()> {
-  let opts = std::env::args().fold(CommandLineOptions::default(), |mut opts, item| {
-    if opts.input_dir.parsed {
-      opts.input_dir = opts.input_dir.store(item.clone());
+/// The online services the addresses in the report are looked up with before it's printed.
+#[derive(Debug, Default)]
+struct Lookups {
+  /// Host names (`--resolve`), and the DNS blocklists addresses are checked against (`--dnsbl`).
+  resolver: Option<resolve::Resolver>,
+  resolve: bool,
+  dnsbls: Vec<String>,
+
+  /// Reputations (`--abuseipdb`), and whether offenders are reported back (`--report`).
+  abuseipdb: Option<abuseipdb::Client>,
+  report: bool,
+
+  /// What earlier runs found (`--cache`), used instead of looking the same addresses up again.
+  cache: Option<cache::Cache>,
+}
+
+impl Lookups {
+  fn start(options: &CommandLineOptions) -> Result<Self> {
+    let abuseipdb = match options.abuseipdb {
+      true => Some(abuseipdb::Client::new(std::env::var(abuseipdb::KEY_ENV).map_err(
+        |_| Error::other(format!("'--abuseipdb' needs an API key in '{}'", abuseipdb::KEY_ENV)),
+      )?)),
+      false if options.report => return Err(Error::other("'--report' needs '--abuseipdb'")),
+      false => None,
+    };
+    let dnsbls = options.dnsbls.value.clone().unwrap_or_default();
+
+    Ok(Self {
+      resolver: (options.resolve || !dnsbls.is_empty())
+        .then(resolve::start)
+        .transpose()?,
+      resolve: options.resolve,
+      dnsbls,
+      abuseipdb,
+      report: options.report,
+      cache: options.cache.value.as_deref().map(cache::Cache::load).transpose()?,
+    })
+  }
+}
+
+/// Prints the report, first looking up the addresses in it with whatever `lookups` were asked for (and the
+/// registrations of the busiest if `--whois` was).
+async fn report(summary: &mut summary::Summary, lookups: &mut Lookups, inputs: &std::sync::atomic::AtomicUsize) {
+  let cache = &mut lookups.cache;
+
+  if let Some(resolver) = lookups.resolver.as_ref().filter(|_| lookups.resolve) {
+    let mut addresses = summary.reported();
+
+    if let Some(cache) = cache.as_ref() {
+      let cached = addresses
+        .iter()
+        .filter_map(|address| Some((address.clone(), cache.name(address)?)))
+        .collect::<Vec<_>>();
+      addresses.retain(|address| cache.name(address).is_none());
+      summary.name(
+        cached
+          .into_iter()
+          .filter_map(|(address, name)| Some((address, name?)))
+          .collect(),
+      );
     }
 
-    if item == "--input-dir" {
-      opts.input_dir.parsed = true;
+    let names = resolver.names(addresses.clone()).await;
+
+    if let Some(cache) = cache.as_mut() {
+      for address in addresses
+        .iter()
+        .filter(|address| address.parse::<std::net::IpAddr>().is_ok())
+      {
+        cache.set_name(address, names.get(address));
+      }
     }
 
-    opts
-  });
+    summary.name(names);
+  }
+
+  if let Some(resolver) = lookups.resolver.as_ref().filter(|_| !lookups.dnsbls.is_empty()) {
+    let addresses = summary.unlisted();
+    let listings = resolver.listings(addresses.clone(), lookups.dnsbls.clone()).await;
+    summary.list(addresses, listings);
+  }
+
+  for address in summary.unregistered() {
+    if let Some(record) = cache.as_ref().and_then(|cache| cache.registration(&address)) {
+      summary.register(address, Some(record));
+      continue;
+    }
 
+    let record = whois::lookup(&address).await;
+
+    // Only what was found, since not finding anything is more likely the registry not answering.
+    if let (Some(cache), Some(record)) = (cache.as_mut(), record.as_ref()) {
+      cache.set_registration(&address, record);
+    }
+
+    summary.register(address, record);
+  }
+
+  if let Some(client) = lookups.abuseipdb.as_mut() {
+    for address in summary.unrated() {
+      if let Some(score) = cache.as_ref().and_then(|cache| cache.reputation(&address)) {
+        summary.rate(address, Some(score));
+        continue;
+      }
+
+      match client.check(&address).await {
+        Ok(score) => {
+          if let Some(cache) = cache.as_mut() {
+            cache.set_reputation(&address, score);
+          }
+          summary.rate(address, Some(score))
+        }
+        // Most likely the rate limit, so the rest are left for the next report.
+        Err(error) => {
+          eprintln!("WARNING - checking '{address}' failed - {error}");
+          summary.rate(address, None);
+          break;
+        }
+      }
+    }
+
+    for (address, categories, comment) in summary.offenders().into_iter().filter(|_| lookups.report) {
+      if let Err(error) = client.report(&address, &categories, &comment).await {
+        eprintln!("WARNING - reporting '{address}' failed - {error}");
+        break;
+      }
+
+      summary.submitted(address);
+    }
+  }
+
+  if let Some(Err(error)) = cache.as_ref().map(cache::Cache::save) {
+    eprintln!("WARNING - {error}");
+  }
+
+  summary.report(inputs.load(std::sync::atomic::Ordering::Relaxed));
+}
+
+async fn run(mut options: CommandLineOptions) -> Result<()> {
+  if options.command == Command::Diff {
+    return diff(options).await;
+  }
+
+  let mut summary = summary::Summary::new(report_settings(&mut options).await?);
+
+  let (sender, receiver) = channel::bounded(4);
+
+  let continuous = options.watch || options.follow.value.is_some() || options.command == Command::Listen;
+
+  // Streaming the events leaves stdout to them alone, in place of the report.
+  let events = match options.events.value.take().as_deref() {
+    Some("ndjson") => true,
+    Some(other) => {
+      return Err(Error::other(format!(
+        "invalid '--events' - unknown format '{other}' (expected 'ndjson')"
+      )))
+    }
+    None => false,
+  };
+
+  let settings = parse_settings(&mut options)?;
+  let inputs = settings.inputs.clone();
+  let mut lookups = Lookups::start(&options)?;
+
+  let producers = match options.command {
+    Command::Scan | Command::Diff | Command::Report => scan(&mut options, settings, sender)?,
+    Command::Fetch => {
+      let messages = fetch::messages(fetch_config(&mut options)?);
+      let cache = options.cache_dir.value.take().map(std::path::PathBuf::from);
+      vec![async_std::task::spawn(download(messages, cache, settings, sender))]
+    }
+    Command::Listen => {
+      let address = options
+        .syslog
+        .value
+        .take()
+        .unwrap_or_else(|| syslog::DEFAULT_ADDRESS.to_string());
+      vec![async_std::task::spawn(syslog::listen(
+        address,
+        settings.grammar,
+        settings.unmatched,
+        sender,
+      ))]
+    }
+  };
+
+  let mut dirty = false;
+
+  loop {
+    let next = match continuous {
+      false => receiver.recv().await,
+      true => match async_std::future::timeout(WATCH_REPORT_DELAY, receiver.recv()).await {
+        Ok(next) => next,
+        Err(_) => {
+          if dirty && !events {
+            report(&mut summary, &mut lookups, &inputs).await;
+            dirty = false;
+          }
+
+          continue;
+        }
+      },
+    };
+
+    let Ok(next) = next else {
+      break;
+    };
+
+    if options.verbose {
+      describe(&next);
+    }
+
+    if events {
+      println!("{}", next.to_json());
+    }
+
+    summary.record(next);
+    dirty = true;
+  }
+
+  eprintln!("done receiving");
+
+  for producer in producers {
+    producer.await?;
+  }
+
+  if !events {
+    report(&mut summary, &mut lookups, &inputs).await;
+  }
+
+  summary.save_history()
+}
+
+/// Prints a single event, along with the email it was found in (`--verbose`). Like progress and warnings it goes to
+/// stderr, leaving stdout to the report or the `--events` stream.
+fn describe(event: &LogEvent) {
+  let when = event
+    .timestamp()
+    .map(|timestamp| timestamp.to_string())
+    .unwrap_or_else(|| "an unknown time".into());
+
+  let origin = event.origin();
+  let message = origin
+    .message
+    .as_ref()
+    .map(|message| {
+      format!(
+        " in '{}' ({}, sent {})",
+        message.subject.as_deref().unwrap_or("no subject"),
+        message.message_id.as_deref().unwrap_or("no Message-ID"),
+        message.date.as_deref().unwrap_or("at an unknown time"),
+      )
+    })
+    .unwrap_or_default();
+
+  let source = match origin.source.is_some() {
+    true => format!(" [{origin}]"),
+    false => String::new(),
+  };
+
+  eprintln!("{event} at {when}{message}{source}");
+}
+
+fn main() -> Result<()> {
+  let opts = CommandLineOptions::from_args(std::env::args());
   async_std::task::block_on(run(opts))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cache_names_stay_in_the_directory() {
+    assert_eq!(cache_name("pop3-1a2B3c.eml"), "pop3-1a2B3c.eml");
+    assert_eq!(cache_name("pop3-../../etc/passwd.eml"), "pop3-.._.._etc_passwd.eml");
+    assert_eq!(cache_name(".."), "_");
+    assert_eq!(cache_name("../x"), "_x");
+    assert_eq!(cache_name("a\\b:c d"), "a_b_c_d");
+  }
+}
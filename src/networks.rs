@@ -0,0 +1,298 @@
+use std::io::{Error, Result};
+use std::net::IpAddr;
+
+/// A range of addresses written in CIDR notation, e.g. `203.0.113.0/24` or `2001:db8::/32`; a bare address is a
+/// range of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+  address: IpAddr,
+  length: u32,
+}
+
+impl std::str::FromStr for Network {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    let invalid = || Error::other(format!("invalid network '{value}' (expected e.g. '203.0.113.0/24')"));
+    let (address, length) = match value.trim().split_once('/') {
+      Some((address, length)) => (address, Some(length.parse::<u32>().map_err(|_| invalid())?)),
+      None => (value.trim(), None),
+    };
+    let address = address.parse::<IpAddr>().map_err(|_| invalid())?;
+    let bits = match address {
+      IpAddr::V4(_) => 32,
+      IpAddr::V6(_) => 128,
+    };
+
+    match length.unwrap_or(bits) {
+      length if length <= bits => Ok(Self { address, length }),
+      _ => Err(invalid()),
+    }
+  }
+}
+
+impl Network {
+  /// Whether `address` falls within the range. Addresses of the other IP version never do.
+  pub fn contains(&self, address: &IpAddr) -> bool {
+    match (self.address, address) {
+      (IpAddr::V4(network), IpAddr::V4(address)) => {
+        let mask = u32::MAX.checked_shl(32 - self.length).unwrap_or(0);
+        u32::from(network) & mask == u32::from(*address) & mask
+      }
+      (IpAddr::V6(network), IpAddr::V6(address)) => {
+        let mask = u128::MAX.checked_shl(128 - self.length).unwrap_or(0);
+        u128::from(network) & mask == u128::from(*address) & mask
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Reads the labels of known networks from the CSVs at `paths` (`--annotate`), one `NETWORK,LABEL` row per line,
+/// e.g. `203.0.113.7,my VPS`; a label may be quoted. Blank lines, `#` comments and a header row are skipped. The most
+/// specific networks come first, so a label for one address wins over one for its whole range.
+pub fn load_labels(paths: &[String]) -> Result<Vec<(Network, String)>> {
+  let mut labels = Vec::new();
+
+  for path in paths {
+    labels.extend(read_labels(path)?);
+  }
+
+  labels.sort_by_key(|(network, _)| std::cmp::Reverse(network.length));
+  Ok(labels)
+}
+
+fn read_labels(path: &str) -> Result<Vec<(Network, String)>> {
+  let contents = std::fs::read_to_string(path).map_err(|error| Error::other(format!("'{path}' - {error}")))?;
+  let mut labels = Vec::new();
+  let rows = contents
+    .lines()
+    .enumerate()
+    .map(|(index, line)| (index + 1, line.trim()))
+    .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+  for (row, (number, line)) in rows.enumerate() {
+    let invalid = |reason: String| Error::other(format!("'{path}' line {number} - {reason}"));
+    let (network, label) = line
+      .split_once(',')
+      .ok_or_else(|| invalid("expected 'NETWORK,LABEL'".into()))?;
+    let label = label.trim();
+    let label = label
+      .strip_prefix('"')
+      .and_then(|label| label.strip_suffix('"'))
+      .map(|label| label.replace("\"\"", "\""))
+      .unwrap_or(label.to_string());
+
+    match network.trim().trim_matches('"').parse::<Network>() {
+      Ok(network) => labels.push((network, label)),
+      Err(_) if row == 0 => continue,
+      Err(error) => return Err(invalid(format!("{error}"))),
+    }
+  }
+
+  Ok(labels)
+}
+
+/// The ranges no remote address can come from, and what kind they are: RFC 6890's special purpose registries, whose
+/// addresses aren't routed across the internet (or, for multicast, are never a source).
+const SPECIAL: [(&str, &str); 21] = [
+  ("0.0.0.0/8", "reserved"),
+  ("10.0.0.0/8", "private"),
+  ("100.64.0.0/10", "private"),
+  ("127.0.0.0/8", "loopback"),
+  ("169.254.0.0/16", "link-local"),
+  ("172.16.0.0/12", "private"),
+  ("192.0.0.0/24", "reserved"),
+  ("192.0.2.0/24", "documentation"),
+  ("192.168.0.0/16", "private"),
+  ("198.18.0.0/15", "reserved"),
+  ("198.51.100.0/24", "documentation"),
+  ("203.0.113.0/24", "documentation"),
+  ("224.0.0.0/4", "multicast"),
+  ("240.0.0.0/4", "reserved"),
+  ("::/128", "reserved"),
+  ("::1/128", "loopback"),
+  ("::ffff:0:0/96", "reserved"),
+  ("2001:db8::/32", "documentation"),
+  ("fc00::/7", "private"),
+  ("fe80::/10", "link-local"),
+  ("ff00::/8", "multicast"),
+];
+
+/// What kind of special purpose range `address` is in (e.g. `private` for RFC 1918 space), if it's an IP address
+/// in one.
+pub fn special(address: &str) -> Option<&'static str> {
+  let address = address.parse::<IpAddr>().ok()?;
+
+  SPECIAL
+    .iter()
+    .find(|(network, _)| {
+      network
+        .parse::<Network>()
+        .is_ok_and(|network| network.contains(&address))
+    })
+    .map(|(_, kind)| *kind)
+}
+
+/// Whether `address` is an IP address within any of `networks`.
+pub fn any_contains(networks: &[Network], address: &str) -> bool {
+  match address.parse::<IpAddr>() {
+    Ok(address) => networks.iter().any(|network| network.contains(&address)),
+    Err(_) => false,
+  }
+}
+
+/// What a list read from `path` is called in the report: its file name without the extension, e.g. `drop` for
+/// `lists/drop.txt`.
+pub fn list_name(path: &str) -> String {
+  let path = std::path::Path::new(path);
+  path
+    .file_stem()
+    .unwrap_or(path.as_os_str())
+    .to_string_lossy()
+    .into_owned()
+}
+
+/// Reads a list of networks from `path`, one per line. Blank lines and comments (after a `#` or, as in the Spamhaus
+/// lists, a `;`) are skipped.
+pub fn load(path: &str) -> Result<Vec<Network>> {
+  let contents = std::fs::read_to_string(path).map_err(|error| Error::other(format!("'{path}' - {error}")))?;
+
+  contents
+    .lines()
+    .enumerate()
+    .map(|(index, line)| (index + 1, line.split(['#', ';']).next().unwrap_or_default().trim()))
+    .filter(|(_, line)| !line.is_empty())
+    .map(|(number, line)| {
+      line
+        .parse()
+        .map_err(|error| Error::other(format!("'{path}' line {number} - {error}")))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn network(value: &str) -> Network {
+    value.parse().unwrap()
+  }
+
+  fn address(value: &str) -> IpAddr {
+    value.parse().unwrap()
+  }
+
+  fn file(name: &str, contents: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rupert-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+  }
+
+  #[test]
+  fn parses_cidr_notation() {
+    assert_eq!(network("203.0.113.7"), network("203.0.113.7/32"));
+    assert_eq!(network(" 2001:db8::1 "), network("2001:db8::1/128"));
+
+    for invalid in [
+      "203.0.113.0/33",
+      "2001:db8::/129",
+      "203.0.113.0/",
+      "203.0.113.0/x",
+      "example.com/24",
+      "",
+    ] {
+      assert!(invalid.parse::<Network>().is_err(), "{invalid}");
+    }
+  }
+
+  #[test]
+  fn contains_the_addresses_the_prefix_covers() {
+    assert!(network("0.0.0.0/0").contains(&address("255.255.255.255")));
+    assert!(network("::/0").contains(&address("2001:db8::1")));
+    assert!(network("203.0.113.7/32").contains(&address("203.0.113.7")));
+    assert!(!network("203.0.113.7/32").contains(&address("203.0.113.8")));
+    assert!(network("2001:db8::1/128").contains(&address("2001:db8::1")));
+    assert!(!network("2001:db8::1/128").contains(&address("2001:db8::2")));
+
+    // Host bits are ignored, leaving the network they're in.
+    assert!(network("192.168.1.5/24").contains(&address("192.168.1.200")));
+    assert!(!network("192.168.1.5/24").contains(&address("192.168.2.5")));
+
+    // Addresses are never within networks of the other IP version, even as IPv4-mapped IPv6 addresses.
+    assert!(!network("::/0").contains(&address("203.0.113.7")));
+    assert!(!network("::ffff:0:0/96").contains(&address("203.0.113.7")));
+    assert!(!network("0.0.0.0/0").contains(&address("::ffff:203.0.113.7")));
+  }
+
+  #[test]
+  fn names_special_purpose_ranges() {
+    for (value, kind) in [
+      ("10.1.2.3", Some("private")),
+      ("172.31.255.255", Some("private")),
+      ("192.168.1.1", Some("private")),
+      ("172.32.0.1", None),
+      ("127.0.0.1", Some("loopback")),
+      ("::1", Some("loopback")),
+      ("169.254.1.1", Some("link-local")),
+      ("fe80::1", Some("link-local")),
+      ("8.8.8.8", None),
+      ("router.local", None),
+    ] {
+      assert_eq!(special(value), kind, "{value}");
+    }
+  }
+
+  #[test]
+  fn reads_labels_most_specific_first() {
+    let ranges = file(
+      "ranges.csv",
+      "network,label\n# home\n\n203.0.113.0/24,\"office, \"\"east\"\" wing\"\n\"2001:db8::/32\", lab \n",
+    );
+    let hosts = file("hosts.csv", "203.0.113.7,my VPS\n");
+
+    // The header row is skipped, and the first network in the list to contain an address labels it.
+    let labels = load_labels(&[ranges.clone(), hosts.clone()]).unwrap();
+    let label = |value: &str| {
+      labels
+        .iter()
+        .find(|(network, _)| network.contains(&address(value)))
+        .map(|(_, label)| label.as_str())
+    };
+
+    assert_eq!(labels.len(), 3);
+    assert_eq!(label("203.0.113.7"), Some("my VPS"));
+    assert_eq!(label("203.0.113.8"), Some("office, \"east\" wing"));
+    assert_eq!(label("2001:db8::5"), Some("lab"));
+    assert_eq!(label("198.51.100.7"), None);
+
+    let broken = file("broken.csv", "network,label\n203.0.113.0/24,office\nsomewhere,else\n");
+    let error = load_labels(std::slice::from_ref(&broken)).unwrap_err().to_string();
+    assert!(error.contains("line 3 - invalid network 'somewhere'"), "{error}");
+
+    let unlabelled = file("unlabelled.csv", "203.0.113.7\n");
+    let error = load_labels(std::slice::from_ref(&unlabelled)).unwrap_err().to_string();
+    assert!(error.ends_with("line 1 - expected 'NETWORK,LABEL'"), "{error}");
+
+    for path in [ranges, hosts, broken, unlabelled] {
+      std::fs::remove_file(path).unwrap();
+    }
+  }
+
+  #[test]
+  fn reads_lists_without_their_comments() {
+    let list = file(
+      "drop.txt",
+      "; Spamhaus DROP List\n\n1.10.16.0/20 ; SBL256894\n  # local additions\n198.51.100.7 # scanner\n",
+    );
+
+    assert_eq!(load(&list).unwrap(), [network("1.10.16.0/20"), network("198.51.100.7")]);
+    assert_eq!(list_name(&list), format!("rupert-{}-drop", std::process::id()));
+
+    std::fs::write(&list, "1.10.16.0/20\n\n1.10.16.0/40\n").unwrap();
+    let error = load(&list).unwrap_err().to_string();
+    assert!(error.contains("line 3 - invalid network '1.10.16.0/40'"), "{error}");
+
+    std::fs::remove_file(list).unwrap();
+  }
+}
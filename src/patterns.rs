@@ -0,0 +1,316 @@
+use std::io::{Error, Result};
+
+use crate::dates::DateTime;
+use crate::logs::{self, LineContext, LogEvent};
+
+/// The kinds of event a pattern can describe, as named in a patterns file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  Access,
+  DosAttack,
+  AdminLogin,
+  WlanRejected,
+  DhcpLease,
+  UpnpMapping,
+  Connected,
+  Disconnected,
+  TimeSync,
+  EmailSent,
+  SiteBlocked,
+  ServiceBlocked,
+  DynamicDns,
+}
+
+impl Kind {
+  fn name(&self) -> &'static str {
+    match self {
+      Self::Access => "access",
+      Self::DosAttack => "dos",
+      Self::AdminLogin => "login",
+      Self::WlanRejected => "wlan",
+      Self::DhcpLease => "dhcp",
+      Self::UpnpMapping => "upnp",
+      Self::Connected => "connected",
+      Self::Disconnected => "disconnected",
+      Self::TimeSync => "sync",
+      Self::EmailSent => "email",
+      Self::SiteBlocked => "site",
+      Self::ServiceBlocked => "service",
+      Self::DynamicDns => "ddns",
+    }
+  }
+
+  /// The capture groups a pattern of this kind must have. Any kind may also capture a `timestamp`, and `port`s,
+  /// `protocol` (of an access), `server`, `address` (of a connection) and `failed` (for logins and Dynamic DNS
+  /// updates) are optional.
+  fn required(&self) -> &'static [&'static str] {
+    match self {
+      Self::Access => &["address", "local_address"],
+      Self::DosAttack => &["kind", "address"],
+      Self::AdminLogin | Self::UpnpMapping => &["address"],
+      Self::WlanRejected => &["reason", "mac"],
+      Self::DhcpLease => &["address", "mac"],
+      Self::Connected | Self::Disconnected | Self::TimeSync => &[],
+      Self::EmailSent => &["recipient"],
+      Self::SiteBlocked | Self::ServiceBlocked => &["destination", "address"],
+      Self::DynamicDns => &["host"],
+    }
+  }
+}
+
+const KINDS: [Kind; 13] = [
+  Kind::Access,
+  Kind::DosAttack,
+  Kind::AdminLogin,
+  Kind::WlanRejected,
+  Kind::DhcpLease,
+  Kind::UpnpMapping,
+  Kind::Connected,
+  Kind::Disconnected,
+  Kind::TimeSync,
+  Kind::EmailSent,
+  Kind::SiteBlocked,
+  Kind::ServiceBlocked,
+  Kind::DynamicDns,
+];
+
+/// A line parser declared in a patterns file: a regex whose named capture groups are the fields of the event it
+/// describes, e.g. `access ^\[Port forward\] (?P<address>\S+) -> (?P<local_address>[^:]+):(?P<local_port>\d+)`.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+  kind: Kind,
+  regex: regex::Regex,
+}
+
+/// Reads the patterns in `path` (`--patterns`), one `KIND REGEX` per line. Blank lines and `#` comments are
+/// skipped.
+pub fn load(path: &str) -> Result<Vec<Pattern>> {
+  let contents =
+    std::fs::read_to_string(path).map_err(|error| Error::other(format!("invalid '--patterns' '{path}' - {error}")))?;
+  let mut patterns = Vec::new();
+
+  for (number, line) in contents
+    .lines()
+    .enumerate()
+    .map(|(index, line)| (index + 1, line.trim()))
+  {
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let invalid = |reason: String| Error::other(format!("invalid pattern at '{path}' line {number} - {reason}"));
+    let (name, expression) = line
+      .split_once(char::is_whitespace)
+      .ok_or_else(|| invalid("expected 'KIND REGEX'".into()))?;
+    let kind = KINDS
+      .into_iter()
+      .find(|kind| kind.name() == name)
+      .ok_or_else(|| invalid(format!("unknown kind '{name}'")))?;
+    let regex = regex::Regex::new(expression.trim()).map_err(|error| invalid(format!("{error}")))?;
+
+    if let Some(group) = kind
+      .required()
+      .iter()
+      .find(|group| !regex.capture_names().any(|name| name == Some(**group)))
+    {
+      return Err(invalid(format!("'{name}' patterns need a '{group}' group")));
+    }
+
+    patterns.push(Pattern { kind, regex });
+  }
+
+  Ok(patterns)
+}
+
+impl Pattern {
+  /// The event `line` describes, if it matches. A match that leaves a required group empty (e.g. one side of an
+  /// alternation) can't be read, and gives the kind's name.
+  pub fn parse(&self, line: &str, context: &LineContext) -> Option<std::result::Result<LogEvent, &'static str>> {
+    let captures = self.regex.captures(line)?;
+    let group = |name: &str| {
+      captures
+        .name(name)
+        .map(|value| value.as_str().trim())
+        .filter(|value| !value.is_empty())
+    };
+
+    if self.kind.required().iter().any(|name| group(name).is_none()) {
+      return Some(Err(self.kind.name()));
+    }
+
+    // The fields checked just above.
+    let field = |name: &str| group(name).unwrap_or_default().to_string();
+    let endpoint = |name: &str, port: &str| {
+      let (address, written) = logs::host_port(group(name).unwrap_or_default());
+      (address, group(port).and_then(|port| port.parse().ok()).or(written))
+    };
+
+    // Either the Netgear style `Monday, Jan 03,2022 08:00:00` or ISO 8601.
+    let timestamp = group("timestamp")
+      .and_then(|stamp| DateTime::parse_iso(stamp).or_else(|| logs::trailing_timestamp(stamp, context)));
+    let origin = context.origin.clone();
+    let failed = group("failed").is_some();
+
+    let event = match self.kind {
+      Kind::Access => {
+        let (address, port) = endpoint("address", "port");
+        let (local_address, local_port) = endpoint("local_address", "local_port");
+        LogEvent::RemoteAccess(logs::RemoteAccess {
+          address,
+          port,
+          local_address,
+          local_port,
+          protocol: group("protocol").map(str::to_ascii_uppercase),
+          timestamp,
+          origin,
+        })
+      }
+      Kind::DosAttack => LogEvent::DosAttack(logs::DosAttack {
+        kind: logs::Attack::parse(&field("kind")),
+        address: endpoint("address", "port").0,
+        timestamp,
+        origin,
+      }),
+      Kind::AdminLogin => LogEvent::AdminLogin(logs::AdminLogin {
+        address: endpoint("address", "port").0,
+        failed,
+        timestamp,
+        origin,
+      }),
+      Kind::WlanRejected => LogEvent::WlanRejected(logs::WlanRejected {
+        reason: field("reason"),
+        mac: field("mac").to_ascii_lowercase(),
+        timestamp,
+        origin,
+      }),
+      Kind::DhcpLease => LogEvent::DhcpLease(logs::DhcpLease {
+        address: field("address"),
+        mac: field("mac").to_ascii_lowercase(),
+        timestamp,
+        origin,
+      }),
+      Kind::UpnpMapping => LogEvent::UpnpMapping(logs::UpnpMapping {
+        action: group("action").unwrap_or("add").to_ascii_lowercase(),
+        address: endpoint("address", "port").0,
+        timestamp,
+        origin,
+      }),
+      Kind::Connected | Kind::Disconnected => LogEvent::InternetConnection(logs::InternetConnection {
+        connected: self.kind == Kind::Connected,
+        address: group("address").map(str::to_string),
+        timestamp,
+        origin,
+      }),
+      Kind::TimeSync => LogEvent::TimeSync(logs::TimeSync {
+        server: group("server").map(str::to_string),
+        timestamp,
+        origin,
+      }),
+      Kind::EmailSent => LogEvent::EmailSent(logs::EmailSent {
+        recipient: field("recipient"),
+        timestamp,
+        origin,
+      }),
+      Kind::SiteBlocked | Kind::ServiceBlocked => LogEvent::Blocked(logs::Blocked {
+        service: self.kind == Kind::ServiceBlocked,
+        destination: field("destination"),
+        address: endpoint("address", "port").0,
+        timestamp,
+        origin,
+      }),
+      Kind::DynamicDns => LogEvent::DynamicDns(logs::DynamicDns {
+        host: field("host"),
+        succeeded: !failed,
+        timestamp,
+        origin,
+      }),
+    };
+
+    Some(Ok(event))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Loads `contents` as a patterns file.
+  fn patterns(name: &str, contents: &str) -> Result<Vec<Pattern>> {
+    let path = std::env::temp_dir().join(format!("rupert-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    let patterns = load(&path.to_string_lossy());
+    std::fs::remove_file(&path).unwrap();
+    patterns
+  }
+
+  fn rejected(name: &str, contents: &str) -> String {
+    patterns(name, contents).unwrap_err().to_string()
+  }
+
+  #[test]
+  fn names_the_line_of_an_invalid_pattern() {
+    let error = rejected("unknown.patterns", "# comment\n\nportscan ^(?P<address>\\S+)$\n");
+    assert!(error.ends_with("line 3 - unknown kind 'portscan'"), "{error}");
+
+    let error = rejected("regex.patterns", "login ^(?P<address>\\S+\n");
+    assert!(error.contains("line 1 - regex parse error"), "{error}");
+
+    let error = rejected(
+      "groups.patterns",
+      "login ^x$\naccess ^(?P<address>\\S+) to (?P<local>\\S+)$\n",
+    );
+    assert!(
+      error.ends_with("line 1 - 'login' patterns need a 'address' group"),
+      "{error}"
+    );
+
+    let error = rejected("kind.patterns", "access\n");
+    assert!(error.ends_with("line 1 - expected 'KIND REGEX'"), "{error}");
+  }
+
+  #[test]
+  fn reads_the_groups_that_matched() {
+    let patterns = patterns(
+      "access.patterns",
+      "access ^\\[Port forward\\] (?P<address>\\S+?)(?::(?P<port>\\d+))? -> \
+       (?P<local_address>[^: ]+)(?::(?P<local_port>\\d+))?(?: at (?P<timestamp>.+))?$\n",
+    )
+    .unwrap();
+    let context = LineContext::default();
+
+    let line = "[Port forward] 1.2.3.4:4000 -> 192.168.1.10:22 at 2022-01-03 08:00:00";
+    let Some(Ok(LogEvent::RemoteAccess(access))) = patterns[0].parse(line, &context) else {
+      panic!("{line}");
+    };
+    assert_eq!(
+      (
+        access.address.as_str(),
+        access.port,
+        access.local_address.as_str(),
+        access.local_port
+      ),
+      ("1.2.3.4", Some(4000), "192.168.1.10", Some(22))
+    );
+    assert_eq!(access.timestamp, DateTime::parse_iso("2022-01-03 08:00:00"));
+
+    let line = "[Port forward] 1.2.3.4 -> 192.168.1.10";
+    let Some(Ok(LogEvent::RemoteAccess(access))) = patterns[0].parse(line, &context) else {
+      panic!("{line}");
+    };
+    assert_eq!((access.port, access.local_port, access.timestamp), (None, None, None));
+
+    assert!(patterns[0]
+      .parse("[Port trigger] 1.2.3.4 -> 192.168.1.10", &context)
+      .is_none());
+  }
+
+  #[test]
+  fn names_the_kind_of_a_match_missing_a_required_group() {
+    let patterns = patterns("either.patterns", "login ^login (?:from (?P<address>\\S+)|locally)$\n").unwrap();
+
+    assert!(matches!(
+      patterns[0].parse("login locally", &LineContext::default()),
+      Some(Err("login"))
+    ));
+  }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::io::{Error, Result};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use async_std::channel;
+use mail_auth::common::cache::NoCache;
+use mail_auth::hickory_resolver::proto::op::ResponseCode;
+use mail_auth::{MessageAuthenticator, RecordSet};
+
+/// How many reverse lookups are in flight at once, and how long each is given before the address is left unnamed.
+const CONCURRENCY: usize = 16;
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+enum Request {
+  Names {
+    addresses: Vec<IpAddr>,
+    reply: channel::Sender<HashMap<IpAddr, String>>,
+  },
+  Listings {
+    addresses: Vec<IpAddr>,
+    zones: Vec<String>,
+    reply: channel::Sender<HashMap<IpAddr, Vec<(String, Listing)>>>,
+  },
+}
+
+/// Whether a DNS blocklist (`--dnsbl`) lists an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listing {
+  /// Listed, with the code the zone answered; what it means is up to the zone, e.g. `127.0.0.2` is the SBL in
+  /// Spamhaus' `zen`.
+  Listed(std::net::Ipv4Addr),
+  NotListed,
+
+  /// The zone didn't answer in time, or refused to (as Spamhaus does queries sent through public resolvers).
+  Unknown,
+}
+
+impl std::fmt::Display for Listing {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Listed(code) => write!(formatter, "listed ({code})"),
+      Self::NotListed => write!(formatter, "not listed"),
+      Self::Unknown => write!(formatter, "unknown"),
+    }
+  }
+}
+
+/// Looks up the host names of reported addresses (`--resolve`) and whether DNS blocklists list them (`--dnsbl`) on a
+/// dedicated thread, since the resolver needs its own (tokio) runtime. Every name, or the lack of one, is kept for
+/// the rest of the run, so reporting again while watching only looks up the addresses that are new.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+  requests: std::sync::mpsc::Sender<Request>,
+}
+
+/// The first name `address` points back to, if it answers in time.
+async fn name(authenticator: Arc<MessageAuthenticator>, address: IpAddr) -> Option<String> {
+  let lookup = authenticator.ptr_lookup(address, None::<&NoCache<IpAddr, RecordSet<Box<str>>>>);
+  let records = tokio::time::timeout(TIMEOUT, lookup).await.ok()?.ok()?;
+  let name = records.rrset.first()?;
+  Some(name.trim_end_matches('.').to_string())
+}
+
+/// Whether `zone` lists `address`: a listed address has an `A` record under the zone, written in reverse (by octet,
+/// or for IPv6 by nibble), e.g. `4.3.2.1.zen.spamhaus.org` for `1.2.3.4`.
+async fn listing(authenticator: Arc<MessageAuthenticator>, address: IpAddr, zone: &str) -> Listing {
+  let reversed = match address {
+    IpAddr::V4(address) => address.octets().iter().rev().map(u8::to_string).collect::<Vec<_>>(),
+    IpAddr::V6(address) => address
+      .octets()
+      .iter()
+      .rev()
+      .flat_map(|byte| [byte & 0xf, byte >> 4])
+      .map(|nibble| format!("{nibble:x}"))
+      .collect(),
+  };
+  let query = format!("{}.{zone}.", reversed.join("."));
+  let lookup = authenticator.ipv4_lookup(query, None::<&NoCache<Box<str>, RecordSet<std::net::Ipv4Addr>>>);
+
+  match tokio::time::timeout(TIMEOUT, lookup).await {
+    Ok(Ok(records)) => match records.rrset.first() {
+      // Answers outside of 127.0.0.0/8 (and Spamhaus' 127.255.255.0/24) are errors, not listings.
+      Some(code) if code.octets()[0] == 127 && code.octets()[1..3] != [255, 255] => Listing::Listed(*code),
+      Some(_) => Listing::Unknown,
+      None => Listing::NotListed,
+    },
+    Ok(Err(mail_auth::Error::Dns(mail_auth::DnsError::RecordNotFound(code))))
+      if code == ResponseCode::NXDomain || code == ResponseCode::NoError =>
+    {
+      Listing::NotListed
+    }
+    _ => Listing::Unknown,
+  }
+}
+
+/// Starts the lookup thread, using the system's DNS configuration.
+pub fn start() -> Result<Resolver> {
+  let authenticator =
+    Arc::new(MessageAuthenticator::new_system_conf().map_err(|error| Error::other(format!("resolve - {error}")))?);
+  let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+  let (requests, receiver) = std::sync::mpsc::channel::<Request>();
+
+  std::thread::spawn(move || {
+    let mut names = HashMap::<IpAddr, Option<String>>::new();
+
+    while let Ok(request) = receiver.recv() {
+      let (addresses, reply) = match request {
+        Request::Names { addresses, reply } => (addresses, reply),
+        Request::Listings {
+          addresses,
+          zones,
+          reply,
+        } => {
+          let listings = runtime.block_on(listings(&authenticator, &addresses, &zones));
+          let _ = async_std::task::block_on(reply.send(listings));
+          continue;
+        }
+      };
+
+      runtime.block_on(async {
+        let unknown = addresses.iter().filter(|address| !names.contains_key(*address));
+        let mut pending = unknown.copied().collect::<Vec<_>>().into_iter();
+        let mut lookups = tokio::task::JoinSet::new();
+
+        loop {
+          while lookups.len() < CONCURRENCY {
+            let Some(address) = pending.next() else {
+              break;
+            };
+            let authenticator = authenticator.clone();
+            lookups.spawn(async move { (address, name(authenticator, address).await) });
+          }
+
+          match lookups.join_next().await {
+            Some(Ok((address, name))) => {
+              names.insert(address, name);
+            }
+            Some(Err(_)) => continue,
+            None => break,
+          }
+        }
+      });
+
+      let found = addresses
+        .iter()
+        .filter_map(|address| Some((*address, names.get(address)?.clone()?)))
+        .collect();
+      let _ = async_std::task::block_on(reply.send(found));
+    }
+  });
+
+  Ok(Resolver { requests })
+}
+
+/// Checks each of `addresses` against each of `zones`, [`CONCURRENCY`] queries at a time.
+async fn listings(
+  authenticator: &Arc<MessageAuthenticator>,
+  addresses: &[IpAddr],
+  zones: &[String],
+) -> HashMap<IpAddr, Vec<(String, Listing)>> {
+  let mut pending = addresses
+    .iter()
+    .flat_map(|address| zones.iter().map(move |zone| (*address, zone.clone())));
+  let mut lookups = tokio::task::JoinSet::new();
+  let mut found = HashMap::<IpAddr, Vec<(String, Listing)>>::new();
+
+  loop {
+    while lookups.len() < CONCURRENCY {
+      let Some((address, zone)) = pending.next() else {
+        break;
+      };
+      let authenticator = authenticator.clone();
+      lookups.spawn(async move {
+        let listing = listing(authenticator, address, &zone).await;
+        (address, zone, listing)
+      });
+    }
+
+    match lookups.join_next().await {
+      Some(Ok((address, zone, listing))) => found.entry(address).or_default().push((zone, listing)),
+      Some(Err(_)) => continue,
+      None => break,
+    }
+  }
+
+  // In the order the zones were given, whichever answered first.
+  for listings in found.values_mut() {
+    listings.sort_by_key(|(zone, _)| zones.iter().position(|given| given == zone));
+  }
+
+  found
+}
+
+impl Resolver {
+  /// The host names of those of `addresses` that have one; anything that isn't an IP address (e.g. an
+  /// `--aggregate`d network) is skipped.
+  pub async fn names(&self, addresses: Vec<String>) -> HashMap<String, String> {
+    let (reply, found) = channel::bounded(1);
+    let parsed = addresses
+      .into_iter()
+      .filter_map(|address| Some((address.parse::<IpAddr>().ok()?, address)))
+      .collect::<Vec<_>>();
+    let request = Request::Names {
+      addresses: parsed.iter().map(|(ip, _)| *ip).collect(),
+      reply,
+    };
+
+    if self.requests.send(request).is_err() {
+      return HashMap::new();
+    }
+
+    let found = found.recv().await.unwrap_or_default();
+
+    // Named as they were written, which for IPv6 needn't be how they'd be written back.
+    parsed
+      .into_iter()
+      .filter_map(|(ip, address)| Some((address, found.get(&ip)?.clone())))
+      .collect()
+  }
+
+  /// Whether each of `zones` lists each of those of `addresses` that are IP addresses.
+  pub async fn listings(&self, addresses: Vec<String>, zones: Vec<String>) -> HashMap<String, Vec<(String, Listing)>> {
+    let (reply, found) = channel::bounded(1);
+    let parsed = addresses
+      .into_iter()
+      .filter_map(|address| Some((address.parse::<IpAddr>().ok()?, address)))
+      .collect::<Vec<_>>();
+    let request = Request::Listings {
+      addresses: parsed.iter().map(|(ip, _)| *ip).collect(),
+      zones,
+      reply,
+    };
+
+    if self.requests.send(request).is_err() {
+      return HashMap::new();
+    }
+
+    let mut found = found.recv().await.unwrap_or_default();
+    parsed
+      .into_iter()
+      .filter_map(|(ip, address)| Some((address, found.remove(&ip)?)))
+      .collect()
+  }
+}
@@ -0,0 +1,170 @@
+use std::io::{Error, Read, Result};
+use std::path::{Path, PathBuf};
+
+use async_std::channel;
+
+use crate::archive::Member;
+use crate::fetch::PASSWORD_ENV;
+
+pub const SCHEME: &str = "sftp://";
+
+const DEFAULT_PORT: u16 = 22;
+const DEFAULT_KEYS: [&str; 2] = [".ssh/id_ed25519", ".ssh/id_rsa"];
+const KNOWN_HOSTS: &str = ".ssh/known_hosts";
+
+/// A parsed `sftp://[user@]host[:port]/path` input.
+#[derive(Debug)]
+struct Location {
+  user: String,
+  host: String,
+  port: u16,
+  path: PathBuf,
+}
+
+fn location(input: &str) -> Option<Location> {
+  let rest = input.strip_prefix(SCHEME)?;
+  let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+
+  let (user, address) = match authority.split_once('@') {
+    Some((user, address)) => (user.to_string(), address),
+    None => (std::env::var("USER").ok()?, authority),
+  };
+
+  let (host, port) = match address.rsplit_once(':') {
+    Some((host, port)) => (host, port.parse().ok()?),
+    None => (address, DEFAULT_PORT),
+  };
+
+  if host.is_empty() {
+    return None;
+  }
+
+  Some(Location {
+    user,
+    host: host.to_string(),
+    port,
+    path: PathBuf::from(format!("/{path}")),
+  })
+}
+
+fn ssh_error(error: ssh2::Error) -> Error {
+  Error::other(format!("sftp - {error}"))
+}
+
+/// Refuses to talk to a server whose host key isn't already in `~/.ssh/known_hosts`.
+fn verify_host(session: &ssh2::Session, location: &Location, home: &Path) -> Result<()> {
+  let mut known = session.known_hosts().map_err(ssh_error)?;
+  known
+    .read_file(&home.join(KNOWN_HOSTS), ssh2::KnownHostFileKind::OpenSSH)
+    .map_err(ssh_error)?;
+
+  let (key, _) = session
+    .host_key()
+    .ok_or_else(|| Error::other("sftp - server sent no host key"))?;
+
+  match known.check_port(&location.host, location.port, key) {
+    ssh2::CheckResult::Match => Ok(()),
+    ssh2::CheckResult::Mismatch => Err(Error::other(format!(
+      "sftp - host key for '{}' has CHANGED",
+      location.host
+    ))),
+    _ => Err(Error::other(format!(
+      "sftp - '{}' is not in known_hosts; connect with ssh once to add it",
+      location.host
+    ))),
+  }
+}
+
+/// Tries the ssh agent, then the default key files, then a password from the environment.
+fn authenticate(session: &ssh2::Session, location: &Location, home: &Path) -> Result<()> {
+  if session.userauth_agent(&location.user).is_ok() {
+    return Ok(());
+  }
+
+  for key in DEFAULT_KEYS
+    .iter()
+    .map(|key| home.join(key))
+    .filter(|key| key.is_file())
+  {
+    if session.userauth_pubkey_file(&location.user, None, &key, None).is_ok() {
+      return Ok(());
+    }
+  }
+
+  let password = std::env::var(PASSWORD_ENV)
+    .map_err(|_| Error::other(format!("sftp - no usable key and no password in '{PASSWORD_ENV}'")))?;
+
+  session.userauth_password(&location.user, &password).map_err(ssh_error)
+}
+
+fn read_sftp(location: Location, recursive: bool, sender: channel::Sender<Result<Member>>) -> Result<()> {
+  let home = std::env::var("HOME")
+    .map(PathBuf::from)
+    .map_err(|_| Error::other("sftp - no HOME"))?;
+
+  let stream = std::net::TcpStream::connect((location.host.as_str(), location.port))?;
+  let mut session = ssh2::Session::new().map_err(ssh_error)?;
+  session.set_tcp_stream(stream);
+  session.handshake().map_err(ssh_error)?;
+
+  verify_host(&session, &location, &home)?;
+  authenticate(&session, &location, &home)?;
+
+  let sftp = session.sftp().map_err(ssh_error)?;
+
+  let mut pending = vec![location.path.clone()];
+
+  while let Some(path) = pending.pop() {
+    let stat = sftp.stat(&path).map_err(ssh_error)?;
+
+    let files = match stat.is_dir() {
+      false => vec![path],
+      true => {
+        eprintln!("scanning '{}:{}'", location.host, path.display());
+        let mut files = Vec::with_capacity(100);
+
+        for (entry, stat) in sftp.readdir(&path).map_err(ssh_error)? {
+          if stat.is_dir() && recursive {
+            pending.push(entry);
+          } else if stat.is_file() {
+            files.push(entry);
+          }
+        }
+
+        files
+      }
+    };
+
+    for file in files {
+      let mut contents = Vec::with_capacity(4096);
+      let member = sftp
+        .open(&file)
+        .map_err(ssh_error)
+        .and_then(|mut handle| handle.read_to_end(&mut contents))
+        .map(|_| Member {
+          name: file.display().to_string(),
+          contents,
+        });
+
+      if async_std::task::block_on(sender.send(member)).is_err() {
+        return Ok(());
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Connects to the remote host on a dedicated thread, sending back each file under the input's path.
+pub fn files(input: &str, recursive: bool) -> Result<channel::Receiver<Result<Member>>> {
+  let location = location(input).ok_or_else(|| Error::other(format!("invalid sftp location '{input}'")))?;
+  let (sender, receiver) = channel::bounded(1);
+
+  std::thread::spawn(move || {
+    if let Err(error) = read_sftp(location, recursive, sender.clone()) {
+      let _ = async_std::task::block_on(sender.send(Err(error)));
+    }
+  });
+
+  Ok(receiver)
+}
@@ -0,0 +1,159 @@
+use std::io::{Error, Result};
+
+pub use rusqlite::types::Value;
+
+/// What's done with a row whose key is already in its table, as when a run is over logs an earlier one read.
+#[derive(Debug, Clone, Copy)]
+pub enum Conflict {
+  /// The row already there is kept, e.g. an event that was read before.
+  Ignore,
+
+  /// The new row replaces it, but for the `earliest` columns, which keep the lesser of the two values (e.g. when a
+  /// source was first seen).
+  Update { earliest: &'static [&'static str] },
+}
+
+/// A table to add rows to.
+#[derive(Debug)]
+pub struct Table {
+  pub name: &'static str,
+
+  /// The names and declared types of the columns, e.g. `("count", "INTEGER")`.
+  pub columns: Vec<(&'static str, &'static str)>,
+
+  /// The columns that tell rows apart, in which a NULL is the same as any other.
+  pub key: &'static [&'static str],
+  pub conflict: Conflict,
+  pub rows: Vec<Vec<Value>>,
+}
+
+/// Adds `tables` to the SQLite database at `path` (`--output sqlite:PATH`), creating it and any of the tables that
+/// aren't there yet, all in one transaction. Returns how many rows of each were added or changed.
+pub fn write(path: &str, tables: &[Table]) -> Result<Vec<usize>> {
+  let failed = |error: rusqlite::Error| Error::other(format!("'{path}' - {error}"));
+  let mut database = rusqlite::Connection::open(path).map_err(failed)?;
+  let transaction = database.transaction().map_err(failed)?;
+  let mut written = Vec::with_capacity(tables.len());
+
+  for table in tables {
+    transaction.execute_batch(&schema(table)).map_err(failed)?;
+
+    let mut statement = transaction.prepare(&insert(table)).map_err(failed)?;
+    let mut changed = 0;
+    for row in &table.rows {
+      changed += statement.execute(rusqlite::params_from_iter(row)).map_err(failed)?;
+    }
+
+    written.push(changed);
+  }
+
+  transaction.commit().map_err(failed)?;
+  Ok(written)
+}
+
+/// The key columns of `table`, as its unique index (and so an upsert's conflict target) has them.
+fn key(table: &Table) -> String {
+  table
+    .key
+    .iter()
+    .map(|column| format!("ifnull({column}, '')"))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// Creates `table`, and the index its rows are told apart by, if they aren't there yet.
+fn schema(table: &Table) -> String {
+  let columns = table
+    .columns
+    .iter()
+    .map(|(name, kind)| format!("{name} {kind}"))
+    .collect::<Vec<_>>();
+  format!(
+    "CREATE TABLE IF NOT EXISTS {name} ({}); CREATE UNIQUE INDEX IF NOT EXISTS {name}_key ON {name} ({});",
+    columns.join(", "),
+    key(table),
+    name = table.name,
+  )
+}
+
+/// Inserts a row of `table`, doing what its [`Conflict`] says with one already there.
+fn insert(table: &Table) -> String {
+  let names = table.columns.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+  let values = (1..=names.len()).map(|index| format!("?{index}")).collect::<Vec<_>>();
+  let conflict = match table.conflict {
+    Conflict::Ignore => "DO NOTHING".to_string(),
+    Conflict::Update { earliest } => {
+      let updates = names
+        .iter()
+        .filter(|name| !table.key.contains(name))
+        .map(|name| match earliest.contains(name) {
+          true => format!("{name} = coalesce(min({name}, excluded.{name}), {name}, excluded.{name})"),
+          false => format!("{name} = excluded.{name}"),
+        })
+        .collect::<Vec<_>>();
+      format!("({}) DO UPDATE SET {}", key(table), updates.join(", "))
+    }
+  };
+
+  format!(
+    "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT {conflict}",
+    table.name,
+    names.join(", "),
+    values.join(", ")
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tables(count: i64, first: &str) -> Vec<Table> {
+    let columns = vec![("ip", "TEXT"), ("count", "INTEGER"), ("first_seen", "TEXT")];
+    vec![
+      Table {
+        name: "sources",
+        columns: columns.clone(),
+        key: &["ip"],
+        conflict: Conflict::Update {
+          earliest: &["first_seen"],
+        },
+        rows: vec![vec![
+          Value::Text("7.7.7.7".into()),
+          Value::Integer(count),
+          Value::Text(first.into()),
+        ]],
+      },
+      Table {
+        name: "events",
+        columns,
+        key: &["ip", "count", "first_seen"],
+        conflict: Conflict::Ignore,
+        rows: vec![vec![Value::Text("7.7.7.7".into()), Value::Null, Value::Null]],
+      },
+    ]
+  }
+
+  #[test]
+  fn adds_to_an_existing_database() {
+    let path = std::env::temp_dir().join(format!("rupert-{}-sqlite.db", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+
+    assert_eq!(write(&path, &tables(3, "2022-01-03 08:00:00")).unwrap(), vec![1, 1]);
+    // The event is the same one (NULLs and all), and the source was seen later this time.
+    assert_eq!(write(&path, &tables(5, "2022-01-04 08:00:00")).unwrap(), vec![1, 0]);
+
+    let database = rusqlite::Connection::open(&path).unwrap();
+    let source = database
+      .query_row("SELECT count, first_seen FROM sources", [], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+      })
+      .unwrap();
+    let events = database
+      .query_row("SELECT count(*) FROM events", [], |row| row.get::<_, i64>(0))
+      .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(source, (5, "2022-01-03 08:00:00".to_string()));
+    assert_eq!(events, 1);
+  }
+}
@@ -0,0 +1,2532 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{Error, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::asn::Asn;
+use crate::dates::{self, DateTime};
+use crate::geoip::GeoIp;
+use crate::history::History;
+use crate::html;
+use crate::logs::{Attack, InternetConnection, LogEvent, TimeSync};
+use crate::networks::{self, Network};
+use crate::resolve;
+use crate::sqlite;
+use crate::whois;
+
+/// Ways of breaking the accesses down besides by source (`--group-by`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+  /// By the local port reached, i.e. which services are being hammered.
+  Port,
+
+  /// By the local host reached, i.e. which forwarded machine attracts the most attention.
+  Host,
+
+  /// By the autonomous system the source belongs to, collapsing a hosting provider's addresses into one row. Needs
+  /// an ASN database (`--asn`) to look sources up in.
+  Asn,
+
+  /// By the country the source is located in. Needs a GeoIP database (`--geoip`) to look sources up in.
+  Country,
+}
+
+impl std::str::FromStr for Grouping {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    match value {
+      "port" => Ok(Self::Port),
+      "host" => Ok(Self::Host),
+      "asn" => Ok(Self::Asn),
+      "country" => Ok(Self::Country),
+      other => Err(Error::other(format!(
+        "unknown grouping '{other}' (expected 'port', 'host', 'asn' or 'country')"
+      ))),
+    }
+  }
+}
+
+/// The periods totals are rolled up into (`--rollup`), for looking over a large archive in one go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rollup {
+  /// Weeks starting on Monday.
+  Week,
+  Month,
+}
+
+impl std::str::FromStr for Rollup {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    match value {
+      "week" => Ok(Self::Week),
+      "month" => Ok(Self::Month),
+      other => Err(Error::other(format!(
+        "unknown rollup '{other}' (expected 'week' or 'month')"
+      ))),
+    }
+  }
+}
+
+impl Rollup {
+  fn name(&self) -> &'static str {
+    match self {
+      Self::Week => "week",
+      Self::Month => "month",
+    }
+  }
+
+  /// Names the period starting at `start`, e.g. `week of 2022-01-03` or `2022-01`.
+  fn label(&self, start: DateTime) -> String {
+    let date = start.date;
+
+    match self {
+      Self::Week => format!("week of {:04}-{:02}-{:02}", date.year, date.month, date.day),
+      Self::Month => format!("{:04}-{:02}", date.year, date.month),
+    }
+  }
+
+  /// When the period before the one starting at `start` started.
+  fn previous(&self, start: DateTime) -> DateTime {
+    self.start(DateTime::from_seconds(start.seconds() - 86400))
+  }
+
+  /// When the period `timestamp` falls in started.
+  fn start(&self, timestamp: DateTime) -> DateTime {
+    let midnight = DateTime {
+      hour: 0,
+      minute: 0,
+      second: 0,
+      ..timestamp
+    };
+
+    match self {
+      // 1970-01-01 was a Thursday, three days after the Monday starting its week.
+      Self::Week => {
+        let days = midnight.seconds().div_euclid(86400);
+        DateTime::from_seconds((days - (days + 3).rem_euclid(7)) * 86400)
+      }
+      Self::Month => DateTime {
+        date: dates::Date {
+          day: 1,
+          ..midnight.date
+        },
+        ..midnight
+      },
+    }
+  }
+}
+
+/// How the report is written (`--output`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Output {
+  /// The sections below, to be read as is.
+  #[default]
+  Text,
+
+  /// One JSON document, for scripts.
+  Json,
+
+  /// A row per reported address, in the [`COLUMNS`], for a spreadsheet.
+  Csv,
+
+  /// Tables of the totals, reported addresses and busiest ports, to paste into an issue or a wiki.
+  Markdown,
+
+  /// A page with the totals, charts of when events happened and tables of the reported addresses and busiest
+  /// ports, written to the path (`report --html`).
+  Html(String),
+
+  /// Every event counted and the records of the reported addresses, added to the tables of the SQLite database at
+  /// the path (created if there isn't one yet) for querying. Events an earlier run added aren't added again.
+  Sqlite(String),
+}
+
+/// The columns of `--output csv` (and of the `sources` table of `--output sqlite`), the field of each
+/// [`Summary::entry`] they're filled from and their SQL type. New columns only ever go at the end, so spreadsheets
+/// and queries built on the output keep working.
+const COLUMNS: [(&str, &str, &str); 21] = [
+  ("ip", "address", "TEXT"),
+  ("count", "accesses", "INTEGER"),
+  ("first_seen", "first_seen", "TEXT"),
+  ("last_seen", "last_seen", "TEXT"),
+  ("top_port", "top_port", "INTEGER"),
+  ("country", "country", "TEXT"),
+  ("city", "city", "TEXT"),
+  ("asn", "asn", "INTEGER"),
+  ("organization", "organization", "TEXT"),
+  ("name", "name", "TEXT"),
+  ("label", "label", "TEXT"),
+  ("port_scanner", "port_scanner", "INTEGER"),
+  ("attacks", "attacks", "TEXT"),
+  ("ports", "ports", "TEXT"),
+  ("special", "special", "TEXT"),
+  ("abuse_confidence", "abuse_confidence", "INTEGER"),
+  ("lists", "lists", "TEXT"),
+  ("cloud", "cloud", "TEXT"),
+  ("tor_exit", "tor_exit", "INTEGER"),
+  ("earlier_runs", "earlier_runs", "INTEGER"),
+  ("registration", "registration", "TEXT"),
+];
+
+/// The headings of the tables of reported addresses in `report --html` and `--output markdown`, and the field of
+/// each [`Summary::entry`] they're filled from.
+const OFFENDER_COLUMNS: [(&str, &str); 11] = [
+  ("address", "address"),
+  ("name", "name"),
+  ("label", "label"),
+  ("country", "country"),
+  ("network", "organization"),
+  ("accesses", "accesses"),
+  ("top port", "top_port"),
+  ("port scanner", "port_scanner"),
+  ("attacks", "attacks"),
+  ("listed on", "lists"),
+  ("last seen", "last_seen"),
+];
+
+/// A field of a [`Summary::entry`] as it reads in a table cell, lists separated by commas.
+fn cell(value: &serde_json::Value) -> String {
+  match value {
+    serde_json::Value::Null | serde_json::Value::Bool(false) => String::new(),
+    serde_json::Value::Bool(true) => "yes".into(),
+    serde_json::Value::String(text) => text.clone(),
+    serde_json::Value::Array(values) => values
+      .iter()
+      .map(|value| value.as_str().map_or_else(|| value.to_string(), str::to_string))
+      .collect::<Vec<_>>()
+      .join(", "),
+    serde_json::Value::Object(entries) => entries
+      .iter()
+      .map(|(name, count)| format!("{name} {count}"))
+      .collect::<Vec<_>>()
+      .join(", "),
+    other => other.to_string(),
+  }
+}
+
+impl std::str::FromStr for Output {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    match value {
+      "text" => Ok(Self::Text),
+      "json" => Ok(Self::Json),
+      "csv" => Ok(Self::Csv),
+      "markdown" => Ok(Self::Markdown),
+      other => match other.strip_prefix("sqlite:") {
+        Some(path) if !path.is_empty() => Ok(Self::Sqlite(path.to_string())),
+        _ => Err(Error::other(format!(
+          "unknown output '{other}' (expected 'text', 'json', 'csv', 'markdown' or 'sqlite:PATH')"
+        ))),
+      },
+    }
+  }
+}
+
+/// What's known of a remote address besides its counts (see [`Summary::enrichment`]).
+struct Enrichment<'a> {
+  name: Option<&'a str>,
+  label: Option<&'a str>,
+  location: Option<crate::geoip::Location>,
+  system: Option<crate::asn::System>,
+  special: Option<&'static str>,
+  reputation: Option<u32>,
+  lists: Vec<&'a str>,
+  cloud: Option<&'a str>,
+  tor_exit: bool,
+}
+
+/// How the hour of day by day of week counts are written (`--heatmap`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heatmap {
+  /// Aligned columns, to be read as is.
+  Table,
+
+  /// Comma separated values, for a spreadsheet or another tool.
+  Csv,
+}
+
+impl std::str::FromStr for Heatmap {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    match value {
+      "table" => Ok(Self::Table),
+      "csv" => Ok(Self::Csv),
+      other => Err(Error::other(format!(
+        "unknown heatmap '{other}' (expected 'table' or 'csv')"
+      ))),
+    }
+  }
+}
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// How much more often (in percent) a source or port has to have been seen in the latest period than in the one
+/// before for the change to be called significant, and how many times it has to have been seen at least.
+const SIGNIFICANT_INCREASE: i64 = 100;
+const SIGNIFICANT_MINIMUM: u32 = 10;
+
+/// How many of the biggest changes are shown for each of a comparison's breakdowns when no `--top` is given.
+const COMPARE_SHOWN: usize = 10;
+
+/// How many of a period's busiest sources are named in a rollup.
+const ROLLUP_SOURCES_SHOWN: usize = 3;
+
+/// How many accesses a source needs to be shown when neither `--min-count` nor `--top` is given.
+pub const DEFAULT_MIN_COUNT: u32 = 100;
+
+/// The prefix lengths remote addresses are rolled up to (`--aggregate`), written `/V4` or `/V4,/V6`, e.g. `/24`
+/// or `/24,/48`. Scans often come from a whole subnet, which per address counts would fragment.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Aggregate {
+  v4: Option<u32>,
+  v6: Option<u32>,
+}
+
+impl std::str::FromStr for Aggregate {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    let invalid = || {
+      Error::other(format!(
+        "invalid prefix lengths '{value}' (expected e.g. '/24' or '/24,/48')"
+      ))
+    };
+    let mut lengths = value.split(',').map(|length| {
+      length
+        .trim()
+        .strip_prefix('/')
+        .and_then(|length| length.parse::<u32>().ok())
+        .ok_or_else(invalid)
+    });
+
+    let aggregate = Self {
+      v4: lengths.next().transpose()?,
+      v6: lengths.next().transpose()?,
+    };
+
+    match (lengths.next(), aggregate.v4 > Some(32), aggregate.v6 > Some(128)) {
+      (None, false, false) => Ok(aggregate),
+      _ => Err(invalid()),
+    }
+  }
+}
+
+impl Aggregate {
+  /// The network `address` is counted under, e.g. `203.0.113.0/24`; anything that isn't an IP address (or is of a
+  /// version not being rolled up) is counted as it is.
+  fn network(&self, address: String) -> String {
+    match (address.parse::<IpAddr>(), self.v4, self.v6) {
+      (Ok(IpAddr::V4(ip)), Some(length), _) => {
+        let mask = u32::MAX.checked_shl(32 - length).unwrap_or(0);
+        format!("{}/{length}", Ipv4Addr::from(u32::from(ip) & mask))
+      }
+      (Ok(IpAddr::V6(ip)), _, Some(length)) => {
+        let mask = u128::MAX.checked_shl(128 - length).unwrap_or(0);
+        format!("{}/{length}", Ipv6Addr::from(u128::from(ip) & mask))
+      }
+      _ => address,
+    }
+  }
+}
+
+/// The window accesses are counted in for spotting bursts when no `--bucket` is given, in seconds.
+const DEFAULT_WINDOW: i64 = 3600;
+
+/// How many times busier than the median of the windows before it a window has to be to count as a burst, and how
+/// many windows (empty ones included) that median is taken over.
+const BURST_FACTOR: u32 = 5;
+const BURST_BASELINE: usize = 24;
+
+/// A source reaching at least `SCAN_PORTS` different local ports within `SCAN_WINDOW` seconds is taken to be
+/// scanning for open ones, rather than using (or hammering) a service it knows about.
+const SCAN_PORTS: usize = 10;
+const SCAN_WINDOW: i64 = 600;
+
+/// The (lowest, highest) access counts of the ranges sources are counted in for the histogram.
+const HISTOGRAM: [(u32, u32); 4] = [(1, 1), (2, 10), (11, 100), (101, u32::MAX)];
+
+/// How many of a burst's sources are named before the rest are just counted.
+const BURST_SOURCES_SHOWN: usize = 5;
+
+/// How the counts are broken down when reported.
+#[derive(Debug, Default)]
+pub struct ReportSettings {
+  pub groupings: Vec<Grouping>,
+
+  /// The length of the windows accesses are counted in (`--bucket`), in seconds.
+  pub bucket: Option<i64>,
+
+  /// How many of the busiest sources to show (`--top`).
+  pub top: Option<usize>,
+
+  /// How many accesses a source needs to be shown (`--min-count`); the rest are only counted.
+  pub min_count: u32,
+
+  /// What remote addresses are rolled up to before being counted.
+  pub aggregate: Aggregate,
+
+  /// The longest quiet spell between two accesses from a source that still counts as one session (`--session-gap`),
+  /// in seconds.
+  pub session_gap: Option<i64>,
+
+  /// Addresses left out of the counts entirely (`--ignore-ip`, `--ignore-cidr`, `--ignore-file`), e.g. one's own
+  /// monitoring or VPN: no event with one of them is counted, whatever its kind.
+  pub ignored: Vec<Network>,
+
+  /// Only the events logged within this range of time are counted (`--since`/`--until`), when either end is given;
+  /// events without a readable timestamp can't be placed, and are left out too.
+  pub since: Option<DateTime>,
+  pub until: Option<DateTime>,
+
+  /// The periods totals are rolled up into (`--rollup`).
+  pub rollups: Vec<Rollup>,
+
+  /// Where remote addresses are, for annotating them and grouping by country (`--geoip`).
+  pub geoip: Option<GeoIp>,
+
+  /// Which autonomous system remote addresses belong to, for annotating them and grouping by it (`--asn`).
+  pub asn: Option<Asn>,
+
+  /// Lists of known bad networks (`--blocklist`, e.g. Spamhaus DROP or FireHOL), by name, that reported addresses
+  /// are tagged with when on them.
+  pub blocklists: Vec<(String, Vec<Network>)>,
+
+  /// The user's own labels for known networks (`--annotate`), most specific first.
+  pub annotations: Vec<(Network, String)>,
+
+  /// The address ranges of cloud providers (`--cloud`), by name, that reported addresses are tagged with when
+  /// hosted by one.
+  pub clouds: Vec<(String, Vec<Network>)>,
+
+  /// The addresses of Tor exit relays (`--tor`); what comes from one was sent by some Tor user, not the relay.
+  pub tor_exits: Option<HashSet<std::net::IpAddr>>,
+
+  /// How many of the busiest sources to look up the registrations of (`--whois`).
+  pub whois: Option<usize>,
+
+  /// Compares the latest period of this length with the one before it (`--compare previous-week|previous-month`).
+  pub compare: Option<Rollup>,
+
+  /// Whether (and how) to print the events per hour of each day of the week (`--heatmap`).
+  pub heatmap: Option<Heatmap>,
+
+  /// How the report is written (`--output`).
+  pub output: Output,
+
+  /// The remote addresses seen in earlier runs (`--history`).
+  pub history: Option<History>,
+}
+
+/// The accesses of one group, and the sources they came from.
+#[derive(Default)]
+struct Tally {
+  accesses: u32,
+  sources: HashSet<String>,
+}
+
+/// The events in one `--rollup` (or `--compare`) period, the accesses and attacks from each remote source, and the
+/// accesses to each local port.
+#[derive(Default)]
+struct Period {
+  events: u32,
+  sources: HashMap<String, u32>,
+  ports: HashMap<u16, u32>,
+}
+
+/// The accesses in one window of time, per source, and the local ports they reached.
+#[derive(Default)]
+struct Window {
+  sources: HashMap<String, u32>,
+  ports: BTreeSet<u16>,
+}
+
+impl Window {
+  fn accesses(&self) -> u32 {
+    self.sources.values().sum()
+  }
+}
+
+/// The accesses from one remote address.
+#[derive(Default)]
+struct Source {
+  accesses: u32,
+
+  /// When it was first and last seen, if any of its accesses had a readable timestamp.
+  seen: Option<(DateTime, DateTime)>,
+
+  /// The local ports reached in each `SCAN_WINDOW`, by its start, and how often each was reached overall.
+  probes: HashMap<i64, BTreeSet<u16>>,
+  ports: BTreeMap<u16, u32>,
+
+  /// When each access was made (in [`DateTime::seconds`]), if it had a readable timestamp.
+  times: Vec<i64>,
+}
+
+impl Source {
+  /// The local port reached most often, the lowest of any tied.
+  fn top_port(&self) -> Option<u16> {
+    let (port, _) = self
+      .ports
+      .iter()
+      .max_by_key(|(port, count)| (**count, std::cmp::Reverse(**port)))?;
+    Some(*port)
+  }
+
+  /// The (start, end) of each run of accesses with no more than `gap` seconds between them, in order.
+  fn sessions(&self, gap: i64) -> Vec<(i64, i64)> {
+    let mut times = self.times.clone();
+    times.sort_unstable();
+
+    let mut sessions: Vec<(i64, i64)> = Vec::new();
+    for time in times {
+      match sessions.last_mut() {
+        Some((_, end)) if time - *end <= gap => *end = time,
+        _ => sessions.push((time, time)),
+      }
+    }
+    sessions
+  }
+
+  /// The window in which the most ports were reached, if enough were for it to look like a port scan.
+  fn scan(&self) -> Option<(i64, &BTreeSet<u16>)> {
+    self
+      .probes
+      .iter()
+      .map(|(start, ports)| (*start, ports))
+      .filter(|(_, ports)| ports.len() >= SCAN_PORTS)
+      .max_by_key(|(start, ports)| (ports.len(), std::cmp::Reverse(*start)))
+  }
+}
+
+/// Writes the p50, p90 and p99 of `values`, if there are any. Each is by nearest rank, the value a percentile of the
+/// sorted values come at or before, so e.g. the p50 of an even number of values is the lower of the middle two.
+fn percentiles<F>(values: &mut [i64], format: F) -> Option<String>
+where
+  F: Fn(i64) -> String,
+{
+  if values.is_empty() {
+    return None;
+  }
+
+  values.sort_unstable();
+  let written = [50, 90, 99]
+    .iter()
+    .map(|percentile| {
+      let rank = (values.len() * percentile).div_ceil(100).max(1);
+      format!("p{percentile} {}", format(values[rank - 1]))
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  Some(written)
+}
+
+/// Writes how a count went from `before` to `after`, e.g. `10 -> 30 (+200%)`.
+fn change(before: u32, after: u32) -> String {
+  let percent = match before {
+    0 => "new".to_string(),
+    _ => format!("{:+.0}%", (after as f64 - before as f64) * 100.0 / before as f64),
+  };
+  let significant = after >= SIGNIFICANT_MINIMUM
+    && (before == 0 || (after as i64 - before as i64) * 100 >= SIGNIFICANT_INCREASE * before as i64);
+
+  match significant {
+    true => format!("{before} -> {after} ({percent}) [significant increase]"),
+    false => format!("{before} -> {after} ({percent})"),
+  }
+}
+
+/// Lines describing how each key's count changed from `previous` to `current` (if it did), biggest change first.
+fn compare<K, F>(previous: &HashMap<K, u32>, current: &HashMap<K, u32>, name: F) -> Vec<String>
+where
+  K: std::hash::Hash + Eq + Ord,
+  F: Fn(&K) -> String,
+{
+  let mut keys = previous
+    .keys()
+    .chain(current.keys())
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .collect::<Vec<_>>();
+  let count = |counts: &HashMap<K, u32>, key: &K| counts.get(key).copied().unwrap_or(0);
+  keys.retain(|key| count(previous, key) != count(current, key));
+  keys.sort_by_key(|key| std::cmp::Reverse(count(previous, key).abs_diff(count(current, key))));
+
+  keys
+    .into_iter()
+    .map(|key| format!("{}: {}", name(key), change(count(previous, key), count(current, key))))
+    .collect()
+}
+
+/// Extends `span` to include `timestamp`.
+fn widen(span: &mut Option<(DateTime, DateTime)>, timestamp: DateTime) {
+  *span = Some(match *span {
+    Some((first, last)) => (std::cmp::min(first, timestamp), std::cmp::max(last, timestamp)),
+    None => (timestamp, timestamp),
+  });
+}
+
+/// The Dynamic DNS updates for one host name.
+#[derive(Default)]
+struct DnsUpdates {
+  succeeded: u32,
+  failed: u32,
+
+  /// When the latest update was made, and whether it succeeded.
+  latest: Option<(DateTime, bool)>,
+}
+
+/// Everything counted from the events received so far, reported once they stop arriving (or periodically while
+/// watching).
+#[derive(Default)]
+pub struct Summary {
+  settings: ReportSettings,
+  accesses: HashMap<String, Source>,
+
+  /// Accesses in each `--bucket` (or hour, to look for bursts in), by the start of the window (in
+  /// [`DateTime::seconds`]).
+  windows: BTreeMap<i64, Window>,
+
+  /// The events in each `--rollup` period, by when it started.
+  periods: BTreeMap<(Rollup, DateTime), Period>,
+
+  /// The events in each hour of each day of the week, from Monday.
+  hours: [[u32; 24]; 7],
+
+  /// Dated events per day, by the number of days since 1970-01-01.
+  days: BTreeMap<i64, u32>,
+
+  /// Accesses per local port, i.e. which services are being reached.
+  services: BTreeMap<u16, Tally>,
+
+  /// Accesses per local host, for networks forwarding ports to several machines.
+  hosts: BTreeMap<String, Tally>,
+
+  /// Accesses and DoS attacks per protocol, as (accesses, attacks), where the entries tell.
+  protocols: BTreeMap<String, (u32, u32)>,
+
+  /// DoS attacks per source, by kind.
+  attacks: HashMap<String, BTreeMap<Attack, u32>>,
+
+  /// Sign ins to the router's web interface per source, as (succeeded, failed).
+  logins: HashMap<String, (u32, u32)>,
+  rejections: HashMap<String, u32>,
+
+  /// Leases per local (address, mac) pair.
+  devices: BTreeMap<(String, String), u32>,
+
+  /// UPnP mappings per local device, as (opened, closed).
+  upnp: BTreeMap<String, (u32, u32)>,
+  connections: Vec<InternetConnection>,
+
+  dynamic_dns: BTreeMap<String, DnsUpdates>,
+
+  /// Blocked sites and services per local device.
+  blocked: BTreeMap<String, BTreeMap<String, u32>>,
+  syncs: Vec<TimeSync>,
+
+  /// When the router says it sent each digest (once, however many digests repeat the entry), those it logged
+  /// without a readable time, and the (Message-ID, Date) of every message any event was found in.
+  sent: HashSet<DateTime>,
+  sent_undated: u32,
+  messages: HashSet<(Option<String>, Option<String>)>,
+
+  /// The earliest and latest event timestamps, and how many events had none that could be read.
+  span: Option<(DateTime, DateTime)>,
+  undated: u32,
+  events: u32,
+
+  /// The email (or, outside of one, the input) each dated event was first seen in, by (timestamp, kind, source,
+  /// destination), and how many more were dropped for having been seen in another already.
+  firsts: HashMap<(DateTime, &'static str, String, String), Container>,
+  duplicates: u32,
+
+  /// The host names of remote addresses (`--resolve`).
+  names: HashMap<String, String>,
+
+  /// The registrations of the busiest sources, if they could be looked up (`--whois`).
+  registrations: HashMap<String, Option<whois::Record>>,
+
+  /// The AbuseIPDB confidence scores of reported addresses, if they could be checked (`--abuseipdb`), and the
+  /// offenders already reported back to it (`--report`).
+  reputations: HashMap<String, Option<u32>>,
+  submitted: HashSet<String>,
+
+  /// How each DNS blocklist answered for the reported addresses that were checked (`--dnsbl`).
+  listings: HashMap<String, Vec<(String, resolve::Listing)>>,
+
+  /// Every event counted, as [`LogEvent::to_json`] writes it, to be written out with `--output sqlite`.
+  kept: Vec<serde_json::Value>,
+}
+
+/// What an event was found in: the (Message-ID, Date) of its email, and the input it was read from.
+type Container = (Option<String>, Option<String>, Option<std::sync::Arc<str>>);
+
+impl Summary {
+  pub fn new(settings: ReportSettings) -> Self {
+    Self {
+      settings,
+      ..Self::default()
+    }
+  }
+
+  pub fn record(&mut self, event: LogEvent) {
+    if self.settings.since.is_some() || self.settings.until.is_some() {
+      let within = event.timestamp().is_some_and(|timestamp| {
+        self.settings.since.is_none_or(|since| timestamp >= since)
+          && self.settings.until.is_none_or(|until| timestamp <= until)
+      });
+
+      if !within {
+        return;
+      }
+    }
+
+    if event
+      .address()
+      .is_some_and(|address| networks::any_contains(&self.settings.ignored, address))
+    {
+      return;
+    }
+
+    // A digest whose every entry was in the one before it was still received.
+    if let Some(message) = event.origin().message.as_deref() {
+      self.messages.insert((message.message_id.clone(), message.date.clone()));
+    }
+
+    if self.resent(&event) {
+      self.duplicates += 1;
+      return;
+    }
+
+    self.events += 1;
+
+    if matches!(self.settings.output, Output::Sqlite(_)) {
+      self.kept.push(event.to_json());
+    }
+
+    // The sources periods are rolled up by are those of accesses and attacks.
+    let remote = match &event {
+      LogEvent::RemoteAccess(access) => Some(&access.address),
+      LogEvent::DosAttack(attack) => Some(&attack.address),
+      _ => None,
+    };
+    let port = match &event {
+      LogEvent::RemoteAccess(access) => access.local_port,
+      _ => None,
+    };
+
+    // Rolling up and comparing by the same period counts it once.
+    let rollups = self
+      .settings
+      .rollups
+      .iter()
+      .chain(self.settings.compare.iter())
+      .copied()
+      .collect::<BTreeSet<_>>();
+
+    for rollup in rollups {
+      let Some(timestamp) = event.timestamp() else {
+        break;
+      };
+
+      let period = self.periods.entry((rollup, rollup.start(timestamp))).or_default();
+      period.events += 1;
+
+      if let Some(address) = remote {
+        *period
+          .sources
+          .entry(self.settings.aggregate.network(address.clone()))
+          .or_insert(0) += 1;
+      }
+
+      if let Some(port) = port {
+        *period.ports.entry(port).or_insert(0) += 1;
+      }
+    }
+
+    if let Some(timestamp) = event.timestamp() {
+      // 1970-01-01 was a Thursday.
+      let weekday = (timestamp.seconds().div_euclid(86400) + 3).rem_euclid(7) as usize;
+      self.hours[weekday][timestamp.hour as usize] += 1;
+      *self.days.entry(timestamp.seconds().div_euclid(86400)).or_insert(0) += 1;
+    }
+
+    match event.timestamp() {
+      Some(timestamp) => widen(&mut self.span, timestamp),
+      None => self.undated += 1,
+    }
+
+    match event {
+      LogEvent::RemoteAccess(access) => {
+        let address = self.settings.aggregate.network(access.address);
+
+        if let Some(port) = access.local_port {
+          let tally = self.services.entry(port).or_default();
+          tally.accesses += 1;
+          tally.sources.insert(address.clone());
+        }
+
+        if let Some(timestamp) = access.timestamp {
+          let size = self.settings.bucket.unwrap_or(DEFAULT_WINDOW);
+          let window = self
+            .windows
+            .entry(timestamp.seconds().div_euclid(size) * size)
+            .or_default();
+          *window.sources.entry(address.clone()).or_insert(0) += 1;
+          window.ports.extend(access.local_port);
+        }
+
+        if let Some(protocol) = access.protocol {
+          self.protocols.entry(protocol).or_default().0 += 1;
+        }
+
+        let tally = self.hosts.entry(access.local_address).or_default();
+        tally.accesses += 1;
+        tally.sources.insert(address.clone());
+
+        let source = self.accesses.entry(address).or_default();
+        source.accesses += 1;
+
+        if let Some(port) = access.local_port {
+          *source.ports.entry(port).or_insert(0) += 1;
+        }
+
+        if let Some(timestamp) = access.timestamp {
+          widen(&mut source.seen, timestamp);
+
+          let start = timestamp.seconds().div_euclid(SCAN_WINDOW) * SCAN_WINDOW;
+          source.probes.entry(start).or_default().extend(access.local_port);
+          source.times.push(timestamp.seconds());
+        }
+      }
+      LogEvent::DosAttack(attack) => {
+        if let Some(protocol) = attack.kind.protocol() {
+          self.protocols.entry(protocol.to_string()).or_default().1 += 1;
+        }
+
+        *self
+          .attacks
+          .entry(self.settings.aggregate.network(attack.address))
+          .or_default()
+          .entry(attack.kind)
+          .or_insert(0) += 1
+      }
+      LogEvent::AdminLogin(login) => {
+        let (succeeded, failed) = self.logins.entry(login.address).or_insert((0, 0));
+        *(if login.failed { failed } else { succeeded }) += 1;
+      }
+      LogEvent::WlanRejected(rejection) => *self.rejections.entry(rejection.mac).or_insert(0) += 1,
+      LogEvent::DhcpLease(lease) => *self.devices.entry((lease.address, lease.mac)).or_insert(0) += 1,
+      LogEvent::UpnpMapping(mapping) => {
+        let (opened, closed) = self.upnp.entry(mapping.address.clone()).or_insert((0, 0));
+        *(if mapping.opens() { opened } else { closed }) += 1;
+      }
+      LogEvent::InternetConnection(connection) => self.connections.push(connection),
+      LogEvent::TimeSync(sync) => self.syncs.push(sync),
+      LogEvent::EmailSent(sent) => match sent.timestamp {
+        Some(timestamp) => {
+          self.sent.insert(timestamp);
+        }
+        None => self.sent_undated += 1,
+      },
+      LogEvent::DynamicDns(update) => {
+        let updates = self.dynamic_dns.entry(update.host).or_default();
+        *(if update.succeeded {
+          &mut updates.succeeded
+        } else {
+          &mut updates.failed
+        }) += 1;
+
+        let latest = updates.latest.is_none_or(|(last, _)| update.timestamp >= Some(last));
+        if let Some(timestamp) = update.timestamp.filter(|_| latest) {
+          updates.latest = Some((timestamp, update.succeeded));
+        }
+      }
+      LogEvent::Blocked(blocked) => {
+        let destination = match blocked.service {
+          true => format!("service {}", blocked.destination),
+          false => blocked.destination,
+        };
+        *self
+          .blocked
+          .entry(blocked.address)
+          .or_default()
+          .entry(destination)
+          .or_insert(0) += 1;
+      }
+    }
+  }
+
+  /// The sources shown in the report, busiest first, so that `--top` keeps the ones that matter.
+  fn shown(&self) -> Vec<(&String, &Source)> {
+    let mut sources = self
+      .accesses
+      .iter()
+      .filter(|(_, source)| source.accesses >= self.settings.min_count)
+      .collect::<Vec<_>>();
+    sources.sort_by_key(|(address, source)| (std::cmp::Reverse(source.accesses), address.as_str()));
+    sources.truncate(self.settings.top.unwrap_or(usize::MAX));
+    sources
+  }
+
+  /// The remote addresses the report names: the sources shown, those of DoS attacks and any port scanners.
+  pub fn reported(&self) -> Vec<String> {
+    let scanners = self.accesses.iter().filter(|(_, source)| source.scan().is_some());
+    let addresses = self
+      .shown()
+      .into_iter()
+      .chain(scanners)
+      .map(|(address, _)| address)
+      .chain(self.attacks.keys())
+      .collect::<BTreeSet<_>>();
+    addresses.into_iter().cloned().collect()
+  }
+
+  /// Adds the host names `--resolve` found for reported addresses.
+  pub fn name(&mut self, names: HashMap<String, String>) {
+    self.names.extend(names);
+  }
+
+  /// The reported IP addresses that haven't been checked with AbuseIPDB yet.
+  pub fn unrated(&self) -> Vec<String> {
+    self
+      .reported()
+      .into_iter()
+      .filter(|address| address.parse::<std::net::IpAddr>().is_ok() && !self.reputations.contains_key(address))
+      .collect()
+  }
+
+  /// Records the AbuseIPDB confidence score of `address`, if it could be checked; either way it isn't checked again.
+  pub fn rate(&mut self, address: String, score: Option<u32>) {
+    self.reputations.insert(address, score);
+  }
+
+  /// The reported IP addresses that haven't been checked against the DNS blocklists yet.
+  pub fn unlisted(&self) -> Vec<String> {
+    self
+      .reported()
+      .into_iter()
+      .filter(|address| address.parse::<std::net::IpAddr>().is_ok() && !self.listings.contains_key(address))
+      .collect()
+  }
+
+  /// Records how the DNS blocklists answered for `addresses`; any that got no answer at all aren't checked again
+  /// either.
+  pub fn list(&mut self, addresses: Vec<String>, mut listings: HashMap<String, Vec<(String, resolve::Listing)>>) {
+    for address in addresses {
+      let found = listings.remove(&address).unwrap_or_default();
+      self.listings.insert(address, found);
+    }
+  }
+
+  /// The sources worth reporting to AbuseIPDB that haven't been yet (`--report`): port scanners and DoS attackers,
+  /// with the categories and a comment describing what they did. Only the firewall's own verdicts are reported, not
+  /// plain accesses, which may have been welcome.
+  pub fn offenders(&self) -> Vec<(String, Vec<u32>, String)> {
+    let mut offenders = BTreeMap::<&String, (Vec<u32>, Vec<String>)>::new();
+
+    for (address, source) in self.accesses.iter() {
+      if let Some((_, ports)) = source.scan() {
+        let (categories, comments) = offenders.entry(address).or_default();
+        categories.extend([crate::abuseipdb::CATEGORY_PORT_SCAN, crate::abuseipdb::CATEGORY_HACKING]);
+        comments.push(format!(
+          "connected to {} ports within {}",
+          ports.len(),
+          dates::duration(SCAN_WINDOW)
+        ));
+      }
+    }
+
+    for (address, kinds) in self.attacks.iter() {
+      let (categories, comments) = offenders.entry(address).or_default();
+      categories.push(crate::abuseipdb::CATEGORY_DDOS);
+      comments.push(format!(
+        "triggered router DoS detection: {}",
+        Self::describe_attacks(kinds)
+      ));
+    }
+
+    offenders
+      .into_iter()
+      .filter(|(address, _)| address.parse::<std::net::IpAddr>().is_ok() && !self.submitted.contains(*address))
+      .map(|(address, (categories, comments))| (address.clone(), categories, comments.join("; ")))
+      .collect()
+  }
+
+  /// Records that `address` was reported to AbuseIPDB, so that it isn't again in this run.
+  pub fn submitted(&mut self, address: String) {
+    self.submitted.insert(address);
+  }
+
+  /// The busiest sources whose registrations are to be looked up (`--whois`) but haven't been yet; an
+  /// `--aggregate`d network can't be.
+  pub fn unregistered(&self) -> Vec<String> {
+    self
+      .busiest_registered()
+      .into_iter()
+      .filter(|address| !self.registrations.contains_key(*address))
+      .cloned()
+      .collect()
+  }
+
+  /// Records what was found for `address` (`--whois`), if anything could be; either way it isn't looked up again.
+  pub fn register(&mut self, address: String, record: Option<whois::Record>) {
+    self.registrations.insert(address, record);
+  }
+
+  /// The `--whois` many busiest sources that are IP addresses.
+  fn busiest_registered(&self) -> Vec<&String> {
+    let Some(count) = self.settings.whois else {
+      return Vec::new();
+    };
+
+    let mut sources = self
+      .accesses
+      .iter()
+      .filter(|(address, _)| address.parse::<std::net::IpAddr>().is_ok())
+      .collect::<Vec<_>>();
+    sources.sort_by_key(|(address, source)| (std::cmp::Reverse(source.accesses), address.as_str()));
+    sources.into_iter().take(count).map(|(address, _)| address).collect()
+  }
+
+  /// Prints what the registries say of the busiest sources' networks (`--whois`).
+  fn report_registrations(&self) {
+    let sources = self.busiest_registered();
+
+    if sources.is_empty() {
+      return;
+    }
+
+    println!("whois for the {} busiest sources:", sources.len());
+
+    for address in sources {
+      match self.registrations.get(address) {
+        Some(Some(record)) => println!("{}: {record}", self.named(address)),
+        _ => println!("{}: lookup failed", self.named(address)),
+      }
+    }
+  }
+
+  /// Prints how each DNS blocklist answered for the checked addresses that any of them lists (`--dnsbl`), or that
+  /// some couldn't be asked about.
+  fn report_listings(&self) {
+    if self.listings.is_empty() {
+      return;
+    }
+
+    let listed = |listings: &[(String, resolve::Listing)]| {
+      listings
+        .iter()
+        .any(|(_, listing)| matches!(listing, resolve::Listing::Listed(_)))
+    };
+    let mut shown = self
+      .listings
+      .iter()
+      .filter(|(_, listings)| {
+        listings
+          .iter()
+          .any(|(_, listing)| *listing != resolve::Listing::NotListed)
+      })
+      .collect::<Vec<_>>();
+    shown.sort_by_key(|(address, listings)| (!listed(listings), address.as_str()));
+
+    println!(
+      "dnsbl listings: {} of {} checked sources listed",
+      self.listings.values().filter(|listings| listed(listings)).count(),
+      self.listings.len()
+    );
+
+    for (address, listings) in shown {
+      let zones = listings
+        .iter()
+        .map(|(zone, listing)| format!("{zone} {listing}"))
+        .collect::<Vec<_>>();
+      println!("{}: {}", self.named(address), zones.join(", "));
+    }
+  }
+
+  /// What's known of `address` besides its counts, from the lookups and lists it was checked against.
+  fn enrichment(&self, address: &str) -> Enrichment<'_> {
+    // An `--aggregate`d network is looked up by its first address.
+    let first = address.split('/').next().unwrap_or(address);
+    let ip = first.parse::<std::net::IpAddr>().ok();
+    let listings = self.listings.get(address).into_iter().flatten();
+    let lists = self
+      .settings
+      .blocklists
+      .iter()
+      .filter(|(_, networks)| networks::any_contains(networks, first))
+      .map(|(name, _)| name.as_str())
+      .chain(listings.filter_map(|(zone, listing)| match listing {
+        resolve::Listing::Listed(_) => Some(zone.as_str()),
+        _ => None,
+      }))
+      .collect();
+
+    Enrichment {
+      name: self.names.get(address).map(String::as_str),
+      label: ip
+        .and_then(|ip| {
+          self
+            .settings
+            .annotations
+            .iter()
+            .find(|(network, _)| network.contains(&ip))
+        })
+        .map(|(_, label)| label.as_str()),
+      location: self.locate(address),
+      system: self.system(address),
+      special: networks::special(first),
+      reputation: self.reputations.get(address).copied().flatten(),
+      lists,
+      cloud: self
+        .settings
+        .clouds
+        .iter()
+        .find(|(_, networks)| networks::any_contains(networks, first))
+        .map(|(name, _)| name.as_str()),
+      tor_exit: self
+        .settings
+        .tor_exits
+        .as_ref()
+        .zip(address.parse().ok())
+        .is_some_and(|(exits, address)| exits.contains(&address)),
+    }
+  }
+
+  /// Writes a remote address as it's reported, along with whatever else is known of it, e.g. `"203.0.113.5"
+  /// (scanner.example.com) [DE, Berlin] [AS64496 Example Hosting] [on drop]`.
+  fn named(&self, address: &str) -> String {
+    let enrichment = self.enrichment(address);
+    let tag = |value: Option<String>| value.map(|value| format!(" [{value}]")).unwrap_or_default();
+
+    let name = enrichment.name.map(|name| format!(" ({name})")).unwrap_or_default();
+    let label = tag(enrichment.label.map(str::to_string));
+    let location = tag(enrichment.location.map(|location| location.to_string()));
+    let system = tag(enrichment.system.map(|system| system.to_string()));
+    let special = tag(enrichment.special.map(|kind| format!("{kind} address")));
+    let reputation = tag(enrichment.reputation.map(|score| format!("abuse confidence {score}%")));
+    let lists = tag(
+      Some(enrichment.lists.join(", "))
+        .filter(|lists| !lists.is_empty())
+        .map(|lists| format!("on {lists}")),
+    );
+    let cloud = tag(enrichment.cloud.map(|name| format!("hosted on {name}")));
+    let exit = tag(enrichment.tor_exit.then(|| "tor exit".to_string()));
+    format!("{address:?}{name}{label}{location}{system}{special}{reputation}{lists}{cloud}{exit}")
+  }
+
+  /// Everything known of `address`, as one record of the structured output (`--output`).
+  fn entry(&self, address: &str) -> serde_json::Map<String, serde_json::Value> {
+    let source = self.accesses.get(address);
+    let enrichment = self.enrichment(address);
+    let ports = source
+      .map(|source| source.ports.keys().collect::<Vec<_>>())
+      .unwrap_or_default();
+    let attacks = self
+      .attacks
+      .get(address)
+      .into_iter()
+      .flatten()
+      .map(|(kind, count)| (kind.to_string(), serde_json::json!(count)))
+      .collect::<serde_json::Map<_, _>>();
+    let seen = source.and_then(|source| source.seen);
+
+    let serde_json::Value::Object(entry) = serde_json::json!({
+      "address": address,
+      "accesses": source.map_or(0, |source| source.accesses),
+      "first_seen": seen.map(|(first, _)| first.to_string()),
+      "last_seen": seen.map(|(_, last)| last.to_string()),
+      "top_port": source.and_then(Source::top_port),
+      "ports": ports,
+      "port_scanner": source.is_some_and(|source| source.scan().is_some()),
+      "attacks": attacks,
+      "earlier_runs": self.runs(address),
+      "name": enrichment.name,
+      "label": enrichment.label,
+      "country": enrichment.location.as_ref().and_then(|location| location.country.clone()),
+      "city": enrichment.location.as_ref().and_then(|location| location.city.clone()),
+      "asn": enrichment.system.as_ref().map(|system| system.number),
+      "organization": enrichment.system.as_ref().and_then(|system| system.organization.clone()),
+      "special": enrichment.special,
+      "abuse_confidence": enrichment.reputation,
+      "lists": enrichment.lists,
+      "cloud": enrichment.cloud,
+      "tor_exit": enrichment.tor_exit,
+      "registration": self.registrations.get(address).cloned().flatten().map(|record| record.to_string()),
+    }) else {
+      unreachable!("an object literal is an object")
+    };
+    entry
+  }
+
+  /// The records of every reported address (see [`Summary::reported`]), busiest first.
+  fn entries(&self) -> Vec<serde_json::Map<String, serde_json::Value>> {
+    let mut addresses = self.reported();
+    addresses.sort_by_key(|address| std::cmp::Reverse(self.accesses.get(address).map_or(0, |source| source.accesses)));
+    addresses.iter().map(|address| self.entry(address)).collect()
+  }
+
+  /// Prints a header and a row per reported address, a list (of ports, attacks...) being joined with `;`s.
+  fn report_csv(&self) {
+    let field = |value: &serde_json::Value| match value {
+      serde_json::Value::Null => String::new(),
+      serde_json::Value::String(value) => value.clone(),
+      serde_json::Value::Array(values) => values
+        .iter()
+        .map(|value| value.as_str().map_or_else(|| value.to_string(), str::to_string))
+        .collect::<Vec<_>>()
+        .join(";"),
+      serde_json::Value::Object(entries) => entries
+        .iter()
+        .map(|(name, value)| format!("{name} {value}"))
+        .collect::<Vec<_>>()
+        .join(";"),
+      value => value.to_string(),
+    };
+    let quote = |value: String| match value.contains([',', '"', '\n']) {
+      true => format!("\"{}\"", value.replace('"', "\"\"")),
+      false => value,
+    };
+
+    println!("{}", COLUMNS.map(|(column, _, _)| column).join(","));
+
+    for entry in self.entries() {
+      let row = COLUMNS.map(|(_, key, _)| quote(entry.get(key).map(field).unwrap_or_default()));
+      println!("{}", row.join(","));
+    }
+  }
+
+  /// Prints the report as one JSON document: the totals, the accesses per local port and a record per reported
+  /// address.
+  fn report_json(&self, inputs: usize) {
+    println!("{:#}", self.document(inputs));
+  }
+
+  /// What `--output json` prints.
+  fn document(&self, inputs: usize) -> serde_json::Value {
+    let ports = self
+      .services
+      .iter()
+      .map(|(port, tally)| (port.to_string(), serde_json::json!(tally.accesses)))
+      .collect::<serde_json::Map<_, _>>();
+    let mut document = self.totals(inputs);
+    document.insert("local_ports".into(), ports.into());
+    document.insert("reported".into(), self.entries().into());
+    serde_json::Value::Object(document)
+  }
+
+  /// Writes the page of `report --html`, which stands on its own (no scripts, styles or images elsewhere) so it
+  /// can be mailed or opened anywhere.
+  fn report_html(&self, path: &str, inputs: usize) {
+    let mut page = String::from(
+      "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>rupert report</title><style>\
+       body{font-family:sans-serif;margin:2em;color:#222}table{border-collapse:collapse;margin-bottom:2em}\
+       th,td{border:1px solid #ccc;padding:.25em .5em;text-align:left}th{background:#eee}\
+       .chart rect{fill:#4a7ab5}.chart text{font-size:11px;fill:#555}.numbers td{text-align:right}\
+       </style></head><body>\n<h1>rupert report</h1>\n",
+    );
+
+    if let Some((first, last)) = self.span {
+      page.push_str(&format!("<p>From {first} to {last}.</p>\n"));
+    }
+
+    page.push_str("<h2>Summary</h2>\n<table class=\"numbers\">");
+    for (name, total) in self.headline(inputs) {
+      page.push_str(&format!("<tr><th>{name}</th><td>{total}</td></tr>"));
+    }
+    page.push_str("</table>\n");
+
+    if let (Some(first), Some(last)) = (self.days.keys().next(), self.days.keys().next_back()) {
+      // Days without events get an empty bar, rather than the chart skipping over them.
+      let days = (*first..=*last)
+        .map(|day| {
+          let date = DateTime::from_seconds(day * 86400).to_string();
+          let count = self.days.get(&day).copied().unwrap_or(0);
+          (date.split(' ').next().unwrap_or_default().to_string(), count)
+        })
+        .collect::<Vec<_>>();
+      let hours = (0..24)
+        .map(|hour| (format!("{hour:02}:00"), self.hours.iter().map(|day| day[hour]).sum()))
+        .collect::<Vec<_>>();
+      page.push_str(&format!("<h2>Events per day</h2>\n{}\n", html::bar_chart(&days)));
+      page.push_str(&format!(
+        "<h2>Events per hour of day</h2>\n{}\n",
+        html::bar_chart(&hours)
+      ));
+    }
+
+    page.push_str("<h2>Top offenders</h2>\n<table><tr>");
+    for (heading, _) in OFFENDER_COLUMNS {
+      page.push_str(&format!("<th>{heading}</th>"));
+    }
+    page.push_str("</tr>\n");
+    for entry in self.entries() {
+      page.push_str("<tr>");
+      for (_, key) in OFFENDER_COLUMNS {
+        let cell = html::escape(&entry.get(key).map(cell).unwrap_or_default());
+        page.push_str(&format!("<td>{cell}</td>"));
+      }
+      page.push_str("</tr>\n");
+    }
+    page.push_str("</table>\n");
+
+    page.push_str("<h2>Busiest local ports</h2>\n<table class=\"numbers\"><tr><th>port</th><th>accesses</th><th>sources</th></tr>\n");
+    for (port, tally) in self.busiest_ports() {
+      page.push_str(&format!(
+        "<tr><td>{port}</td><td>{}</td><td>{}</td></tr>\n",
+        tally.accesses,
+        tally.sources.len()
+      ));
+    }
+    page.push_str("</table>\n</body></html>\n");
+
+    match std::fs::write(path, page) {
+      Ok(()) => eprintln!("wrote the report to '{path}'"),
+      Err(error) => eprintln!("WARNING - '{path}' - {error}"),
+    }
+  }
+
+  /// Prints the report as markdown tables, to paste into an issue or a wiki page: the totals, the reported
+  /// addresses and the busiest local ports.
+  fn report_markdown(&self, inputs: usize) {
+    // A `|` would end the cell early, and a line break the row.
+    let escape = |text: String| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+
+    println!("# rupert report\n");
+
+    if let Some((first, last)) = self.span {
+      println!("From {first} to {last}.\n");
+    }
+
+    println!("## Summary\n\n| | total |\n| --- | ---: |");
+    for (name, total) in self.headline(inputs) {
+      println!("| {name} | {total} |");
+    }
+
+    println!("\n## Top offenders\n");
+    let headings = OFFENDER_COLUMNS.map(|(heading, _)| heading);
+    println!("| {} |", headings.join(" | "));
+    println!("|{}", headings.map(|_| " --- |").concat());
+    for entry in self.entries() {
+      let row = OFFENDER_COLUMNS.map(|(_, key)| escape(entry.get(key).map(cell).unwrap_or_default()));
+      println!("| {} |", row.join(" | "));
+    }
+
+    println!("\n## Busiest local ports\n\n| port | accesses | sources |\n| ---: | ---: | ---: |");
+    for (port, tally) in self.busiest_ports() {
+      println!("| {port} | {} | {} |", tally.accesses, tally.sources.len());
+    }
+  }
+
+  /// The counts the page and markdown reports open with.
+  fn headline(&self, inputs: usize) -> [(&'static str, u32); 6] {
+    let scanners = self.accesses.values().filter(|source| source.scan().is_some()).count();
+    [
+      ("inputs read", inputs as u32),
+      ("events", self.events),
+      ("remote sources", self.accesses.len() as u32),
+      ("port scanners", scanners as u32),
+      ("dos attackers", self.attacks.len() as u32),
+      ("duplicates dropped", self.duplicates),
+    ]
+  }
+
+  /// The local ports reached most, as many as `--top` allows.
+  fn busiest_ports(&self) -> Vec<(&u16, &Tally)> {
+    let mut ports = self.services.iter().collect::<Vec<_>>();
+    ports.sort_by_key(|(port, tally)| (std::cmp::Reverse(tally.accesses), **port));
+    ports.truncate(self.settings.top.unwrap_or(COMPARE_SHOWN));
+    ports
+  }
+
+  /// How much was read and counted, for the structured outputs.
+  fn totals(&self, inputs: usize) -> serde_json::Map<String, serde_json::Value> {
+    let serde_json::Value::Object(totals) = serde_json::json!({
+      "inputs": inputs,
+      "events": self.events,
+      "duplicates": self.duplicates,
+      "first_event": self.span.map(|(first, _)| first.to_string()),
+      "last_event": self.span.map(|(_, last)| last.to_string()),
+      "sources": self.accesses.len(),
+    }) else {
+      unreachable!("an object literal is an object")
+    };
+    totals
+  }
+
+  /// Adds to the SQLite database of `--output sqlite:PATH`: the `events` counted (with the fields particular to
+  /// their kind as a JSON object, for `json_extract`), but for any an earlier run over the same input added; and
+  /// the `sources` reported, the accesses per `local_ports` and the `totals`, each replacing what an earlier run
+  /// wrote for the same address, port or total (though a source keeps when it was first seen).
+  fn report_sqlite(&self, path: &str, inputs: usize) {
+    let value = |value: &serde_json::Value| match value {
+      serde_json::Value::Null => sqlite::Value::Null,
+      serde_json::Value::Bool(value) => sqlite::Value::Integer(*value as i64),
+      serde_json::Value::Number(number) => match number.as_i64() {
+        Some(integer) => sqlite::Value::Integer(integer),
+        None => sqlite::Value::Text(number.to_string()),
+      },
+      serde_json::Value::String(text) => sqlite::Value::Text(text.clone()),
+      other => sqlite::Value::Text(other.to_string()),
+    };
+    let common = [
+      "kind",
+      "timestamp",
+      "address",
+      "source",
+      "line",
+      "message_id",
+      "subject",
+    ];
+
+    let events = sqlite::Table {
+      name: "events",
+      key: &["kind", "timestamp", "source", "line", "fields"],
+      conflict: sqlite::Conflict::Ignore,
+      columns: common
+        .iter()
+        .map(|column| (*column, if *column == "line" { "INTEGER" } else { "TEXT" }))
+        .chain([("fields", "TEXT")])
+        .collect(),
+      rows: self
+        .kept
+        .iter()
+        .filter_map(serde_json::Value::as_object)
+        .map(|event| {
+          let fields = event
+            .iter()
+            .filter(|(key, _)| !common.contains(&key.as_str()))
+            .map(|(key, field)| (key.clone(), field.clone()))
+            .collect::<serde_json::Map<_, _>>();
+          common
+            .iter()
+            .map(|column| event.get(*column).map_or(sqlite::Value::Null, value))
+            .chain([sqlite::Value::Text(serde_json::Value::Object(fields).to_string())])
+            .collect()
+        })
+        .collect(),
+    };
+    let sources = sqlite::Table {
+      name: "sources",
+      key: &["ip"],
+      conflict: sqlite::Conflict::Update {
+        earliest: &["first_seen"],
+      },
+      columns: COLUMNS.iter().map(|(column, _, kind)| (*column, *kind)).collect(),
+      rows: self
+        .entries()
+        .iter()
+        .map(|entry| {
+          COLUMNS
+            .iter()
+            .map(|(_, key, _)| entry.get(*key).map_or(sqlite::Value::Null, value))
+            .collect()
+        })
+        .collect(),
+    };
+    let ports = sqlite::Table {
+      name: "local_ports",
+      key: &["port"],
+      conflict: sqlite::Conflict::Update { earliest: &[] },
+      columns: vec![("port", "INTEGER"), ("accesses", "INTEGER"), ("sources", "INTEGER")],
+      rows: self
+        .services
+        .iter()
+        .map(|(port, tally)| {
+          vec![
+            sqlite::Value::Integer(*port as i64),
+            sqlite::Value::Integer(tally.accesses as i64),
+            sqlite::Value::Integer(tally.sources.len() as i64),
+          ]
+        })
+        .collect(),
+    };
+    let totals = sqlite::Table {
+      name: "totals",
+      key: &["name"],
+      conflict: sqlite::Conflict::Update { earliest: &[] },
+      columns: vec![("name", "TEXT"), ("value", "TEXT")],
+      rows: self
+        .totals(inputs)
+        .iter()
+        .map(|(name, total)| {
+          let total = total.as_str().map_or_else(|| total.to_string(), str::to_string);
+          vec![sqlite::Value::Text(name.clone()), sqlite::Value::Text(total)]
+        })
+        .collect(),
+    };
+
+    match sqlite::write(path, &[events, sources, ports, totals]).as_deref() {
+      Ok([events, sources, ..]) => eprintln!(
+        "added {events} new events (of {}) to '{path}', and wrote {sources} sources",
+        self.kept.len()
+      ),
+      Ok(_) => {}
+      Err(error) => eprintln!("WARNING - {error}"),
+    }
+  }
+
+  fn system(&self, address: &str) -> Option<crate::asn::System> {
+    self.settings.asn.as_ref()?.system(address)
+  }
+
+  fn locate(&self, address: &str) -> Option<crate::geoip::Location> {
+    self.settings.geoip.as_ref()?.locate(address)
+  }
+
+  /// The country `address` is in, for grouping by.
+  fn country(&self, address: &str) -> String {
+    self
+      .locate(address)
+      .and_then(|location| location.country)
+      .unwrap_or_else(|| "unknown".into())
+  }
+
+  /// Prints everything counted; `inputs` is how many files (or messages, downloads...) have been read so far.
+  pub fn report(&self, inputs: usize) {
+    match &self.settings.output {
+      Output::Json => return self.report_json(inputs),
+      Output::Csv => return self.report_csv(),
+      Output::Markdown => return self.report_markdown(inputs),
+      Output::Sqlite(path) => return self.report_sqlite(path, inputs),
+      Output::Html(path) => return self.report_html(path, inputs),
+      Output::Text => {}
+    }
+
+    // A stale record means the router can't be reached by name, which matters more than any count below.
+    for (host, updates) in self.dynamic_dns.iter().filter(|(_, updates)| updates.failed > 0) {
+      let stale = match updates.latest {
+        Some((timestamp, false)) => format!(", most recently at {timestamp}; the record may be stale"),
+        _ => String::new(),
+      };
+      println!(
+        "WARNING - dynamic dns update for '{host}' failed {} times (of {}){stale}",
+        updates.failed,
+        updates.succeeded + updates.failed
+      );
+    }
+
+    // Nothing from these ranges reaches the router from outside, so the line was forged or read wrong.
+    let special = self
+      .accesses
+      .keys()
+      .filter(|address| networks::special(address.split('/').next().unwrap_or(address)).is_some())
+      .count();
+
+    if special > 0 {
+      println!(
+        "WARNING - {special} remote sources are private, reserved or multicast addresses; the log may be forged or \
+         misparsed"
+      );
+    }
+
+    let total = self.accesses.len();
+    let shown = self.shown();
+    let hidden = total - shown.len();
+
+    for (key, source) in shown {
+      let seen = match source.seen {
+        Some((first, last)) if first == last => format!(" (seen at {first})"),
+        Some((first, last)) => format!(" (first seen {first}, last seen {last})"),
+        None => String::new(),
+      };
+      let scanner = match source.scan() {
+        Some(_) => " [port scanner]",
+        None => "",
+      };
+      let returning = match self.runs(key) {
+        0 => String::new(),
+        runs => format!(" [returning, seen in {runs} earlier runs]"),
+      };
+      // The same source tripping the DoS detection as well says more than either count alone.
+      let attacks = match self.attacks.get(key) {
+        Some(kinds) => format!(" [also triggered {}]", Self::describe_attacks(kinds)),
+        None => String::new(),
+      };
+      println!(
+        "{}: {:?}{seen}{scanner}{returning}{attacks}",
+        self.named(key),
+        source.accesses
+      );
+    }
+
+    println!("{hidden} hidden entries (of {})", total);
+    self.report_histogram();
+    self.report_percentiles();
+
+    if self.settings.groupings.contains(&Grouping::Port) {
+      report_groups(
+        "local port",
+        self.services.iter().map(|(port, tally)| (format!(":{port}"), tally)),
+      );
+    } else if !self.services.is_empty() {
+      let services = self
+        .services
+        .iter()
+        .map(|(port, tally)| format!(":{port} {}", tally.accesses))
+        .collect::<Vec<_>>();
+      println!("accesses by local port: {}", services.join(", "));
+    }
+
+    if self.settings.groupings.contains(&Grouping::Host) {
+      report_groups(
+        "local host",
+        self.hosts.iter().map(|(host, tally)| (host.clone(), tally)),
+      );
+    }
+
+    if self.settings.groupings.contains(&Grouping::Asn) {
+      let mut systems = BTreeMap::<String, Tally>::new();
+
+      for (address, source) in self.accesses.iter() {
+        let system = self.system(address).map(|system| system.to_string());
+        let tally = systems.entry(system.unwrap_or_else(|| "unknown".into())).or_default();
+        tally.accesses += source.accesses;
+        tally.sources.insert(address.clone());
+      }
+
+      report_groups(
+        "autonomous system",
+        systems.iter().map(|(system, tally)| (system.clone(), tally)),
+      );
+    }
+
+    if self.settings.groupings.contains(&Grouping::Country) {
+      let mut countries = BTreeMap::<String, Tally>::new();
+
+      for (address, source) in self.accesses.iter() {
+        let tally = countries.entry(self.country(address)).or_default();
+        tally.accesses += source.accesses;
+        tally.sources.insert(address.clone());
+      }
+
+      report_groups(
+        "country",
+        countries.iter().map(|(country, tally)| (country.clone(), tally)),
+      );
+    }
+
+    if let Some(size) = self.settings.bucket.filter(|_| !self.windows.is_empty()) {
+      self.report_windows(size);
+    }
+
+    if !self.protocols.is_empty() {
+      println!("protocols:");
+
+      for (protocol, (accesses, attacks)) in self.protocols.iter() {
+        println!("{:?}: {accesses} accesses, {attacks} dos attacks", protocol);
+      }
+    }
+
+    self.report_registrations();
+    self.report_listings();
+    self.report_bursts();
+    self.report_scanners();
+    self.report_rollups();
+
+    if let Some(rollup) = self.settings.compare {
+      self.report_compare(rollup);
+    }
+
+    if let Some(heatmap) = self.settings.heatmap {
+      self.report_heatmap(heatmap);
+    }
+
+    if let Some(gap) = self.settings.session_gap {
+      self.report_sessions(gap);
+    }
+
+    if !self.attacks.is_empty() {
+      println!("dos attacks from {} sources:", self.attacks.len());
+
+      for (key, kinds) in self.attacks.iter() {
+        let total = kinds.values().sum::<u32>();
+        let accesses = match self.accesses.get(key) {
+          Some(source) => format!(" [also tried {} connections]", source.accesses),
+          None => String::new(),
+        };
+        println!(
+          "{}: {:?} ({}){accesses}",
+          self.named(key),
+          total,
+          Self::describe_attacks(kinds)
+        );
+      }
+    }
+
+    if !self.logins.is_empty() {
+      println!("admin logins from {} sources:", self.logins.len());
+
+      for (key, (succeeded, failed)) in self.logins.iter() {
+        println!("{:?}: {:?} ({:?} failed)", key, succeeded, failed);
+      }
+    }
+
+    if !self.rejections.is_empty() {
+      println!("wlan access rejected for {} devices:", self.rejections.len());
+
+      for (key, value) in self.rejections.iter() {
+        println!("{:?}: {:?}", key, value);
+      }
+    }
+
+    if !self.devices.is_empty() {
+      println!("{} local devices (address, mac: leases):", self.devices.len());
+
+      for ((address, mac), value) in self.devices.iter() {
+        println!("{:?}, {:?}: {:?}", address, mac, value);
+      }
+    }
+
+    if !self.upnp.is_empty() {
+      println!("upnp port mappings from {} local devices:", self.upnp.len());
+
+      for (key, (opened, closed)) in self.upnp.iter() {
+        println!("{:?}: {:?} opened, {:?} closed", key, opened, closed);
+      }
+    }
+
+    if !self.blocked.is_empty() {
+      println!("blocked from {} local devices:", self.blocked.len());
+
+      for (key, destinations) in self.blocked.iter() {
+        let destinations = destinations
+          .iter()
+          .map(|(destination, count)| format!("{destination} ({count})"))
+          .collect::<Vec<_>>();
+        println!("{:?}: {}", key, destinations.join(", "));
+      }
+    }
+
+    if !self.dynamic_dns.is_empty() {
+      println!("dynamic dns updates for {} hosts:", self.dynamic_dns.len());
+
+      for (key, updates) in self.dynamic_dns.iter() {
+        println!(
+          "{:?}: {:?} succeeded, {:?} failed",
+          key, updates.succeeded, updates.failed
+        );
+      }
+    }
+
+    if !self.connections.is_empty() {
+      self.report_connections();
+    }
+
+    if !self.syncs.is_empty() {
+      let servers = self
+        .syncs
+        .iter()
+        .filter_map(|sync| sync.server.as_deref())
+        .collect::<BTreeSet<_>>();
+      let last = self.syncs.iter().filter_map(|sync| sync.timestamp).max();
+
+      println!(
+        "time synchronized {} times{}{}",
+        self.syncs.len(),
+        last.map(|last| format!(", last at {last}")).unwrap_or_default(),
+        match servers.is_empty() {
+          true => String::new(),
+          false => format!(" (with {})", servers.into_iter().collect::<Vec<_>>().join(", ")),
+        }
+      );
+    }
+
+    // Each digest logs the sending of the one before it, so one more message than sends is expected; any fewer
+    // means some are missing from the archive.
+    let sent = self.sent.len() as u32 + self.sent_undated;
+    if sent > 0 {
+      println!(
+        "the router sent {sent} digests, {} messages were found",
+        self.messages.len()
+      );
+
+      if (self.messages.len() as u32) < sent {
+        println!(
+          "WARNING - {} digests the router sent are missing",
+          sent - self.messages.len() as u32
+        );
+      }
+    }
+
+    self.report_totals(inputs);
+  }
+
+  /// Reads back the reported addresses of an earlier run's `--output json` or `--output csv` at `path`, with their
+  /// accesses and when they were first and last seen, for `diff` to compare. Whether `path` was one; anything else
+  /// is left to be parsed as logs.
+  pub fn import(&mut self, path: &str) -> Result<bool> {
+    let Ok(file) = std::fs::File::open(path) else {
+      return Ok(false);
+    };
+    let mut first = String::new();
+    let header = COLUMNS.map(|(column, _, _)| column).join(",");
+
+    // Only the first line is read when it's neither, as a log can be large.
+    let mut reader = std::io::BufReader::new(file);
+    if std::io::BufRead::read_line(&mut reader, &mut first).is_err() {
+      return Ok(false);
+    }
+
+    let invalid = |error: std::io::Error| Error::other(format!("'{path}' - {error}"));
+    let mut rest = String::new();
+    let rows = match first.trim_end() {
+      "{" => {
+        std::io::Read::read_to_string(&mut reader, &mut rest).map_err(invalid)?;
+        let Ok(document) = serde_json::from_str::<serde_json::Value>(&(first + &rest)) else {
+          return Ok(false);
+        };
+        let Some(reported) = document.get("reported").and_then(serde_json::Value::as_array) else {
+          return Ok(false);
+        };
+
+        reported
+          .iter()
+          .filter_map(|entry| {
+            let text = |key: &str| entry.get(key).and_then(serde_json::Value::as_str).map(str::to_string);
+            let accesses = entry.get("accesses").and_then(serde_json::Value::as_u64)? as u32;
+            Some((text("address")?, accesses, text("first_seen"), text("last_seen")))
+          })
+          .collect::<Vec<_>>()
+      }
+      line if line == header => {
+        std::io::Read::read_to_string(&mut reader, &mut rest).map_err(invalid)?;
+        // The first four columns are never quoted: an address, a count and two timestamps.
+        rest
+          .lines()
+          .filter_map(|line| {
+            let mut fields = line.splitn(5, ',').map(str::to_string);
+            let address = fields.next()?;
+            let accesses = fields.next()?.parse().ok()?;
+            let mut seen = fields
+              .take(2)
+              .map(|field| Some(field).filter(|field| !field.is_empty()));
+            Some((address, accesses, seen.next().flatten(), seen.next().flatten()))
+          })
+          .collect()
+      }
+      _ => return Ok(false),
+    };
+
+    for (address, accesses, first, last) in rows.into_iter().filter(|(_, accesses, _, _)| *accesses > 0) {
+      let seen = first
+        .zip(last)
+        .and_then(|(first, last)| DateTime::parse_iso(&first).zip(DateTime::parse_iso(&last)));
+      let source = self.accesses.entry(address).or_default();
+      source.accesses += accesses;
+      source.seen = seen;
+    }
+
+    Ok(true)
+  }
+
+  /// Prints the sources that only appear in `later`, those that no longer appear, and how the counts of the rest
+  /// changed, biggest changes first.
+  pub fn report_diff(&self, later: &Summary) {
+    let top = self.settings.top.unwrap_or(usize::MAX);
+    fn only<'a>(from: &'a Summary, other: &Summary) -> Vec<(&'a String, u32)> {
+      let mut sources = from
+        .accesses
+        .iter()
+        .filter(|(address, _)| !other.accesses.contains_key(*address))
+        .map(|(address, source)| (address, source.accesses))
+        .collect::<Vec<_>>();
+      sources.sort_by_key(|(address, accesses)| (std::cmp::Reverse(*accesses), address.as_str()));
+      sources
+    }
+
+    for (name, sources) in [("new", only(later, self)), ("gone", only(self, later))] {
+      println!("{name} sources: {}", sources.len());
+
+      for (address, accesses) in sources.into_iter().take(top) {
+        println!("{:?}: {:?}", address, accesses);
+      }
+    }
+
+    let mut changed = self
+      .accesses
+      .iter()
+      .filter_map(|(address, source)| Some((address, source.accesses, later.accesses.get(address)?.accesses)))
+      .filter(|(_, before, after)| before != after)
+      .collect::<Vec<_>>();
+    changed.sort_by_key(|(address, before, after)| (std::cmp::Reverse(before.abs_diff(*after)), address.as_str()));
+    println!("changed sources: {}", changed.len());
+
+    for (address, before, after) in changed.into_iter().take(top) {
+      let delta = after as i64 - before as i64;
+      println!(
+        "{:?}: {before} -> {after} ({delta:+}, {:+.0}%)",
+        address,
+        delta as f64 * 100.0 / before as f64
+      );
+    }
+  }
+
+  /// Whether `event` is one already seen in another email (or input), as when each digest repeats the last lines
+  /// of the one before it. The same entry more than once in one email is counted each time, as a router logs one
+  /// for each of several accesses in the same second.
+  fn resent(&mut self, event: &LogEvent) -> bool {
+    let Some(timestamp) = event.timestamp() else {
+      return false;
+    };
+
+    // What tells two entries of the same kind in the same second apart.
+    let (source, destination) = match event {
+      LogEvent::RemoteAccess(access) => (
+        format!("{}:{}", access.address, access.port.unwrap_or_default()),
+        format!("{}:{}", access.local_address, access.local_port.unwrap_or_default()),
+      ),
+      LogEvent::DosAttack(attack) => (attack.address.clone(), attack.kind.to_string()),
+      LogEvent::AdminLogin(login) => (login.address.clone(), login.failed.to_string()),
+      LogEvent::WlanRejected(rejection) => (rejection.mac.clone(), rejection.reason.clone()),
+      LogEvent::DhcpLease(lease) => (lease.mac.clone(), lease.address.clone()),
+      LogEvent::UpnpMapping(mapping) => (mapping.address.clone(), mapping.action.clone()),
+      LogEvent::InternetConnection(connection) => (connection.address.clone().unwrap_or_default(), String::new()),
+      LogEvent::TimeSync(sync) => (sync.server.clone().unwrap_or_default(), String::new()),
+      LogEvent::EmailSent(sent) => (String::new(), sent.recipient.clone()),
+      LogEvent::Blocked(blocked) => (blocked.address.clone(), blocked.destination.clone()),
+      LogEvent::DynamicDns(update) => (update.host.clone(), update.succeeded.to_string()),
+    };
+    let key = (timestamp, event.kind(), source, destination);
+
+    let origin = event.origin();
+    let container = (
+      origin.message.as_ref().and_then(|message| message.message_id.clone()),
+      origin.message.as_ref().and_then(|message| message.date.clone()),
+      origin.source.clone(),
+    );
+
+    *self.firsts.entry(key).or_insert_with(|| container.clone()) != container
+  }
+
+  /// Lists the DoS detections of one source, e.g. `TCP/UDP SYN flood 9, ...`.
+  fn describe_attacks(kinds: &BTreeMap<Attack, u32>) -> String {
+    kinds
+      .iter()
+      .map(|(kind, count)| format!("{kind} {count}"))
+      .collect::<Vec<_>>()
+      .join(", ")
+  }
+
+  /// How many earlier runs `address` was seen in, if there's a `--history`.
+  fn runs(&self, address: &str) -> u32 {
+    self
+      .settings
+      .history
+      .as_ref()
+      .map_or(0, |history| history.runs(address))
+  }
+
+  /// Records this run's remote addresses in the `--history`, if there is one.
+  pub fn save_history(&self) -> Result<()> {
+    match self.settings.history.as_ref() {
+      Some(history) => history.save(self.accesses.keys().chain(self.attacks.keys())),
+      None => Ok(()),
+    }
+  }
+
+  /// Prints the scale of the run as a whole.
+  fn report_totals(&self, inputs: usize) {
+    let sources = self.accesses.keys().chain(self.attacks.keys()).collect::<HashSet<_>>();
+
+    println!("summary:");
+
+    if inputs > 0 {
+      println!("inputs read: {inputs}");
+    }
+
+    println!("events: {}", self.events);
+    println!("unique sources: {}", sources.len());
+
+    if self.settings.history.is_some() {
+      let returning = sources.iter().filter(|address| self.runs(address) > 0).count();
+      println!("returning sources: {returning}");
+    }
+
+    if let Some((first, last)) = self.span {
+      let days = last.seconds().div_euclid(86400) - first.seconds().div_euclid(86400) + 1;
+      println!("logged between {first} and {last}");
+      println!(
+        "events per day: {:.1}",
+        (self.events - self.undated) as f64 / days as f64
+      );
+    }
+
+    if self.undated > 0 {
+      println!("{} events without a readable timestamp", self.undated);
+    }
+
+    if self.duplicates > 0 {
+      println!(
+        "{} events repeated from earlier emails (or inputs) left out",
+        self.duplicates
+      );
+    }
+  }
+
+  /// Prints the accesses in each `--bucket` window that had any, with the sources behind them (busiest first).
+  fn report_windows(&self, size: i64) {
+    println!("accesses per {}:", dates::duration(size));
+
+    for (start, window) in self.windows.iter() {
+      let counts = busiest(&window.sources)
+        .into_iter()
+        .map(|(address, count)| format!("{address} {count}"))
+        .collect::<Vec<_>>();
+      println!(
+        "{}: {} ({})",
+        DateTime::from_seconds(*start),
+        window.accesses(),
+        counts.join(", ")
+      );
+    }
+  }
+
+  /// The windows with far more accesses than the median of those before them, as their start, their count and that
+  /// median.
+  fn bursts(&self) -> Vec<(i64, u32, u32)> {
+    let size = self.settings.bucket.unwrap_or(DEFAULT_WINDOW);
+    let (Some(first), Some(last)) = (self.windows.keys().next(), self.windows.keys().next_back()) else {
+      return Vec::new();
+    };
+
+    let mut counts = Vec::new();
+    let mut bursts = Vec::new();
+
+    for start in (*first..=*last).step_by(size as usize) {
+      let count = self.windows.get(&start).map_or(0, Window::accesses);
+      let mut baseline = counts[counts.len().saturating_sub(BURST_BASELINE)..].to_vec();
+      baseline.sort_unstable();
+      let median = baseline.get(baseline.len() / 2).copied().unwrap_or(0);
+
+      if !baseline.is_empty() && count > BURST_FACTOR * median.max(1) {
+        bursts.push((start, count, median));
+      }
+
+      counts.push(count);
+    }
+
+    bursts
+  }
+
+  /// Prints the windows with far more accesses than the median of those before them, e.g. a flood from a botnet,
+  /// along with who was behind them and what they reached.
+  fn report_bursts(&self) {
+    let size = self.settings.bucket.unwrap_or(DEFAULT_WINDOW);
+    let bursts = self.bursts();
+
+    if bursts.is_empty() {
+      return;
+    }
+
+    println!(
+      "access bursts (over {BURST_FACTOR}x the median of the {BURST_BASELINE} windows of {} before):",
+      dates::duration(size)
+    );
+
+    for (start, count, median) in bursts {
+      let Some(window) = self.windows.get(&start) else {
+        continue;
+      };
+
+      let sources = busiest(&window.sources);
+      let mut named = sources
+        .iter()
+        .take(BURST_SOURCES_SHOWN)
+        .map(|(address, _)| address.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+      if sources.len() > BURST_SOURCES_SHOWN {
+        named += &format!(" (+{} more)", sources.len() - BURST_SOURCES_SHOWN);
+      }
+
+      let ports = window.ports.iter().map(|port| format!(":{port}")).collect::<Vec<_>>();
+      println!(
+        "{}: {count} accesses (median {median}) from {named} to {}",
+        DateTime::from_seconds(start),
+        match ports.is_empty() {
+          true => "unknown ports".to_string(),
+          false => ports.join(", "),
+        }
+      );
+    }
+  }
+
+  /// Prints the totals of each `--rollup` period: its events, how many remote sources there were and the busiest of
+  /// them.
+  fn report_rollups(&self) {
+    for rollup in self.settings.rollups.iter() {
+      let periods = self.periods.iter().filter(|((kind, _), _)| kind == rollup);
+      println!("per {}:", rollup.name());
+
+      for ((_, start), period) in periods {
+        let label = rollup.label(*start);
+        let busiest = busiest(&period.sources)
+          .into_iter()
+          .take(ROLLUP_SOURCES_SHOWN)
+          .map(|(address, count)| format!("{address} {count}"))
+          .collect::<Vec<_>>();
+        let busiest = match busiest.is_empty() {
+          true => String::new(),
+          false => format!(" ({})", busiest.join(", ")),
+        };
+        println!(
+          "{label}: {} events, {} unique sources{busiest}",
+          period.events,
+          period.sources.len()
+        );
+      }
+    }
+  }
+
+  /// Prints how the latest period's events, sources and ports compare with those of the period before it, biggest
+  /// changes first, calling out the significant increases.
+  fn report_compare(&self, rollup: Rollup) {
+    let Some(((_, latest), current)) = self.periods.iter().rfind(|((kind, _), _)| *kind == rollup) else {
+      return;
+    };
+
+    let start = rollup.previous(*latest);
+    let empty = Period::default();
+    let previous = self.periods.get(&(rollup, start)).unwrap_or(&empty);
+
+    println!(
+      "{} compared with the {} before ({}):",
+      rollup.label(*latest),
+      rollup.name(),
+      rollup.label(start)
+    );
+    println!("events: {}", change(previous.events, current.events));
+
+    let sources = compare(&previous.sources, &current.sources, |address| format!("{address:?}"));
+    let ports = compare(&previous.ports, &current.ports, |port| format!("\":{port}\""));
+    let mut breakdowns = vec![("sources", sources), ("ports", ports)];
+
+    if self.settings.geoip.is_some() {
+      let countries = |period: &Period| {
+        let mut countries = HashMap::new();
+        for (address, count) in period.sources.iter() {
+          *countries.entry(self.country(address)).or_insert(0) += count;
+        }
+        countries
+      };
+      let changes = compare(&countries(previous), &countries(current), |country| {
+        format!("{country:?}")
+      });
+      breakdowns.push(("countries", changes));
+    }
+
+    for (name, changes) in breakdowns {
+      if changes.is_empty() {
+        continue;
+      }
+
+      println!("{name}:");
+
+      for line in changes.into_iter().take(self.settings.top.unwrap_or(COMPARE_SHOWN)) {
+        println!("{line}");
+      }
+    }
+  }
+
+  /// Prints the events in each hour (columns) of each day of the week (rows), showing whether they cluster at
+  /// particular times.
+  fn report_heatmap(&self, heatmap: Heatmap) {
+    println!("events per hour of day and day of week:");
+
+    match heatmap {
+      Heatmap::Table => {
+        let width = self.hours.iter().flatten().max().unwrap_or(&0).to_string().len().max(2);
+        let header = (0..24).map(|hour| format!("{hour:>width$}")).collect::<Vec<_>>();
+        println!("    {}", header.join(" "));
+
+        for (day, hours) in WEEKDAYS.iter().zip(self.hours.iter()) {
+          let counts = hours.iter().map(|count| format!("{count:>width$}")).collect::<Vec<_>>();
+          println!("{day} {}", counts.join(" "));
+        }
+      }
+      Heatmap::Csv => {
+        let header = (0..24).map(|hour| hour.to_string()).collect::<Vec<_>>();
+        println!("day,{}", header.join(","));
+
+        for (day, hours) in WEEKDAYS.iter().zip(self.hours.iter()) {
+          let counts = hours.iter().map(|count| count.to_string()).collect::<Vec<_>>();
+          println!("{day},{}", counts.join(","));
+        }
+      }
+    }
+  }
+
+  /// Prints the sources that look to have been scanning for open ports, whatever their total, with the window in
+  /// which they reached the most.
+  fn report_scanners(&self) {
+    let mut scanners = self
+      .accesses
+      .iter()
+      .filter_map(|(address, source)| Some((address, source.scan()?)))
+      .collect::<Vec<_>>();
+
+    if scanners.is_empty() {
+      return;
+    }
+
+    scanners.sort_by_key(|(address, (_, ports))| (std::cmp::Reverse(ports.len()), address.as_str()));
+    println!(
+      "port scanners ({SCAN_PORTS} or more local ports within {}):",
+      dates::duration(SCAN_WINDOW)
+    );
+
+    for (address, (start, ports)) in scanners {
+      println!(
+        "{}: {} ports in the window from {} ({} to :{})",
+        self.named(address),
+        ports.len(),
+        DateTime::from_seconds(start),
+        ports.first().map(|port| format!(":{port}")).unwrap_or_default(),
+        ports.last().copied().unwrap_or_default()
+      );
+    }
+  }
+
+  /// Prints how many sources fall into each range of access counts, the shape of the long tail that's otherwise
+  /// only counted as hidden.
+  fn report_histogram(&self) {
+    if self.accesses.is_empty() {
+      return;
+    }
+
+    let ranges = HISTOGRAM
+      .iter()
+      .map(|(lowest, highest)| {
+        let count = self
+          .accesses
+          .values()
+          .filter(|source| (*lowest..=*highest).contains(&source.accesses))
+          .count();
+        match (lowest, highest) {
+          (_, &u32::MAX) => format!("{lowest}+: {count}"),
+          _ if lowest == highest => format!("{lowest}: {count}"),
+          _ => format!("{lowest}-{highest}: {count}"),
+        }
+      })
+      .collect::<Vec<_>>();
+    println!("sources by accesses: {}", ranges.join(", "));
+  }
+
+  /// Prints the p50/p90/p99 of the accesses per source and of the time between one source's consecutive accesses,
+  /// figures that can be compared from one month's logs to the next.
+  fn report_percentiles(&self) {
+    let mut counts = self
+      .accesses
+      .values()
+      .map(|source| source.accesses as i64)
+      .collect::<Vec<_>>();
+    let mut gaps = Vec::new();
+
+    for source in self.accesses.values() {
+      let mut times = source.times.clone();
+      times.sort_unstable();
+      gaps.extend(times.windows(2).map(|pair| pair[1] - pair[0]));
+    }
+
+    if let Some(counts) = percentiles(&mut counts, |count| count.to_string()) {
+      println!("accesses per source: {counts}");
+    }
+
+    if let Some(gaps) = percentiles(&mut gaps, |gap| format!("{gap}s")) {
+      println!("time between a source's accesses: {gaps}");
+    }
+  }
+
+  /// Prints how many sessions the shown sources' accesses make up and how long they lasted, longest in total first;
+  /// a crawler coming back every night reads better as that than as a single count.
+  fn report_sessions(&self, gap: i64) {
+    let mut sources = self
+      .accesses
+      .iter()
+      .filter(|(_, source)| source.accesses >= self.settings.min_count && !source.times.is_empty())
+      .map(|(address, source)| {
+        let lengths = source
+          .sessions(gap)
+          .iter()
+          .map(|(start, end)| end - start)
+          .collect::<Vec<_>>();
+        (address, lengths)
+      })
+      .collect::<Vec<_>>();
+
+    if sources.is_empty() {
+      return;
+    }
+
+    sources.sort_by_key(|(address, lengths)| (std::cmp::Reverse(lengths.iter().sum::<i64>()), address.as_str()));
+    println!("sessions (accesses at most {} apart):", dates::duration(gap));
+
+    for (address, lengths) in sources.into_iter().take(self.settings.top.unwrap_or(usize::MAX)) {
+      println!(
+        "{}: {} sessions, longest {}, {} in total",
+        self.named(address),
+        lengths.len(),
+        dates::duration(lengths.iter().copied().max().unwrap_or_default()),
+        dates::duration(lengths.iter().sum())
+      );
+    }
+  }
+
+  /// Prints the WAN link's ups and downs in order, with how long each connection lasted and when the address the
+  /// router was given changed. Entries without a timestamp can't be placed and are left out.
+  fn report_connections(&self) {
+    let mut timeline = self
+      .connections
+      .iter()
+      .filter_map(|connection| Some((connection.timestamp?, connection)))
+      .collect::<Vec<_>>();
+    timeline.sort_by_key(|(timestamp, _)| *timestamp);
+
+    println!("internet connection timeline:");
+
+    let mut up_since = None;
+    let mut address = None;
+
+    for (timestamp, connection) in timeline {
+      if !connection.connected {
+        let lasted = up_since
+          .take()
+          .map(|since: DateTime| format!(" after {}", dates::duration(timestamp.seconds() - since.seconds())))
+          .unwrap_or_default();
+        println!("{timestamp}: disconnected{lasted}");
+        continue;
+      }
+
+      up_since.get_or_insert(timestamp);
+
+      let changed = match (address, connection.address.as_deref()) {
+        (Some(previous), Some(current)) if previous != current => format!(" (was {previous})"),
+        _ => String::new(),
+      };
+      address = connection.address.as_deref().or(address);
+
+      println!(
+        "{timestamp}: connected as {}{changed}",
+        connection.address.as_deref().unwrap_or("unknown")
+      );
+    }
+  }
+}
+
+/// The sources in `counts`, busiest first.
+fn busiest(counts: &HashMap<String, u32>) -> Vec<(&String, u32)> {
+  let mut sources = counts
+    .iter()
+    .map(|(address, count)| (address, *count))
+    .collect::<Vec<_>>();
+  sources.sort_by_key(|(address, count)| (std::cmp::Reverse(*count), address.as_str()));
+  sources
+}
+
+/// Prints the accesses of each group, busiest first.
+fn report_groups<'a, G>(name: &str, groups: G)
+where
+  G: Iterator<Item = (String, &'a Tally)>,
+{
+  let mut groups = groups.collect::<Vec<_>>();
+  groups.sort_by_key(|(_, tally)| std::cmp::Reverse(tally.accesses));
+
+  println!("accesses by {name} ({}):", groups.len());
+
+  for (key, tally) in groups {
+    println!("{key}: {} from {} sources", tally.accesses, tally.sources.len());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::logs::{AdminLogin, Origin, RemoteAccess};
+
+  fn access(address: &str, minute: u8) -> LogEvent {
+    access_at(address, &format!("2022-01-03 08:{minute:02}:00"))
+  }
+
+  fn access_at(address: &str, timestamp: &str) -> LogEvent {
+    LogEvent::RemoteAccess(RemoteAccess {
+      address: address.into(),
+      port: Some(40000),
+      local_address: "192.168.1.1".into(),
+      local_port: Some(22),
+      protocol: None,
+      timestamp: DateTime::parse_iso(timestamp),
+      origin: Origin::default(),
+    })
+  }
+
+  fn exported(name: &str, contents: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rupert-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+  }
+
+  #[test]
+  fn leaves_out_every_event_from_an_ignored_address() {
+    let mut summary = Summary::new(ReportSettings {
+      ignored: vec!["7.7.7.0/24".parse().unwrap()],
+      ..ReportSettings::default()
+    });
+    summary.record(access("7.7.7.7", 0));
+    summary.record(LogEvent::AdminLogin(AdminLogin {
+      address: "7.7.7.8".into(),
+      failed: true,
+      timestamp: None,
+      origin: Origin::default(),
+    }));
+    summary.record(access("8.8.8.8", 1));
+
+    assert_eq!(summary.events, 1);
+    assert!(summary.logins.is_empty());
+  }
+
+  #[test]
+  fn imports_what_output_json_wrote() {
+    let mut summary = Summary::new(ReportSettings::default());
+    for minute in 0..3 {
+      summary.record(access("7.7.7.7", minute));
+    }
+    summary.record(access("8.8.8.8", 9));
+
+    let path = exported("report.json", &format!("{:#}", summary.document(1)));
+    let mut imported = Summary::new(ReportSettings::default());
+    assert!(imported.import(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(imported.accesses.len(), 2);
+    assert_eq!(imported.accesses["7.7.7.7"].accesses, 3);
+    assert_eq!(imported.accesses["8.8.8.8"].accesses, 1);
+    assert_eq!(imported.accesses["7.7.7.7"].seen, summary.accesses["7.7.7.7"].seen);
+  }
+
+  #[test]
+  fn imports_what_output_csv_wrote() {
+    let header = COLUMNS.map(|(column, _, _)| column).join(",");
+    let path = exported(
+      "report.csv",
+      &format!("{header}\n7.7.7.7,30,2022-01-03 08:00:00,2022-01-03 08:07:00,20,\"Somewhere, Else\"\n"),
+    );
+    let mut imported = Summary::new(ReportSettings::default());
+    assert!(imported.import(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    let source = &imported.accesses["7.7.7.7"];
+    assert_eq!(source.accesses, 30);
+    assert_eq!(
+      source.seen.map(|(first, _)| first.to_string()).as_deref(),
+      Some("2022-01-03 08:00:00")
+    );
+  }
+
+  #[test]
+  fn leaves_logs_to_be_parsed() {
+    let path = exported(
+      "router.log",
+      "[LAN access from remote] from 7.7.7.7:40000 to 192.168.1.1:22\n",
+    );
+    let mut imported = Summary::new(ReportSettings::default());
+    assert!(!imported.import(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert!(imported.accesses.is_empty());
+  }
+
+  fn at(timestamp: &str) -> DateTime {
+    DateTime::parse_iso(timestamp).unwrap()
+  }
+
+  #[test]
+  fn weeks_start_on_monday() {
+    for (timestamp, start) in [
+      ("2022-01-03 00:00:00", "2022-01-03 00:00:00"),
+      ("2022-01-09 23:59:59", "2022-01-03 00:00:00"),
+      ("2022-01-02 12:00:00", "2021-12-27 00:00:00"),
+      ("2022-03-01 08:00:00", "2022-02-28 00:00:00"),
+    ] {
+      assert_eq!(Rollup::Week.start(at(timestamp)), at(start), "{timestamp}");
+    }
+
+    assert_eq!(
+      Rollup::Week.previous(at("2022-01-03 00:00:00")),
+      at("2021-12-27 00:00:00")
+    );
+    assert_eq!(Rollup::Week.label(at("2021-12-27 00:00:00")), "week of 2021-12-27");
+  }
+
+  #[test]
+  fn months_start_on_the_first() {
+    assert_eq!(
+      Rollup::Month.start(at("2022-03-31 23:59:59")),
+      at("2022-03-01 00:00:00")
+    );
+    assert_eq!(
+      Rollup::Month.previous(at("2022-03-01 00:00:00")),
+      at("2022-02-01 00:00:00")
+    );
+    assert_eq!(
+      Rollup::Month.previous(at("2022-01-01 00:00:00")),
+      at("2021-12-01 00:00:00")
+    );
+    assert_eq!(Rollup::Month.label(at("2021-12-01 00:00:00")), "2021-12");
+  }
+
+  #[test]
+  fn bursts_are_windows_over_five_times_the_median_before_them() {
+    let bursts = |last: &str| {
+      let mut summary = Summary::new(ReportSettings::default());
+      for timestamp in ["2022-01-03 07:00:00", "2022-01-03 07:59:59"] {
+        summary.record(access_at("8.8.8.8", timestamp));
+      }
+      for _ in 0..10 {
+        summary.record(access_at("7.7.7.7", "2022-01-03 08:00:00"));
+      }
+      summary.record(access_at("7.7.7.7", last));
+      summary.bursts()
+    };
+    let eight = at("2022-01-03 08:00:00").seconds();
+
+    // The last access is just inside the hour, making 11 accesses against a median of 2, or just outside it.
+    assert_eq!(bursts("2022-01-03 08:59:59"), [(eight, 11, 2)]);
+    assert_eq!(bursts("2022-01-03 09:00:00"), []);
+  }
+
+  #[test]
+  fn sessions_split_on_gaps_longer_than_the_threshold() {
+    let source = Source {
+      times: vec![600, 0, 300, 901, 1201],
+      ..Source::default()
+    };
+
+    // 300 seconds apart is still the same session; 301 starts a new one.
+    assert_eq!(source.sessions(300), [(0, 600), (901, 1201)]);
+    assert_eq!(source.sessions(301), [(0, 1201)]);
+    assert_eq!(
+      source.sessions(0),
+      [(0, 0), (300, 300), (600, 600), (901, 901), (1201, 1201)]
+    );
+    assert!(Source::default().sessions(300).is_empty());
+  }
+
+  #[test]
+  fn percentiles_are_by_nearest_rank() {
+    let written = |values: &[i64]| percentiles(&mut values.to_vec(), |value| value.to_string());
+
+    assert_eq!(written(&[]), None);
+    assert_eq!(written(&[7]).as_deref(), Some("p50 7, p90 7, p99 7"));
+    assert_eq!(written(&[40, 10, 30, 20]).as_deref(), Some("p50 20, p90 40, p99 40"));
+    assert_eq!(
+      written(&[10, 9, 8, 7, 6, 5, 4, 3, 2, 1]).as_deref(),
+      Some("p50 5, p90 9, p99 10")
+    );
+  }
+}
@@ -0,0 +1,179 @@
+use std::io::Result;
+
+use async_std::channel;
+use async_std::io::prelude::BufReadExt;
+use async_std::stream::StreamExt;
+
+use crate::logs::{self, LogEvent};
+
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:514";
+
+const MAX_DATAGRAM_SIZE: usize = 8192;
+
+/// Pulls the router's log entry out of a syslog message. Rather than fully parsing the RFC 3164/5424 header we
+/// drop the `<PRI>` and treat everything from the first `[` onwards as the entry, which is where every Netgear
+/// log line begins. Other formats are read with their header, which holds their timestamp.
+pub fn entry(raw: &str, format: logs::Format) -> Option<&str> {
+  let raw = raw.trim_end_matches(['\r', '\n', '\0']);
+  let body = raw
+    .strip_prefix('<')
+    .and_then(|rest| rest.split_once('>'))
+    .map(|(_, rest)| rest)
+    .unwrap_or(raw);
+
+  match format {
+    logs::Format::Netgear => body.find('[').map(|start| &body[start..]),
+    _ => Some(body).filter(|body| !body.is_empty()),
+  }
+}
+
+async fn receive(
+  raw: &str,
+  peer: std::net::SocketAddr,
+  grammar: &logs::Grammar,
+  unmatched: Option<&logs::Unmatched>,
+  output: &channel::Sender<LogEvent>,
+) -> Result<()> {
+  // The listener runs indefinitely, so unrecognized entries are written out (or dropped) rather than accumulated.
+  let mut peripheral = Vec::new();
+
+  if let Some(entry) = entry(raw, grammar.format) {
+    let context = logs::LineContext::live(format!("syslog from {peer}").into(), None, grammar.clone());
+
+    // A message `--strict` rejects is reported on its own; only losing whoever reads the events ends the listener.
+    if let Err(error) = logs::parse_line(entry, &context, output, &mut peripheral).await {
+      match output.is_closed() {
+        true => return Err(error),
+        false => eprintln!("WARNING - {error}"),
+      }
+    }
+  }
+
+  match unmatched {
+    Some(unmatched) => unmatched.drain(&mut peripheral),
+    None => Ok(()),
+  }
+}
+
+async fn receive_datagrams(
+  socket: async_std::net::UdpSocket,
+  grammar: logs::Grammar,
+  unmatched: Option<logs::Unmatched>,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+
+  loop {
+    let (size, peer) = socket.recv_from(&mut buffer).await?;
+    receive(
+      &String::from_utf8_lossy(&buffer[..size]),
+      peer,
+      &grammar,
+      unmatched.as_ref(),
+      &output,
+    )
+    .await?;
+  }
+}
+
+/// TCP syslog is expected to use newline ("non-transparent") framing, one message per line.
+async fn receive_stream(
+  stream: async_std::net::TcpStream,
+  grammar: logs::Grammar,
+  unmatched: Option<logs::Unmatched>,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  let peer = stream.peer_addr()?;
+  let mut lines = async_std::io::BufReader::new(stream).lines();
+
+  while let Some(line) = lines.next().await {
+    let line = line.map_err(|error| {
+      eprintln!("WARNING - dropping syslog connection from {peer} - {error}");
+      error
+    })?;
+    receive(&line, peer, &grammar, unmatched.as_ref(), &output).await?;
+  }
+
+  Ok(())
+}
+
+/// Accepts syslog messages over both UDP and TCP on `address` until either listener fails, returning its error.
+pub async fn listen(
+  address: String,
+  grammar: logs::Grammar,
+  unmatched: Option<logs::Unmatched>,
+  output: channel::Sender<LogEvent>,
+) -> Result<()> {
+  let socket = async_std::net::UdpSocket::bind(&address).await?;
+  let listener = async_std::net::TcpListener::bind(&address).await?;
+
+  eprintln!("listening for syslog on '{address}' (udp + tcp)");
+
+  let datagrams = receive_datagrams(socket, grammar.clone(), unmatched.clone(), output.clone());
+  let streams = async {
+    let mut incoming = listener.incoming();
+
+    while let Some(stream) = incoming.next().await {
+      async_std::task::spawn(receive_stream(
+        stream?,
+        grammar.clone(),
+        unmatched.clone(),
+        output.clone(),
+      ));
+    }
+
+    Ok(())
+  };
+
+  futures_lite::future::or(datagrams, streams).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn entries_start_where_the_format_does() {
+    let line = "<134>Jan  3 08:00:00 router [admin login] from source 1.2.3.4, Monday, Jan 03,2022 08:00:00\r\n\0";
+    assert_eq!(
+      entry(line, logs::Format::Netgear),
+      Some("[admin login] from source 1.2.3.4, Monday, Jan 03,2022 08:00:00")
+    );
+    assert_eq!(
+      entry("<134>Jan  3 08:00:00 router started", logs::Format::Netgear),
+      None
+    );
+
+    let line = "<30>Jan  3 08:00:00 router dnsmasq[42]: DHCPACK(br0) 192.168.1.5 aa:bb:cc:dd:ee:ff\n";
+    assert_eq!(
+      entry(line, logs::Format::GenericSyslog),
+      Some("Jan  3 08:00:00 router dnsmasq[42]: DHCPACK(br0) 192.168.1.5 aa:bb:cc:dd:ee:ff")
+    );
+    assert_eq!(entry("<30>\r\n", logs::Format::GenericSyslog), None);
+  }
+
+  #[test]
+  fn strict_rejections_only_end_the_listener_once_nothing_reads_its_events() {
+    let grammar = logs::Grammar {
+      strict: true,
+      ..logs::Grammar::default()
+    };
+    let peer = "192.168.1.1:514".parse().unwrap();
+    let (output, events) = channel::unbounded();
+
+    async_std::task::block_on(async {
+      assert!(
+        receive("<134>[LAN access from remote] garbage", peer, &grammar, None, &output)
+          .await
+          .is_ok()
+      );
+
+      events.close();
+      assert!(
+        receive("<134>[LAN access from remote] garbage", peer, &grammar, None, &output)
+          .await
+          .is_err()
+      );
+    });
+  }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+use std::io::{Error, Result};
+use std::net::IpAddr;
+
+use async_std::io::ReadExt;
+
+use crate::web;
+
+/// Reads the addresses of Tor exit relays (`--tor`) from a file or URL, e.g. the list the Tor Project publishes
+/// at <https://check.torproject.org/torbulkexitlist>. Either the bulk list of one address per line or the `exit-addresses` document with its `ExitAddress` lines is understood;
+/// anything else on a line is skipped.
+pub async fn load(source: &str) -> Result<HashSet<IpAddr>> {
+  let invalid = |error: Error| Error::other(format!("invalid '--tor' '{source}' - {error}"));
+  let mut contents = String::new();
+
+  if web::is_url(source) {
+    let download = web::get(source).await.map_err(invalid)?;
+    let reader = async_std::io::BufReader::new(download.body);
+
+    match download.compressed {
+      true => async_compression::futures::bufread::GzipDecoder::new(reader)
+        .read_to_string(&mut contents)
+        .await
+        .map_err(invalid)?,
+      false => async_std::io::BufReader::new(reader)
+        .read_to_string(&mut contents)
+        .await
+        .map_err(invalid)?,
+    };
+  } else {
+    contents = async_std::fs::read_to_string(source).await.map_err(invalid)?;
+  }
+
+  let exits = contents
+    .lines()
+    .filter_map(|line| {
+      let mut fields = line.split_whitespace();
+      match fields.next()? {
+        "ExitAddress" => fields.next()?.parse().ok(),
+        address => address.parse().ok(),
+      }
+    })
+    .collect::<HashSet<_>>();
+
+  match exits.is_empty() {
+    true => Err(invalid(Error::other("no exit addresses"))),
+    false => Ok(exits),
+  }
+}
@@ -0,0 +1,542 @@
+use crate::dates::DateTime;
+use crate::logs::{self, AdminLogin, Attack, DhcpLease, DosAttack, Format, InternetConnection, LineContext, LogEvent};
+use crate::logs::{RemoteAccess, TimeSync};
+
+type Parsed = Option<std::result::Result<LogEvent, &'static str>>;
+
+/// The parts of a syslog message: when it was written, the program that wrote it and what it said.
+struct Message<'a> {
+  timestamp: Option<DateTime>,
+  program: &'a str,
+  text: &'a str,
+}
+
+/// Splits the first word off `value`, however many spaces follow it (e.g. the day in `Jan  3`).
+fn word(value: &str) -> Option<(&str, &str)> {
+  let (word, rest) = value.trim_start().split_once(' ')?;
+  Some((word, rest.trim_start()))
+}
+
+/// The word following `marker` in `text`, without any punctuation around it, e.g. the address in
+/// `... from: 1.2.3.4 (Local Database)`.
+fn after<'a>(text: &'a str, marker: &str) -> Option<&'a str> {
+  let (_, rest) = text.split_once(marker)?;
+  rest
+    .split_whitespace()
+    .next()
+    .map(|word| word.trim_matches([',', '.', '(', ')', '\'']))
+    .filter(|word| !word.is_empty())
+}
+
+/// Reads an RFC 5424 (`1 2022-01-03T08:00:00Z host app pid id - text`) or RFC 3164
+/// (`Jan  3 08:00:00 host app[pid]: text`) syslog header, after any `<PRI>`. The host is optional since routers
+/// leave it out of their own log files.
+fn message<'a>(line: &'a str, context: &LineContext) -> Option<Message<'a>> {
+  let line = line
+    .strip_prefix('<')
+    .and_then(|rest| rest.split_once('>'))
+    .map_or(line, |(_, rest)| rest);
+
+  if let Some(rest) = line.strip_prefix("1 ") {
+    let (stamp, rest) = word(rest)?;
+    let (_host, rest) = word(rest)?;
+    let (program, rest) = word(rest)?;
+    let (_pid, rest) = word(rest)?;
+    let (_id, rest) = word(rest)?;
+    let text = match rest.starts_with('[') {
+      true => rest.split_once("] ").map_or("", |(_, text)| text),
+      false => rest.strip_prefix('-').unwrap_or(rest),
+    };
+
+    return Some(Message {
+      timestamp: DateTime::parse_iso(stamp),
+      program,
+      text: text.trim_start(),
+    });
+  }
+
+  let (month, rest) = word(line)?;
+  let (day, rest) = word(rest)?;
+  let (clock, rest) = word(rest)?;
+  // Without a year (or a message date to take one from) the timestamp can't be read, but the message still can.
+  let timestamp = DateTime::parse_log(month, &format!("{day},{clock}"), context.received);
+
+  if !clock.contains(':') {
+    return None;
+  }
+
+  // Whatever comes before the first `: ` is the host (if there is one) and the tag, e.g. `router dnsmasq[42]`.
+  let (tag, text) = rest.split_once(": ")?;
+  let program = tag.rsplit(' ').next().unwrap_or(tag);
+
+  Some(Message {
+    timestamp,
+    program: program.split('[').next().unwrap_or(program),
+    text,
+  })
+}
+
+/// Reads an iptables log message, e.g. `ACCEPT IN=eth0 OUT=br0 SRC=1.2.3.4 DST=192.168.1.10 ... SPT=51234 DPT=22`.
+/// The text before `IN=` is whatever prefix the rule was given, and packets it says were dropped aren't accesses.
+fn netfilter(text: &str, timestamp: Option<DateTime>, context: &LineContext) -> Parsed {
+  let (prefix, fields) = text.split_once("IN=")?;
+  let prefix = prefix.to_ascii_uppercase();
+
+  if ["DROP", "REJECT", "BLOCK", "DENY"]
+    .iter()
+    .any(|verdict| prefix.contains(verdict))
+  {
+    return None;
+  }
+
+  let field = |key: &str| {
+    fields
+      .split_whitespace()
+      .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+  };
+  let port = |key: &str| field(key).and_then(|port| port.parse().ok());
+
+  let access = field("SRC").zip(field("DST")).map(|(source, destination)| {
+    LogEvent::RemoteAccess(RemoteAccess {
+      address: logs::host_port(source).0,
+      port: port("SPT"),
+      local_address: logs::host_port(destination).0,
+      local_port: port("DPT"),
+      protocol: field("PROTO").map(str::to_ascii_uppercase),
+      timestamp,
+      origin: context.origin.clone(),
+    })
+  });
+
+  Some(access.ok_or("access"))
+}
+
+/// Reads dnsmasq's `DHCPACK(br0) 192.168.1.5 aa:bb:cc:dd:ee:ff [name]`.
+fn dnsmasq(text: &str, timestamp: Option<DateTime>, context: &LineContext) -> Parsed {
+  let rest = text.strip_prefix("DHCPACK(")?;
+  let lease = match &rest.split_whitespace().collect::<Vec<&str>>()[..] {
+    [_interface, address, mac, ..] => Ok(LogEvent::DhcpLease(DhcpLease {
+      address: address.to_string(),
+      mac: mac.to_ascii_lowercase(),
+      timestamp,
+      origin: context.origin.clone(),
+    })),
+    _ => Err("dhcp"),
+  };
+
+  Some(lease)
+}
+
+/// Reads sshd's `Accepted password for root from 1.2.3.4 port 22 ssh2` (or `publickey`), and `Failed password`.
+fn sshd(text: &str, timestamp: Option<DateTime>, context: &LineContext) -> Parsed {
+  let failed = match text.split(' ').next() {
+    Some("Accepted") => false,
+    Some("Failed") => true,
+    _ => return None,
+  };
+
+  let login = after(text, " from ").map(|address| {
+    LogEvent::AdminLogin(AdminLogin {
+      address: logs::host_port(address).0,
+      failed,
+      timestamp,
+      origin: context.origin.clone(),
+    })
+  });
+
+  Some(login.ok_or("admin login"))
+}
+
+/// The messages any Linux based router may log, whatever else its firmware adds.
+fn generic(message: &Message, context: &LineContext) -> Parsed {
+  match message.program {
+    "kernel" => netfilter(message.text, message.timestamp, context),
+    "sshd" | "dropbear" => sshd(message.text, message.timestamp, context),
+    program if program.starts_with("dnsmasq") => dnsmasq(message.text, message.timestamp, context),
+    _ => None,
+  }
+}
+
+/// Asus (Asuswrt) logs iptables and dnsmasq messages, plus its own `WAN(0) Connection: WAN was restored.`.
+fn asus(message: &Message, context: &LineContext) -> Parsed {
+  if message.program != "Connection" {
+    return generic(message, context);
+  }
+
+  let text = message.text.to_ascii_lowercase();
+  let connected = match ["down", "not", "fail", "disconnected"]
+    .iter()
+    .any(|problem| text.contains(problem))
+  {
+    true => false,
+    false if text.contains("restored") || text.contains("connected") => true,
+    false => return None,
+  };
+
+  Some(Ok(LogEvent::InternetConnection(InternetConnection {
+    connected,
+    address: None,
+    timestamp: message.timestamp,
+    origin: context.origin.clone(),
+  })))
+}
+
+/// Reads pfSense's `filterlog` CSV, e.g. `5,,,1000000103,igb0,match,pass,in,4,0x0,,64,0,0,DF,6,tcp,60,SRC,DST,
+/// SPORT,DPORT,...`, whose fields after the IP version differ between IPv4 and IPv6. Only passed, inbound packets
+/// are accesses.
+fn filterlog(text: &str, timestamp: Option<DateTime>, context: &LineContext) -> Parsed {
+  let fields = text.split(',').collect::<Vec<&str>>();
+  let (action, direction, rest) = match &fields[..] {
+    [_rule, _sub, _anchor, _tracker, _interface, _reason, action, direction, rest @ ..] => (*action, *direction, rest),
+    _ => return Some(Err("filterlog")),
+  };
+
+  if action != "pass" || direction != "in" {
+    return None;
+  }
+
+  let (protocol, source, destination, ports) = match rest {
+    ["4", _tos, _ecn, _ttl, _id, _offset, _flags, _number, protocol, _length, source, destination, ports @ ..] => {
+      (*protocol, *source, *destination, ports)
+    }
+    ["6", _class, _label, _hops, protocol, _number, _length, source, destination, ports @ ..] => {
+      (*protocol, *source, *destination, ports)
+    }
+    _ => return Some(Err("filterlog")),
+  };
+
+  let (port, local_port) = match (protocol, ports) {
+    ("tcp" | "udp", [port, local_port, ..]) => (port.parse().ok(), local_port.parse().ok()),
+    _ => (None, None),
+  };
+
+  Some(Ok(LogEvent::RemoteAccess(RemoteAccess {
+    address: logs::host_port(source).0,
+    port,
+    local_address: logs::host_port(destination).0,
+    local_port,
+    protocol: Some(protocol.to_ascii_uppercase()),
+    timestamp,
+    origin: context.origin.clone(),
+  })))
+}
+
+/// pfSense logs its firewall through `filterlog`, leases through ISC `dhcpd`
+/// (`DHCPACK on 192.168.1.20 to aa:bb:cc:dd:ee:ff (name) via igb1`) and web interface logins and WAN address
+/// changes through PHP.
+fn pfsense(message: &Message, context: &LineContext) -> Parsed {
+  let (text, timestamp) = (message.text, message.timestamp);
+
+  match message.program {
+    "filterlog" => filterlog(text, timestamp, context),
+    "dhcpd" if text.starts_with("DHCPACK on ") => {
+      let lease = after(text, "DHCPACK on ")
+        .zip(after(text, " to "))
+        .map(|(address, mac)| {
+          LogEvent::DhcpLease(DhcpLease {
+            address: address.to_string(),
+            mac: mac.to_ascii_lowercase(),
+            timestamp,
+            origin: context.origin.clone(),
+          })
+        });
+      Some(lease.ok_or("dhcp"))
+    }
+    "php" | "php-fpm" | "php-cgi" if text.contains("Successful login") || text.contains("authentication error") => {
+      let login = after(text, " from: ").map(|address| {
+        LogEvent::AdminLogin(AdminLogin {
+          address: logs::host_port(address).0,
+          failed: text.contains("authentication error"),
+          timestamp,
+          origin: context.origin.clone(),
+        })
+      });
+      Some(login.ok_or("admin login"))
+    }
+    "php" | "php-fpm" | "php-cgi" if text.contains("rc.newwanip: on") => {
+      Some(Ok(LogEvent::InternetConnection(InternetConnection {
+        connected: true,
+        address: after(text, "(IP address: ").map(str::to_string),
+        timestamp,
+        origin: context.origin.clone(),
+      })))
+    }
+    _ => generic(message, context),
+  }
+}
+
+/// TP-Link's log, as shown on (and exported from) its System Log page, is `DATE TIME MODULE LEVEL text`, e.g.
+/// `2022-01-03 08:00:00 SECURITY WARNING Detected TCP SYN Flood attack from 1.2.3.4`. Over syslog the date is in
+/// the header instead.
+fn tplink(line: &str, context: &LineContext) -> Parsed {
+  let (date, rest) = word(line)?;
+  let (clock, rest) = word(rest)?;
+
+  let (timestamp, rest) = match DateTime::parse_iso(&format!("{date} {clock}")) {
+    Some(timestamp) => (Some(timestamp), rest),
+    None => {
+      let message = message(line, context)?;
+      (message.timestamp, message.text)
+    }
+  };
+
+  let (module, rest) = word(rest)?;
+  let (_level, text) = word(rest)?;
+  let lowered = text.to_ascii_lowercase();
+
+  let event = match module {
+    "SECURITY" | "DOS" if lowered.contains("attack") => {
+      let (kind, address) = lowered
+        .find(" from ")
+        .map(|start| (&text[..start], after(text, " from ")))?;
+      let kind = kind
+        .split(' ')
+        .filter(|word| !word.eq_ignore_ascii_case("detected") && !word.eq_ignore_ascii_case("attack"))
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+      address
+        .map(|address| {
+          LogEvent::DosAttack(DosAttack {
+            kind: Attack::parse(&kind),
+            address: logs::host_port(address).0,
+            timestamp,
+            origin: context.origin.clone(),
+          })
+        })
+        .ok_or("dos attack")
+    }
+    "HTTPD" | "WEB" if lowered.contains("login") => after(text, " from ")
+      .map(|address| {
+        LogEvent::AdminLogin(AdminLogin {
+          address: logs::host_port(address).0,
+          failed: lowered.contains("fail"),
+          timestamp,
+          origin: context.origin.clone(),
+        })
+      })
+      .ok_or("admin login"),
+    "WAN" | "PPP" | "DHCPC" if lowered.contains("connect") || lowered.contains("down") => {
+      Ok(LogEvent::InternetConnection(InternetConnection {
+        connected: !lowered.contains("disconnect") && !lowered.contains("down"),
+        address: after(text, "IP ").or_else(|| after(text, "ip ")).map(str::to_string),
+        timestamp,
+        origin: context.origin.clone(),
+      }))
+    }
+    "NTP" | "TIME" if lowered.contains("synchron") || lowered.contains("success") => Ok(LogEvent::TimeSync(TimeSync {
+      server: after(text, "server ").map(str::to_string),
+      timestamp,
+      origin: context.origin.clone(),
+    })),
+    "DHCPS" if text.starts_with("Send ACK to ") => {
+      let mac = after(text, "MAC ").map(|mac| mac.replace('-', ":").to_ascii_lowercase());
+      after(text, "Send ACK to ")
+        .zip(mac)
+        .map(|(address, mac)| {
+          LogEvent::DhcpLease(DhcpLease {
+            address: address.to_string(),
+            mac,
+            timestamp,
+            origin: context.origin.clone(),
+          })
+        })
+        .ok_or("dhcp")
+    }
+    _ => return None,
+  };
+
+  Some(event)
+}
+
+/// Reads the event a line describes in the context's format, for routers that log over syslog rather than in
+/// Netgear's `[TAG] details, timestamp` entries. Lines that aren't anything we count (e.g. traffic the firewall
+/// blocked) give `None`, and are left among the peripheral lines.
+pub fn parse_event(line: &str, context: &LineContext) -> Parsed {
+  match context.grammar.format {
+    Format::TpLink => tplink(line, context),
+    Format::Asus => asus(&message(line, context)?, context),
+    Format::Pfsense => pfsense(&message(line, context)?, context),
+    Format::GenericSyslog => generic(&message(line, context)?, context),
+    Format::Netgear => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn context(format: Format) -> LineContext {
+    LineContext {
+      received: DateTime::parse_iso("2022-01-04 09:00:00"),
+      grammar: logs::Grammar {
+        format,
+        ..logs::Grammar::default()
+      },
+      ..LineContext::default()
+    }
+  }
+
+  /// The event `line` describes, failing the test when it isn't read as one.
+  fn event(format: Format, line: &str) -> LogEvent {
+    match parse_event(line, &context(format)) {
+      Some(Ok(event)) => event,
+      _ => panic!("unread: {line}"),
+    }
+  }
+
+  fn monday() -> Option<DateTime> {
+    DateTime::parse_iso("2022-01-03 08:00:00")
+  }
+
+  #[test]
+  fn reads_rfc5424_and_rfc3164_headers() {
+    let context = context(Format::GenericSyslog);
+
+    for (line, program, text) in [
+      (
+        "<34>1 2022-01-03T08:00:00.003Z router sshd 42 ID47 - Accepted password for root",
+        "sshd",
+        "Accepted password for root",
+      ),
+      (
+        "1 2022-01-03T08:00:00Z router app - - [origin ip=\"192.168.1.1\"] restarted",
+        "app",
+        "restarted",
+      ),
+      (
+        "<30>Jan  3 08:00:00 router dnsmasq[42]: DHCPACK(br0)",
+        "dnsmasq",
+        "DHCPACK(br0)",
+      ),
+      ("Jan 3 08:00:00 kernel: ACCEPT IN=eth0", "kernel", "ACCEPT IN=eth0"),
+    ] {
+      let message = message(line, &context).expect(line);
+      assert_eq!(
+        (message.program, message.text, message.timestamp),
+        (program, text, monday()),
+        "{line}"
+      );
+    }
+
+    assert!(message("Jan 3 router kernel: ACCEPT IN=eth0", &context).is_none());
+  }
+
+  #[test]
+  fn reads_netfilter_accesses_but_not_drops() {
+    let line = "Jan  3 08:00:00 router kernel: [WAN] ACCEPT IN=eth0 OUT=br0 MAC=00:11:22:33:44:55 SRC=1.2.3.4 \
+                DST=192.168.1.10 LEN=60 PROTO=TCP SPT=51234 DPT=22";
+    let LogEvent::RemoteAccess(access) = event(Format::GenericSyslog, line) else {
+      panic!("{line}");
+    };
+    assert_eq!(
+      (
+        access.address.as_str(),
+        access.port,
+        access.local_address.as_str(),
+        access.local_port
+      ),
+      ("1.2.3.4", Some(51234), "192.168.1.10", Some(22))
+    );
+    assert_eq!((access.protocol.as_deref(), access.timestamp), (Some("TCP"), monday()));
+
+    let dropped = "Jan  3 08:00:00 router kernel: DROP IN=eth0 OUT= SRC=1.2.3.4 DST=192.168.1.10 PROTO=TCP";
+    assert!(parse_event(dropped, &context(Format::GenericSyslog)).is_none());
+  }
+
+  #[test]
+  fn reads_dnsmasq_leases() {
+    let line = "Jan  3 08:00:00 router dnsmasq-dhcp[42]: DHCPACK(br0) 192.168.1.5 AA:BB:CC:DD:EE:FF laptop";
+    let LogEvent::DhcpLease(lease) = event(Format::GenericSyslog, line) else {
+      panic!("{line}");
+    };
+    assert_eq!(
+      (lease.address.as_str(), lease.mac.as_str()),
+      ("192.168.1.5", "aa:bb:cc:dd:ee:ff")
+    );
+  }
+
+  #[test]
+  fn reads_sshd_logins() {
+    let line = "Jan  3 08:00:00 router sshd[42]: Failed password for root from 1.2.3.4 port 22 ssh2";
+    let LogEvent::AdminLogin(login) = event(Format::GenericSyslog, line) else {
+      panic!("{line}");
+    };
+    assert_eq!((login.address.as_str(), login.failed), ("1.2.3.4", true));
+
+    let closed = "Jan  3 08:00:00 router sshd[42]: Connection closed by 1.2.3.4 port 22";
+    assert!(parse_event(closed, &context(Format::GenericSyslog)).is_none());
+  }
+
+  #[test]
+  fn reads_asus_connections() {
+    for (line, up) in [
+      ("Jan  3 08:00:00 WAN(0) Connection: WAN was restored.", true),
+      ("Jan  3 08:00:00 WAN(0) Connection: WAN(0) link down.", false),
+    ] {
+      let LogEvent::InternetConnection(connection) = event(Format::Asus, line) else {
+        panic!("{line}");
+      };
+      assert_eq!((connection.connected, connection.timestamp), (up, monday()), "{line}");
+    }
+  }
+
+  #[test]
+  fn reads_passed_inbound_filterlog_packets_of_either_ip_version() {
+    for (fields, address, local_address) in [
+      (
+        "5,,,1000000103,igb0,match,pass,in,4,0x0,,64,0,0,DF,6,tcp,60,1.2.3.4,192.168.1.10,51234,22,0,S,1,,64240,,mss",
+        "1.2.3.4",
+        "192.168.1.10",
+      ),
+      (
+        "5,,,1000000103,igb0,match,pass,in,6,0x00,0x00000,64,tcp,6,40,2001:db8::1,2001:db8::10,51234,22,0,S,1,,64800,,",
+        "2001:db8::1",
+        "2001:db8::10",
+      ),
+    ] {
+      let line = format!("Jan  3 08:00:00 pfsense filterlog[1234]: {fields}");
+      let LogEvent::RemoteAccess(access) = event(Format::Pfsense, &line) else {
+        panic!("{line}");
+      };
+      assert_eq!(
+        (
+          access.address.as_str(),
+          access.port,
+          access.local_address.as_str(),
+          access.local_port
+        ),
+        (address, Some(51234), local_address, Some(22)),
+        "{line}"
+      );
+      assert_eq!(access.protocol.as_deref(), Some("TCP"));
+    }
+
+    for fields in [
+      "5,,,1000000103,igb0,match,block,in,4,0x0,,64,0,0,DF,6,tcp,60,1.2.3.4,192.168.1.10,51234,22,0,S,1,,64240,,mss",
+      "5,,,1000000103,igb0,match,reject,in,4,0x0,,64,0,0,DF,17,udp,60,1.2.3.4,192.168.1.10,51234,53,40",
+      "5,,,1000000103,igb0,match,pass,out,4,0x0,,64,0,0,DF,6,tcp,60,192.168.1.10,1.2.3.4,51234,443,0,S,1,,64240,,",
+    ] {
+      let line = format!("Jan  3 08:00:00 pfsense filterlog[1234]: {fields}");
+      assert!(parse_event(&line, &context(Format::Pfsense)).is_none(), "{line}");
+    }
+
+    let truncated = "Jan  3 08:00:00 pfsense filterlog[1234]: 5,,,1000000103,igb0,match,pass,in,4,0x0";
+    assert!(matches!(
+      parse_event(truncated, &context(Format::Pfsense)),
+      Some(Err("filterlog"))
+    ));
+  }
+
+  #[test]
+  fn reads_tplink_attacks() {
+    let line = "2022-01-03 08:00:00 SECURITY WARNING Detected TCP SYN Flood attack from 1.2.3.4";
+    let LogEvent::DosAttack(attack) = event(Format::TpLink, line) else {
+      panic!("{line}");
+    };
+    assert_eq!(
+      (attack.kind, attack.address.as_str(), attack.timestamp),
+      (Attack::SynFlood, "1.2.3.4", monday())
+    );
+  }
+}
@@ -0,0 +1,135 @@
+use std::io::{Error, Result};
+use std::net::IpAddr;
+
+use async_std::channel;
+use mail_auth::spf::verify::SpfParameters;
+use mail_auth::{AuthenticatedMessage, DkimResult, MessageAuthenticator, SpfResult};
+
+use crate::email::Headers;
+
+/// Stands in for our own hostname in SPF macros, which are the only place it's used.
+const HOST_DOMAIN: &str = "localhost";
+
+/// The outcome of checking a message.
+#[derive(Debug)]
+pub enum Verdict {
+  Pass,
+  Fail(String),
+}
+
+/// The SMTP details SPF is checked against, recovered from the `Received` header added on delivery.
+#[derive(Debug)]
+pub struct Envelope {
+  ip: IpAddr,
+  helo: String,
+  sender: String,
+}
+
+impl Envelope {
+  /// Reads the topmost `Received` header, e.g. `from mail.example.com (mail.example.com [203.0.113.5]) by ...`,
+  /// taking the sender from `Return-Path` (or `From`).
+  pub fn from_headers(head: &Headers) -> Option<Self> {
+    let received = head.get("Received")?;
+    let helo = received.split_whitespace().skip_while(|word| *word != "from").nth(1)?;
+    let (_, address) = received.split_once('[')?;
+    let (address, _) = address.split_once(']')?;
+    let ip = address.trim_start_matches("IPv6:").parse().ok()?;
+
+    let sender = head.get("Return-Path").or_else(|| head.get("From"))?;
+    let sender = sender
+      .rsplit_once('<')
+      .and_then(|(_, rest)| rest.split_once('>'))
+      .map_or(sender, |(address, _)| address);
+
+    Some(Self {
+      ip,
+      helo: helo.to_string(),
+      sender: sender.trim().to_string(),
+    })
+  }
+}
+
+struct Request {
+  raw: Vec<u8>,
+  envelope: Option<Envelope>,
+  reply: channel::Sender<Verdict>,
+}
+
+/// Checks messages on a dedicated thread, since the resolver they are checked with needs its own (tokio) runtime.
+#[derive(Debug, Clone)]
+pub struct Verifier {
+  requests: std::sync::mpsc::Sender<Request>,
+  spf: bool,
+}
+
+async fn check(authenticator: &MessageAuthenticator, raw: &[u8], envelope: Option<Envelope>, spf: bool) -> Verdict {
+  let Some(message) = AuthenticatedMessage::parse(raw) else {
+    return Verdict::Fail("unreadable message".into());
+  };
+
+  let outputs = authenticator.verify_dkim(&message).await;
+
+  if !outputs.iter().any(|output| output.result() == &DkimResult::Pass) {
+    let reason = outputs
+      .first()
+      .map(|output| output.result().to_string())
+      .unwrap_or_else(|| "no signature".into());
+    return Verdict::Fail(format!("dkim - {reason}"));
+  }
+
+  if !spf {
+    return Verdict::Pass;
+  }
+
+  let Some(envelope) = envelope else {
+    return Verdict::Fail("spf - no usable 'Received' header".into());
+  };
+
+  let output = authenticator
+    .verify_spf(SpfParameters::verify_mail_from(
+      envelope.ip,
+      &envelope.helo,
+      HOST_DOMAIN,
+      &envelope.sender,
+    ))
+    .await;
+
+  match output.result() {
+    SpfResult::Pass => Verdict::Pass,
+    other => Verdict::Fail(format!("spf - {other} for {}", envelope.ip)),
+  }
+}
+
+/// Starts the verification thread, using the system's DNS configuration for key and policy lookups.
+pub fn start(spf: bool) -> Result<Verifier> {
+  let authenticator =
+    MessageAuthenticator::new_system_conf().map_err(|error| Error::other(format!("verify - {error}")))?;
+  let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+  let (requests, receiver) = std::sync::mpsc::channel::<Request>();
+
+  std::thread::spawn(move || {
+    while let Ok(request) = receiver.recv() {
+      let verdict = runtime.block_on(check(&authenticator, &request.raw, request.envelope, spf));
+      let _ = async_std::task::block_on(request.reply.send(verdict));
+    }
+  });
+
+  Ok(Verifier { requests, spf })
+}
+
+impl Verifier {
+  /// Checks the DKIM signature of a whole raw message and, if enabled, its SPF record.
+  pub async fn verify(&self, raw: Vec<u8>, head: &Headers) -> Verdict {
+    let (reply, verdict) = channel::bounded(1);
+    let envelope = self.spf.then(|| Envelope::from_headers(head)).flatten();
+
+    if self.requests.send(Request { raw, envelope, reply }).is_err() {
+      return Verdict::Fail("verification stopped".into());
+    }
+
+    verdict
+      .recv()
+      .await
+      .unwrap_or_else(|_| Verdict::Fail("verification stopped".into()))
+  }
+}
@@ -0,0 +1,281 @@
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+pub const MAILDIR_LEAVES: [&str; 2] = ["cur", "new"];
+const MAILDIR_TMP: &str = "tmp";
+const MAILDIR_INFO_DELIM: char = ':';
+const MAILDIR_INFO_VERSION: &str = "2,";
+const MAILDIR_TRASHED_FLAG: char = 'T';
+
+/// A `--exclude` pattern, matched against both the path relative to the input and the bare name so that
+/// `Thumbs.db` or `*.sig` apply at any depth. A trailing `/` (e.g. `spam/`) only excludes directories.
+#[derive(Debug)]
+pub struct Exclusion {
+  pattern: glob::Pattern,
+  directory: bool,
+}
+
+impl std::str::FromStr for Exclusion {
+  type Err = glob::PatternError;
+
+  fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+    let trimmed = value.trim_end_matches('/');
+
+    Ok(Self {
+      pattern: glob::Pattern::new(trimmed)?,
+      directory: trimmed.len() != value.len(),
+    })
+  }
+}
+
+impl Exclusion {
+  fn matches(&self, relative: &Path, directory: bool) -> bool {
+    if self.directory && !directory {
+      return false;
+    }
+
+    let options = glob::MatchOptions {
+      require_literal_separator: true,
+      ..glob::MatchOptions::new()
+    };
+
+    self.pattern.matches_path_with(relative, options)
+      || relative
+        .file_name()
+        .is_some_and(|name| self.pattern.matches_with(&name.to_string_lossy(), options))
+  }
+}
+
+/// Walks input directories, producing the list of files that should be parsed.
+#[derive(Debug, Default)]
+pub struct Walker {
+  pub recursive: bool,
+  pub glob: Option<glob::Pattern>,
+  pub dates: Option<crate::dates::FilenameDates>,
+  pub exclude: Vec<Exclusion>,
+
+  /// Whether symlinked files and directories are followed; when they are, directories already walked (by their
+  /// canonical path) are skipped so a link back up the tree can't loop forever.
+  pub follow_symlinks: bool,
+  maildir_ids: std::collections::HashSet<PathBuf>,
+}
+
+/// A directory is treated as a maildir when it has all of the `cur`, `new` and `tmp` subdirectories.
+pub fn is_maildir<P>(path: P) -> bool
+where
+  P: std::convert::AsRef<Path>,
+{
+  MAILDIR_LEAVES
+    .iter()
+    .chain(std::iter::once(&MAILDIR_TMP))
+    .all(|leaf| path.as_ref().join(leaf).is_dir())
+}
+
+/// Splits a maildir filename into its unique part and (version 2) flags, e.g. `1650000000.M1P2.host:2,RS`.
+fn maildir_name(name: &str) -> (&str, Option<&str>) {
+  match name.rsplit_once(MAILDIR_INFO_DELIM) {
+    Some((unique, info)) => (unique, info.strip_prefix(MAILDIR_INFO_VERSION)),
+    None => (name, None),
+  }
+}
+
+impl Walker {
+  fn matches<P>(&self, relative: P) -> bool
+  where
+    P: std::convert::AsRef<Path>,
+  {
+    let options = glob::MatchOptions {
+      require_literal_separator: true,
+      ..glob::MatchOptions::new()
+    };
+
+    self
+      .glob
+      .as_ref()
+      .map(|pattern| pattern.matches_path_with(relative.as_ref(), options))
+      .unwrap_or(true)
+  }
+
+  /// Whether `path` (or, for files, any directory between it and `root`) was excluded.
+  fn excluded(&self, root: &Path, path: &Path, directory: bool) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+      return false;
+    };
+
+    let mut ancestors = relative.ancestors().filter(|ancestor| !ancestor.as_os_str().is_empty());
+    let own = ancestors
+      .next()
+      .is_some_and(|own| self.exclude.iter().any(|rule| rule.matches(own, directory)));
+
+    own || ancestors.any(|ancestor| self.exclude.iter().any(|rule| rule.matches(ancestor, true)))
+  }
+
+  fn skip_symlink(&self, path: &Path) -> bool {
+    let symlink = path
+      .symlink_metadata()
+      .is_ok_and(|metadata| metadata.file_type().is_symlink());
+
+    if symlink && !self.follow_symlinks {
+      eprintln!("skipping symlink '{path:?}'");
+      return true;
+    }
+
+    false
+  }
+
+  fn accept_entry(&mut self, root: &Path, path: &Path, leaf: bool) -> bool {
+    let mut relative = match path.strip_prefix(root) {
+      Ok(relative) => relative.to_path_buf(),
+      Err(_) => return false,
+    };
+
+    if self.excluded(root, path, false) {
+      return false;
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if self.dates.as_ref().is_some_and(|dates| !dates.accept(&name)) {
+      return false;
+    }
+
+    if leaf {
+      let (unique, flags) = maildir_name(&name);
+
+      if flags.is_some_and(|flags| flags.contains(MAILDIR_TRASHED_FLAG)) {
+        return false;
+      }
+
+      // Messages move from `new` to `cur` (gaining an info suffix) as they are read; keying on the unique
+      // part keeps a message caught mid-move from being counted twice.
+      let id = path
+        .parent()
+        .and_then(Path::parent)
+        .map(|maildir| maildir.join(unique))
+        .unwrap_or_default();
+
+      if !self.maildir_ids.insert(id) {
+        return false;
+      }
+
+      relative.set_file_name(unique);
+    }
+
+    self.matches(&relative)
+  }
+
+  /// Decides whether a single file found under `root` outside of a full walk (e.g. one that has just appeared
+  /// while watching) should be parsed, applying the same maildir and glob rules as `walk`.
+  pub fn accept(&mut self, root: &Path, path: &Path) -> bool {
+    let parent = path.parent();
+    let maildir = parent.and_then(Path::parent).filter(|maildir| is_maildir(maildir));
+    let leaf_name = parent.and_then(Path::file_name);
+
+    if maildir.is_some() && leaf_name.is_some_and(|name| name == MAILDIR_TMP) {
+      return false;
+    }
+
+    let leaf = maildir.is_some() && leaf_name.is_some_and(|name| MAILDIR_LEAVES.iter().any(|leaf| name == *leaf));
+    !self.skip_symlink(path) && self.accept_entry(root, path, leaf)
+  }
+
+  pub fn walk(&mut self, root: PathBuf) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::with_capacity(100);
+
+    // Each pending directory is paired with whether it is the `cur`/`new` leaf of a maildir.
+    let mut pending = vec![(root.clone(), false)];
+    let mut visited = std::collections::HashSet::new();
+
+    while let Some((dir, leaf)) = pending.pop() {
+      // One directory that can't be read (e.g. a dangling symlink, or no permission) is skipped rather than ending
+      // the walk; only the root itself has to be.
+      let unreadable = |error: std::io::Error| match dir == root {
+        true => Err(error),
+        false => {
+          eprintln!("WARNING - skipping '{dir:?}' - {error}");
+          Ok(())
+        }
+      };
+
+      let canonical = match dir.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(error) => {
+          unreadable(error)?;
+          continue;
+        }
+      };
+
+      if !visited.insert(canonical) {
+        eprintln!("skipping '{dir:?}' (already scanned)");
+        continue;
+      }
+
+      if !leaf && is_maildir(&dir) {
+        eprintln!("scanning maildir '{dir:?}'");
+        pending.extend(MAILDIR_LEAVES.iter().map(|name| (dir.join(name), true)));
+
+        // Anything other than the maildir leaves themselves (e.g. maildir++ `.Folder` directories) is only
+        // considered when recursing.
+        if self.recursive {
+          let entries = match dir.read_dir() {
+            Ok(entries) => entries,
+            Err(error) => {
+              unreadable(error)?;
+              continue;
+            }
+          };
+
+          for entry in entries.flatten() {
+            let name = entry.file_name();
+            let reserved = MAILDIR_LEAVES
+              .iter()
+              .chain(std::iter::once(&MAILDIR_TMP))
+              .any(|leaf| name == *leaf);
+
+            if !reserved
+              && entry.path().is_dir()
+              && !self.excluded(&root, &entry.path(), true)
+              && !self.skip_symlink(&entry.path())
+            {
+              pending.push((entry.path(), false));
+            }
+          }
+        }
+
+        continue;
+      }
+
+      let mut entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(error) => {
+          unreadable(error)?;
+          continue;
+        }
+      };
+
+      eprintln!("scanning '{dir:?}'");
+
+      while let Some(Ok(entry)) = entries.next() {
+        if self.skip_symlink(&entry.path()) {
+          continue;
+        }
+
+        if entry.path().is_dir() {
+          if self.recursive && !leaf && !self.excluded(&root, &entry.path(), true) {
+            pending.push((entry.path(), false));
+          }
+
+          continue;
+        }
+
+        if !self.accept_entry(&root, &entry.path(), leaf) {
+          continue;
+        }
+
+        eprintln!("checking '{:?}'", entry.file_name());
+        files.push(entry.path());
+      }
+    }
+
+    Ok(files)
+  }
+}
@@ -0,0 +1,75 @@
+use std::io::{Error, Result};
+use std::path::PathBuf;
+
+use async_std::channel;
+use notify::Watcher;
+
+use crate::walk;
+
+fn notify_error(error: notify::Error) -> Error {
+  Error::other(format!("watch - {error}"))
+}
+
+/// Keeps the underlying filesystem watcher alive for as long as new paths are being received.
+pub struct Watch {
+  _watcher: notify::RecommendedWatcher,
+  pub paths: channel::Receiver<PathBuf>,
+}
+
+/// Only files that are finished being written are reported: either closed after writing, or renamed into place
+/// (which is how maildir delivery moves messages out of `tmp`).
+fn completed(kind: &notify::EventKind) -> bool {
+  use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+
+  matches!(
+    kind,
+    notify::EventKind::Access(AccessKind::Close(AccessMode::Write))
+      | notify::EventKind::Modify(ModifyKind::Name(RenameMode::To))
+  )
+}
+
+fn hidden(path: &std::path::Path) -> bool {
+  path
+    .file_name()
+    .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+pub fn watch(roots: &[PathBuf], recursive: bool) -> Result<Watch> {
+  let (sender, receiver) = channel::unbounded();
+
+  let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+    Ok(event) if completed(&event.kind) => {
+      // Dotfiles are the conventional name for a copy that is still in flight (e.g. rsync), which will be
+      // renamed into place once it is complete.
+      for path in event.paths.into_iter().filter(|path| !hidden(path)) {
+        let _ = sender.try_send(path);
+      }
+    }
+    Ok(_) => (),
+    Err(error) => eprintln!("WARNING - {error}"),
+  })
+  .map_err(notify_error)?;
+
+  let mode = match recursive {
+    true => notify::RecursiveMode::Recursive,
+    false => notify::RecursiveMode::NonRecursive,
+  };
+
+  for root in roots {
+    // Without recursion a maildir root would never see deliveries, which land in its `new` and `cur` leaves.
+    let dirs = match !recursive && walk::is_maildir(root) {
+      true => walk::MAILDIR_LEAVES.iter().map(|leaf| root.join(leaf)).collect(),
+      false => vec![root.clone()],
+    };
+
+    for dir in dirs {
+      eprintln!("watching '{dir:?}'");
+      watcher.watch(&dir, mode).map_err(notify_error)?;
+    }
+  }
+
+  Ok(Watch {
+    _watcher: watcher,
+    paths: receiver,
+  })
+}
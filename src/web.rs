@@ -0,0 +1,56 @@
+use std::io::{Error, Result};
+
+const SCHEMES: [&str; 2] = ["http://", "https://"];
+const GZIP_ENCODING: &str = "gzip";
+const MAX_REDIRECTS: usize = 5;
+
+pub fn is_url(input: &str) -> bool {
+  SCHEMES.iter().any(|scheme| input.starts_with(scheme))
+}
+
+fn http_error<E>(error: E) -> Error
+where
+  E: std::fmt::Display,
+{
+  Error::other(format!("http - {error}"))
+}
+
+/// A successful response whose body is ready to be streamed into the parser.
+pub struct Download {
+  pub body: surf::Response,
+
+  /// Whether the server sent the body gzip-compressed (`Content-Encoding: gzip`).
+  pub compressed: bool,
+}
+
+pub async fn get(url: &str) -> Result<Download> {
+  let mut location = surf::Url::parse(url).map_err(http_error)?;
+
+  for _ in 0..MAX_REDIRECTS {
+    let body = surf::get(location.clone())
+      .header("Accept-Encoding", GZIP_ENCODING)
+      .await
+      .map_err(http_error)?;
+
+    if body.status().is_redirection() {
+      if let Some(next) = body.header("Location") {
+        location = location.join(next.as_str()).map_err(http_error)?;
+        continue;
+      }
+    }
+
+    if !body.status().is_success() {
+      return Err(http_error(format!("'{location}' responded with {}", body.status())));
+    }
+
+    let compressed = body.header("Content-Encoding").is_some_and(|values| {
+      values
+        .iter()
+        .any(|value| value.as_str().eq_ignore_ascii_case(GZIP_ENCODING))
+    });
+
+    return Ok(Download { body, compressed });
+  }
+
+  Err(http_error(format!("too many redirects from '{url}'")))
+}
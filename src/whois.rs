@@ -0,0 +1,180 @@
+use async_std::io::{ReadExt, WriteExt};
+
+/// Where every query starts, which refers it on to the registry (ARIN, RIPE...) holding the address.
+const ROOT_SERVER: &str = "whois.iana.org";
+const PORT: u16 = 43;
+
+/// How long each server is given to answer.
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// What the registry says about the network an address is in, for filing an abuse report.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+  pub netname: Option<String>,
+  pub organization: Option<String>,
+  pub abuse: Option<String>,
+}
+
+impl std::fmt::Display for Record {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let fields = [
+      ("netname", &self.netname),
+      ("org", &self.organization),
+      ("abuse", &self.abuse),
+    ];
+    let fields = fields
+      .iter()
+      .filter_map(|(name, value)| Some(format!("{name} {}", value.as_deref()?)))
+      .collect::<Vec<_>>();
+
+    match fields.is_empty() {
+      true => formatter.write_str("nothing found"),
+      false => formatter.write_str(&fields.join(", ")),
+    }
+  }
+}
+
+/// Sends `query` to `server`, returning the whole answer.
+async fn ask(server: &str, query: &str) -> std::io::Result<String> {
+  let exchange = async {
+    let mut stream = async_std::net::TcpStream::connect((server, PORT)).await?;
+    stream.write_all(format!("{query}\r\n").as_bytes()).await?;
+
+    let mut answer = Vec::new();
+    stream.read_to_end(&mut answer).await?;
+    Ok(String::from_utf8_lossy(&answer).into_owned())
+  };
+
+  async_std::io::timeout(TIMEOUT, exchange).await
+}
+
+/// Reads the first of `names` (compared without case) from the `Key: value` lines of an answer.
+fn field(answer: &str, names: &[&str]) -> Option<String> {
+  names.iter().find_map(|name| {
+    answer.lines().find_map(|line| {
+      let (key, value) = line.split_once(':')?;
+      let value = value.trim();
+      (key.trim().eq_ignore_ascii_case(name) && !value.is_empty()).then(|| value.to_string())
+    })
+  })
+}
+
+/// Reads a record out of an answer. The registries name their fields differently: ARIN's `NetName`, `OrgName` and
+/// `OrgAbuseEmail` are RIPE's (and APNIC's, AFRINIC's) `netname`, `org-name` (or `descr`) and `abuse-mailbox`, which
+/// RIPE also gives in a `% Abuse contact for ... is 'abuse@...'` comment. Fields holding a handle for another object
+/// (`abuse-c`, `organisation`) are left out, since a handle is no use to whoever files the report.
+fn parse(answer: &str) -> Record {
+  let comment = answer.lines().find_map(|line| {
+    let (_, contact) = line.strip_prefix("% Abuse contact for")?.split_once(" is '")?;
+    Some(contact.trim_end_matches('\'').to_string())
+  });
+
+  Record {
+    netname: field(answer, &["NetName", "netname"]),
+    organization: field(answer, &["OrgName", "org-name", "owner", "descr"]),
+    abuse: comment.or_else(|| field(answer, &["OrgAbuseEmail", "abuse-mailbox"])),
+  }
+}
+
+/// Looks `address` up, following the root server's referral; `None` if neither could be reached.
+pub async fn lookup(address: &str) -> Option<Record> {
+  let root = ask(ROOT_SERVER, address)
+    .await
+    .map_err(|error| eprintln!("WARNING - whois for '{address}' failed - {error}"))
+    .ok()?;
+
+  let answer = match field(&root, &["refer", "whois"]) {
+    Some(server) => ask(&server, address)
+      .await
+      .map_err(|error| eprintln!("WARNING - whois for '{address}' at '{server}' failed - {error}"))
+      .ok()?,
+    None => root,
+  };
+
+  Some(parse(&answer))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(netname: &str, organization: &str, abuse: Option<&str>) -> Record {
+    Record {
+      netname: Some(netname.into()),
+      organization: Some(organization.into()),
+      abuse: abuse.map(str::to_string),
+    }
+  }
+
+  #[test]
+  fn reads_arin_answers() {
+    let answer = "\
+#
+# ARIN WHOIS data and services are subject to the Terms of Use
+#
+
+NetRange:       8.8.8.0 - 8.8.8.255
+CIDR:           8.8.8.0/24
+NetName:        GOGL
+Organization:   Google LLC (GOGL)
+
+OrgName:        Google LLC
+OrgId:          GOGL
+OrgAbuseHandle: ABUSE5250-ARIN
+OrgAbuseEmail:  network-abuse@google.com
+";
+
+    assert_eq!(
+      parse(answer),
+      record("GOGL", "Google LLC", Some("network-abuse@google.com"))
+    );
+  }
+
+  #[test]
+  fn reads_ripe_answers() {
+    let answer = "\
+% This is the RIPE Database query service.
+
+% Abuse contact for '193.0.0.0 - 193.0.7.255' is 'abuse@ripe.net'
+
+inetnum:        193.0.0.0 - 193.0.7.255
+netname:        RIPE-NCC
+descr:          RIPE Network Coordination Centre
+org:            ORG-RIEN1-RIPE
+abuse-c:        ABUSE3850-RIPE
+
+organisation:   ORG-RIEN1-RIPE
+org-name:       Reseaux IP Europeens Network Coordination Centre (RIPE NCC)
+";
+
+    assert_eq!(
+      parse(answer),
+      record(
+        "RIPE-NCC",
+        "Reseaux IP Europeens Network Coordination Centre (RIPE NCC)",
+        Some("abuse@ripe.net")
+      )
+    );
+
+    // Without the comment (or an `abuse-mailbox`) there's no contact to give, only the handle of one.
+    let answer = "netname:        EXAMPLE-NET\ndescr:          Example Hosting\nabuse-c:        AR1234-RIPE\n";
+    assert_eq!(parse(answer), record("EXAMPLE-NET", "Example Hosting", None));
+
+    let answer = "netname:  EXAMPLE-NET\ndescr:\nabuse-mailbox:  abuse@example.net\n";
+    assert_eq!(
+      parse(answer),
+      Record {
+        netname: Some("EXAMPLE-NET".into()),
+        organization: None,
+        abuse: Some("abuse@example.net".into()),
+      }
+    );
+  }
+
+  #[test]
+  fn reads_referrals() {
+    let answer = "% IANA WHOIS server\n\nrefer:        whois.ripe.net\n\ninetnum:      193.0.0.0 - 193.255.255.255\n";
+    assert_eq!(field(answer, &["refer", "whois"]).as_deref(), Some("whois.ripe.net"));
+    assert_eq!(parse("% nothing here\n").to_string(), "nothing found");
+  }
+}